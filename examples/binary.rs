@@ -0,0 +1,161 @@
+//! A decoder for a small binary protocol built on `rlrl`, demonstrating
+//! parsing tokens that aren't text. The frames of a message are the tokens,
+//! and each knows the bytes it spans, so errors are reported at byte
+//! offsets.
+//!
+//! A frame is a kind byte, a length byte, and that many payload bytes. A
+//! message is a `Begin` frame (kind 1) whose payload is a one byte id, any
+//! number of `Data` frames (kind 2), and an `End` frame (kind 3) whose
+//! payload is the sum of the data bytes, modulo 256.
+//!
+//! Run it with `cargo run --example binary -- <file>`.
+use rlrl::parse::{ParseError, ParseResult, TokenQueue};
+use rlrl::span::{HasSpan, Span};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Kind {
+    Begin,
+    Data,
+    End,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+struct Frame {
+    kind: Kind,
+    payload: Vec<u8>,
+    span: Span,
+}
+
+impl HasSpan for Frame {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct Message {
+    id: u8,
+    data: Vec<u8>,
+}
+
+/// Split `bytes` into frames, the tokens of the message.
+fn frames(bytes: &[u8]) -> Result<Vec<Frame>, String> {
+    let mut frames = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let kind = match bytes[pos] {
+            1 => Kind::Begin,
+            2 => Kind::Data,
+            3 => Kind::End,
+            kind => return Err(format!("Unknown frame kind {kind} at {pos}!")),
+        };
+        let payload = bytes
+            .get(pos + 1)
+            .and_then(|len| bytes.get(pos + 2..pos + 2 + *len as usize))
+            .ok_or(format!("Truncated frame at {pos}!"))?;
+        let end = pos + 2 + payload.len();
+        frames.push(Frame {
+            kind,
+            payload: payload.to_vec(),
+            span: Span::new(pos, end),
+        });
+        pos = end;
+    }
+    Ok(frames)
+}
+
+fn parse_message(tq: &TokenQueue<Frame>) -> ParseResult<Message> {
+    let mut tq = tq.clone();
+
+    let begin = tq.consume_map("a begin frame", |frame| {
+        (frame.kind == Kind::Begin).then(|| frame.payload.clone())
+    })?;
+    let [id] = begin[..] else {
+        return Err(ParseError::at(
+            tq.get_idx() - 1,
+            "Expected a one byte id!",
+        ));
+    };
+
+    let mut data = Vec::new();
+    while let Ok(payload) = tq.consume_map("a data frame", |frame| {
+        (frame.kind == Kind::Data).then(|| frame.payload.clone())
+    }) {
+        data.extend(payload);
+    }
+
+    let end = tq.consume_map("a data or end frame", |frame| {
+        (frame.kind == Kind::End).then(|| frame.payload.clone())
+    })?;
+    let checksum = data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+    if end[..] != [checksum] {
+        return Err(ParseError::at(tq.get_idx() - 1, "Checksum mismatch!"));
+    }
+
+    Ok((Message { id, data }, tq.get_idx()))
+}
+
+/// Decode a message, reporting errors at the bytes of the frame they
+/// occurred at.
+fn decode(bytes: &[u8]) -> Result<Message, String> {
+    let mut tq = TokenQueue::from(frames(bytes)?);
+    let message = tq.parse(parse_message).map_err(|err| {
+        format!("{} (at {})", err.message(), tq.error_span(&err))
+    })?;
+    if !tq.is_consumed() {
+        let err = ParseError::at(tq.get_idx(), "Expected the end of input!");
+        return Err(format!("{} (at {})", err.message(), tq.error_span(&err)));
+    }
+    Ok(message)
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or(anyhow::anyhow!("Usage: binary <file>"))?;
+    match decode(&std::fs::read(&path)?) {
+        Ok(message) => println!("message {}: {:?}", message.id, message.data),
+        Err(err) => println!("error: {err}"),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Message, decode};
+
+    #[test]
+    fn test_decode() {
+        let bytes = [1, 1, 7, 2, 2, 10, 20, 2, 1, 30, 3, 1, 60];
+        assert!(
+            decode(&bytes)
+                == Ok(Message {
+                    id: 7,
+                    data: vec![10, 20, 30]
+                })
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        // the checksum should be 30
+        let bytes = [1, 1, 7, 2, 1, 30, 3, 1, 31];
+        assert!(decode(&bytes) == Err("Checksum mismatch! (at 6..9)".into()));
+
+        let bytes = [1, 1, 7, 1, 1, 8];
+        assert!(
+            decode(&bytes)
+                == Err("Expected a data or end frame! (at 3..6)".into())
+        );
+
+        let bytes = [1, 1, 7, 2, 1, 30];
+        assert!(
+            decode(&bytes)
+                == Err("Expected a data or end frame! (at 6..6)".into())
+        );
+
+        assert!(
+            decode(&[1, 1, 7, 2, 5]) == Err("Truncated frame at 3!".into())
+        );
+    }
+}
@@ -0,0 +1,239 @@
+//! A calculator built on `rlrl`: the crate's end-to-end example.
+//!
+//! The lexer turns a line of input into [Token]s, a Pratt parser turns the
+//! tokens into an [Expr] with the usual precedence and associativity, and
+//! [Expr::eval] evaluates it.
+//!
+//! ```text
+//! $ echo "-(1 + 2) * 2 ^ 3 ^ 2" | cargo run --example calculator
+//! -1536
+//! ```
+use rlrl::prelude::*;
+use std::io::BufRead;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    LParen,
+    RParen,
+    Num(f64),
+}
+
+fn setup_lexer() -> Lexer<Token> {
+    let mut lexer = Lexer::new();
+
+    lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+
+    lexer.add_rule(r"\+", |_| LexResult::Token(Token::Add));
+    lexer.add_rule(r"\-", |_| LexResult::Token(Token::Sub));
+    lexer.add_rule(r"\*", |_| LexResult::Token(Token::Mul));
+    lexer.add_rule(r"/", |_| LexResult::Token(Token::Div));
+    lexer.add_rule(r"\^", |_| LexResult::Token(Token::Pow));
+    lexer.add_rule(r"\(", |_| LexResult::Token(Token::LParen));
+    lexer.add_rule(r"\)", |_| LexResult::Token(Token::RParen));
+
+    // no leading `-`: negation is parsed as a unary operator
+    lexer.add_try_rule(r"[0-9]+(?:\.[0-9]+)?", |re_match| {
+        Ok(Some(Token::Num(re_match.as_str().parse()?)))
+    });
+
+    lexer
+}
+
+/// A binary operator.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+impl Op {
+    fn from_token(token: &Token) -> Option<Self> {
+        match token {
+            Token::Add => Some(Self::Add),
+            Token::Sub => Some(Self::Sub),
+            Token::Mul => Some(Self::Mul),
+            Token::Div => Some(Self::Div),
+            Token::Pow => Some(Self::Pow),
+            _ => None,
+        }
+    }
+
+    /// Get the (left, right) binding power of the operator. Higher binds
+    /// tighter; a right power above the left makes the operator
+    /// left-associative, and below makes it right-associative.
+    fn binding_power(&self) -> (u8, u8) {
+        match self {
+            Self::Add | Self::Sub => (1, 2),
+            Self::Mul | Self::Div => (3, 4),
+            Self::Pow => (8, 7),
+        }
+    }
+
+    fn apply(&self, lhs: f64, rhs: f64) -> f64 {
+        match self {
+            Self::Add => lhs + rhs,
+            Self::Sub => lhs - rhs,
+            Self::Mul => lhs * rhs,
+            Self::Div => lhs / rhs,
+            Self::Pow => lhs.powf(rhs),
+        }
+    }
+}
+
+/// Binding power of unary minus: tighter than `*`, looser than `^`, so
+/// `-2 ^ 2` is `-(2 ^ 2)`.
+const NEG_BINDING_POWER: u8 = 5;
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Num(f64),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse an expression from the token queue.
+    fn parse(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let expr = tq.parse_with(Expr::parse_bp, &0)?;
+        Ok((expr, tq.get_idx()))
+    }
+
+    /// Parse an expression containing only operators whose left binding
+    /// power is at least `min_bp`.
+    fn parse_bp(tq: &TokenQueue<Token>, min_bp: &u8) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let mut lhs = match tq.consume_cloned()? {
+            Token::Num(val) => Expr::Num(val),
+            Token::Sub => {
+                let operand =
+                    tq.parse_with(Expr::parse_bp, &NEG_BINDING_POWER)?;
+                Expr::Neg(operand.into())
+            }
+            Token::LParen => {
+                let expr = tq.parse(Expr::parse)?;
+                tq.consume_eq(Token::RParen)?;
+                expr
+            }
+            token => {
+                return Err(ParseError::new(format!(
+                    "Expected a number, `-`, or `(` but found {token:?}!"
+                )));
+            }
+        };
+
+        while let Some(op) = tq.peek().ok().and_then(Op::from_token) {
+            let (left_bp, right_bp) = op.binding_power();
+            if left_bp < *min_bp {
+                break;
+            }
+            tq.increment()?;
+            let rhs = tq.parse_with(Expr::parse_bp, &right_bp)?;
+            lhs = Expr::Binary(op, lhs.into(), rhs.into());
+        }
+
+        Ok((lhs, tq.get_idx()))
+    }
+
+    /// Evaluate the expression.
+    fn eval(&self) -> f64 {
+        match self {
+            Self::Num(val) => *val,
+            Self::Neg(operand) => -operand.eval(),
+            Self::Binary(op, lhs, rhs) => op.apply(lhs.eval(), rhs.eval()),
+        }
+    }
+}
+
+/// Lex, parse, and evaluate `s`, requiring all of it to be consumed.
+fn eval_str(s: &str) -> anyhow::Result<f64> {
+    let mut tq = TokenQueue::from(setup_lexer().lex(s)?);
+    let expr = tq.parse(Expr::parse)?;
+    if !tq.is_consumed() {
+        return Err(ParseError::expected("an operator", &tq).into());
+    }
+    Ok(expr.eval())
+}
+
+fn main() -> anyhow::Result<()> {
+    for line in std::io::stdin().lock().lines() {
+        match eval_str(&line?) {
+            Ok(val) => println!("{val}"),
+            Err(err) => println!("error: {err}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lexer_test() -> anyhow::Result<()> {
+        let l = setup_lexer();
+
+        let toks = l.lex("5 + 6")?;
+        assert!(toks == vec![Token::Num(5.0), Token::Add, Token::Num(6.0)]);
+
+        let toks = l.lex("5-6")?;
+        assert!(toks == vec![Token::Num(5.0), Token::Sub, Token::Num(6.0)]);
+
+        assert!(l.lex("5 & 6").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_test() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(setup_lexer().lex("5 - 6 * 2")?);
+        let expr = tq.parse(Expr::parse)?;
+
+        assert!(
+            expr == Expr::Binary(
+                Op::Sub,
+                Expr::Num(5.0).into(),
+                Expr::Binary(
+                    Op::Mul,
+                    Expr::Num(6.0).into(),
+                    Expr::Num(2.0).into()
+                )
+                .into()
+            )
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn eval_test() -> anyhow::Result<()> {
+        // precedence
+        assert!(eval_str("5 + 6 * 2")? == 17.0);
+        assert!(eval_str("5 * 6 + 2")? == 32.0);
+        // left associativity
+        assert!(eval_str("5 - 6 - 2")? == -3.0);
+        assert!(eval_str("8 / 4 / 2")? == 1.0);
+        // right associativity
+        assert!(eval_str("2 ^ 3 ^ 2")? == 512.0);
+        // parentheses and unary minus
+        assert!(eval_str("(5 - 6) * 2")? == -2.0);
+        assert!(eval_str("--3")? == 3.0);
+        assert!(eval_str("-2 ^ 2")? == -4.0);
+        assert!(eval_str("2 * -3")? == -6.0);
+
+        assert!(eval_str("(1 + 2").is_err());
+        assert!(eval_str("1 + 2)").is_err());
+        assert!(eval_str("1 +").is_err());
+
+        Ok(())
+    }
+}
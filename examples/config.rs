@@ -0,0 +1,309 @@
+//! An INI/TOML-like config language built on `rlrl`, demonstrating error
+//! recovery: a malformed line is reported as a [Diagnostic] and parsing
+//! resumes on the next line, so one run reports every error in the file.
+//!
+//! ```text
+//! # comments run to the end of the line
+//! [server]
+//! host = "localhost"
+//! port = 8080
+//! debug = true
+//! ```
+//!
+//! Run it with `cargo run --example config -- <file>`.
+use rlrl::diagnostic::Diagnostic;
+use rlrl::prelude::*;
+use rlrl::source::SourceFile;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Newline,
+    LBracket,
+    RBracket,
+    Eq,
+    Ident(String),
+    Str(String),
+    Int(i64),
+    Bool(bool),
+    /// Any character no other rule matches, so that lexing never fails and
+    /// the parser can report it in context.
+    Unknown(char),
+}
+
+fn setup_lexer() -> Lexer<Token> {
+    let mut lexer = Lexer::new();
+
+    lexer.add_rule(r"[ \t\r]+", |_| LexResult::Ignore);
+    lexer.add_rule(r"#[^\n]*", |_| LexResult::Ignore);
+
+    lexer.add_rule(r"\n", |_| LexResult::Token(Token::Newline));
+    lexer.add_rule(r"\[", |_| LexResult::Token(Token::LBracket));
+    lexer.add_rule(r"\]", |_| LexResult::Token(Token::RBracket));
+    lexer.add_rule(r"=", |_| LexResult::Token(Token::Eq));
+
+    lexer.add_rule(r"[A-Za-z_][A-Za-z0-9_.-]*", |re_match| {
+        match re_match.as_str() {
+            "true" => LexResult::Token(Token::Bool(true)),
+            "false" => LexResult::Token(Token::Bool(false)),
+            ident => LexResult::Token(Token::Ident(ident.to_owned())),
+        }
+    });
+    lexer.add_rule(r#""[^"\n]*""#, |re_match| {
+        let s = re_match.as_str();
+        LexResult::Token(Token::Str(s[1..s.len() - 1].to_owned()))
+    });
+    lexer.add_try_rule(r"-?[0-9]+", |re_match| {
+        Ok(Some(Token::Int(re_match.as_str().parse()?)))
+    });
+
+    // added last so single-character rules above take priority
+    lexer.add_rule(r"[^\s]", |re_match| {
+        LexResult::Token(Token::Unknown(
+            re_match.as_str().chars().next().unwrap(),
+        ))
+    });
+
+    lexer
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Value {
+    Str(String),
+    Int(i64),
+    Bool(bool),
+}
+
+#[derive(Debug, PartialEq)]
+enum Line {
+    Section(String),
+    Entry(String, Value),
+}
+
+type Tokens = TokenQueue<Spanned<Token>>;
+
+/// Build a diagnostic for the front token of `tq` (or the end of input,
+/// `eof`).
+fn expected(tq: &Tokens, what: &str, eof: Span) -> Diagnostic {
+    match tq.peek() {
+        Ok(token) => Diagnostic::error(
+            format!("expected {what}, found {:?}", token.value),
+            token.span,
+        ),
+        Err(_) => Diagnostic::error(format!("expected {what}"), eof),
+    }
+}
+
+impl Line {
+    /// Parse one line (without its newline). On failure, return a
+    /// diagnostic rather than an error so the caller can recover.
+    fn parse(tq: &Tokens, eof: Span) -> Result<(Self, usize), Diagnostic> {
+        let mut tq = tq.clone();
+
+        let line = match tq.peek().map(|token| token.value.clone()) {
+            Ok(Token::LBracket) => {
+                tq.increment().ok();
+                let Ok(Token::Ident(name)) =
+                    tq.peek().map(|token| token.value.clone())
+                else {
+                    return Err(expected(&tq, "a section name", eof));
+                };
+                tq.increment().ok();
+                if tq.consume_eq(Token::RBracket).is_err() {
+                    return Err(expected(&tq, "`]`", eof));
+                }
+                Line::Section(name)
+            }
+            Ok(Token::Ident(key)) => {
+                tq.increment().ok();
+                if tq.consume_eq(Token::Eq).is_err() {
+                    return Err(expected(&tq, "`=`", eof)
+                        .with_note("entries are written `key = value`"));
+                }
+                let value = match tq.peek().map(|token| token.value.clone()) {
+                    Ok(Token::Str(s)) => Value::Str(s),
+                    Ok(Token::Int(val)) => Value::Int(val),
+                    Ok(Token::Bool(val)) => Value::Bool(val),
+                    _ => return Err(expected(&tq, "a value", eof)),
+                };
+                tq.increment().ok();
+                Line::Entry(key, value)
+            }
+            _ => return Err(expected(&tq, "a section or an entry", eof)),
+        };
+
+        if !tq.is_consumed() && tq.consume_eq(Token::Newline).is_err() {
+            return Err(expected(&tq, "the end of the line", eof));
+        }
+        Ok((line, tq.get_idx()))
+    }
+}
+
+#[derive(Debug, PartialEq, Default)]
+struct Config {
+    /// Entries outside any section are in the section named "".
+    sections: Vec<(String, Vec<(String, Value)>)>,
+}
+
+impl Config {
+    fn get(&self, section: &str, key: &str) -> Option<&Value> {
+        self.sections
+            .iter()
+            .filter(|(name, _)| name == section)
+            .flat_map(|(_, entries)| entries)
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Parse a config file, recovering from errors at the next line. Returns the
+/// config built from the valid lines and a diagnostic for each invalid line.
+fn parse_config(source: &SourceFile) -> (Config, Vec<Diagnostic>) {
+    let eof = Span::new(source.text().len(), source.text().len());
+    let mut config = Config::default();
+    let mut diagnostics = Vec::new();
+
+    let tokens = match setup_lexer().lex_spanned(source.text()) {
+        Ok(tokens) => tokens,
+        Err(err) => {
+            diagnostics.push(Diagnostic::error(err.to_string(), eof));
+            return (config, diagnostics);
+        }
+    };
+    let mut tq = TokenQueue::from(tokens);
+    let mut section = String::new();
+
+    while !tq.is_consumed() {
+        // skip blank lines
+        if tq.consume_eq(Token::Newline).is_ok() {
+            continue;
+        }
+        match Line::parse(&tq, eof) {
+            Ok((line, idx)) => {
+                let span = tq.span_between(tq.get_idx(), idx);
+                tq.go_to(idx).unwrap();
+                match line {
+                    Line::Section(name) => {
+                        config.sections.push((name.clone(), Vec::new()));
+                        section = name;
+                    }
+                    Line::Entry(key, value) => {
+                        if config.get(&section, &key).is_some() {
+                            diagnostics.push(Diagnostic::warning(
+                                format!("duplicate key `{key}`"),
+                                span,
+                            ));
+                        }
+                        if config.sections.is_empty() {
+                            config.sections.push((String::new(), Vec::new()));
+                        }
+                        let entries =
+                            &mut config.sections.last_mut().unwrap().1;
+                        entries.push((key, value));
+                    }
+                }
+            }
+            Err(diagnostic) => {
+                diagnostics.push(diagnostic);
+                // recover: skip to the start of the next line
+                while let Ok(token) = tq.consume() {
+                    if token.value == Token::Newline {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    (config, diagnostics)
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or(anyhow::anyhow!("Usage: config <file>"))?;
+    let source = SourceFile::new(&path, std::fs::read_to_string(&path)?);
+
+    let (config, diagnostics) = parse_config(&source);
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic.render(&source));
+    }
+    for (section, entries) in &config.sections {
+        println!("[{section}]");
+        for (key, value) in entries {
+            println!("{key} = {value:?}");
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rlrl::diagnostic::Severity;
+
+    #[test]
+    fn parse_test() {
+        let source = SourceFile::new(
+            "test.ini",
+            "# server settings\n\
+             [server]\n\
+             host = \"localhost\"\n\
+             \n\
+             port = 8080   # inline comment\n\
+             debug = false",
+        );
+        let (config, diagnostics) = parse_config(&source);
+
+        assert!(diagnostics.is_empty());
+        assert!(
+            config.get("server", "host")
+                == Some(&Value::Str("localhost".into()))
+        );
+        assert!(config.get("server", "port") == Some(&Value::Int(8080)));
+        assert!(config.get("server", "debug") == Some(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn recovery_test() {
+        let source = SourceFile::new(
+            "test.ini",
+            "name = \"a\"\n\
+             [server\n\
+             host \"localhost\"\n\
+             port = 8080\n\
+             port = 9090\n\
+             user = @admin\n\
+             debug = true",
+        );
+        let (config, diagnostics) = parse_config(&source);
+
+        // every valid line is kept despite the errors
+        assert!(config.get("", "name") == Some(&Value::Str("a".into())));
+        assert!(config.get("", "port") == Some(&Value::Int(8080)));
+        assert!(config.get("", "debug") == Some(&Value::Bool(true)));
+
+        let severities: Vec<Severity> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.severity)
+            .collect();
+        assert!(
+            severities
+                == vec![
+                    Severity::Error,
+                    Severity::Error,
+                    Severity::Warning,
+                    Severity::Error
+                ]
+        );
+        assert!(
+            diagnostics[1].render(&source)
+                == "error: expected `=`, found Str(\"localhost\")\n \
+                    --> test.ini:3:6\n  \
+                    |\n\
+                    3 | host \"localhost\"\n  \
+                    |      ^^^^^^^^^^^\n  \
+                    = note: entries are written `key = value`\n"
+        );
+        assert!(diagnostics[3].render(&source).contains("6 | user = @admin"));
+    }
+}
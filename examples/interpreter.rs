@@ -0,0 +1,391 @@
+//! An interpreter for a small language with variables and block scopes,
+//! demonstrating the architecture `rlrl` is meant for: a lexer turns the
+//! source into spanned tokens, hand-written parse functions turn the tokens
+//! into a [Stmt] list, and the statements are evaluated with [Eval] in an
+//! [Env] that keeps the variables in a [ScopeStack].
+//!
+//! ```text
+//! let x = 2 + 3;
+//! {
+//!     let y = x * 2;
+//!     x = y - 1;
+//!     print y;
+//! }
+//! print x;
+//! ```
+//!
+//! Errors, whether in parsing or evaluation, are reported as [Diagnostic]s
+//! at the source they refer to.
+//!
+//! Run it with `cargo run --example interpreter -- <file>`.
+use rlrl::diagnostic::{Diagnostic, Suggestion};
+use rlrl::eval::Eval;
+use rlrl::prelude::*;
+use rlrl::scope::ScopeStack;
+use rlrl::source::SourceFile;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    Let,
+    Print,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Eq,
+    Semi,
+    LParen,
+    RParen,
+    LBrace,
+    RBrace,
+    Ident(String),
+    Num(f64),
+}
+
+type Tokens = TokenQueue<Spanned<Token>>;
+
+fn setup_lexer() -> Lexer<Token> {
+    let mut lexer = Lexer::new();
+
+    lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+
+    lexer.add_rule(r"\+", |_| LexResult::Token(Token::Add));
+    lexer.add_rule(r"\-", |_| LexResult::Token(Token::Sub));
+    lexer.add_rule(r"\*", |_| LexResult::Token(Token::Mul));
+    lexer.add_rule(r"/", |_| LexResult::Token(Token::Div));
+    lexer.add_rule(r"=", |_| LexResult::Token(Token::Eq));
+    lexer.add_rule(r";", |_| LexResult::Token(Token::Semi));
+    lexer.add_rule(r"\(", |_| LexResult::Token(Token::LParen));
+    lexer.add_rule(r"\)", |_| LexResult::Token(Token::RParen));
+    lexer.add_rule(r"\{", |_| LexResult::Token(Token::LBrace));
+    lexer.add_rule(r"\}", |_| LexResult::Token(Token::RBrace));
+
+    lexer.add_rule(r"[A-Za-z_][A-Za-z0-9_]*", |re_match| {
+        match re_match.as_str() {
+            "let" => LexResult::Token(Token::Let),
+            "print" => LexResult::Token(Token::Print),
+            ident => LexResult::Token(Token::Ident(ident.to_owned())),
+        }
+    });
+    lexer.add_try_rule(r"[0-9]+(?:\.[0-9]+)?", |re_match| {
+        Ok(Some(Token::Num(re_match.as_str().parse()?)))
+    });
+
+    lexer
+}
+
+/// Create an error at the front of `tq` saying `what` was expected.
+fn expected(what: &str, tq: &Tokens) -> ParseError {
+    ParseError::at(tq.get_idx(), format!("Expected {what}!"))
+}
+
+/// A variable name, with the span it was written at.
+#[derive(Debug, PartialEq, Clone)]
+struct Name {
+    name: String,
+    span: Span,
+}
+
+impl Name {
+    fn parse(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let span = tq.peek_span()?;
+        match tq.consume_cloned()?.into_inner() {
+            Token::Ident(name) => Ok((Self { name, span }, tq.get_idx())),
+            _ => Err(ParseError::at(tq.get_idx() - 1, "Expected a name!")),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Num(f64),
+    Var(Name),
+    Binary(Op, Box<Expr>, Box<Expr>),
+}
+
+impl Expr {
+    /// Parse a sum of products.
+    fn parse(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let expr = tq.fold_left(
+            Self::parse_product,
+            Self::parse_sum_op,
+            |lhs, op, rhs| Self::Binary(op, lhs.into(), rhs.into()),
+        )?;
+        Ok((expr, tq.get_idx()))
+    }
+
+    fn parse_sum_op(tq: &Tokens) -> ParseResult<Op> {
+        let mut tq = tq.clone();
+        let op = tq.consume_map("`+` or `-`", |token| match **token {
+            Token::Add => Some(Op::Add),
+            Token::Sub => Some(Op::Sub),
+            _ => None,
+        })?;
+        Ok((op, tq.get_idx()))
+    }
+
+    fn parse_product(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let expr = tq.fold_left(
+            Self::parse_atom,
+            Self::parse_product_op,
+            |lhs, op, rhs| Self::Binary(op, lhs.into(), rhs.into()),
+        )?;
+        Ok((expr, tq.get_idx()))
+    }
+
+    fn parse_product_op(tq: &Tokens) -> ParseResult<Op> {
+        let mut tq = tq.clone();
+        let op = tq.consume_map("`*` or `/`", |token| match **token {
+            Token::Mul => Some(Op::Mul),
+            Token::Div => Some(Op::Div),
+            _ => None,
+        })?;
+        Ok((op, tq.get_idx()))
+    }
+
+    fn parse_atom(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let expr = match &**tq.peek()? {
+            Token::Num(val) => {
+                let val = *val;
+                tq.increment()?;
+                Self::Num(val)
+            }
+            Token::Ident(_) => Self::Var(tq.parse(Name::parse)?),
+            Token::LParen => {
+                tq.increment()?;
+                let expr = tq.parse(Self::parse)?;
+                tq.consume_eq(Token::RParen)
+                    .map_err(|_| expected("`)`", &tq))?;
+                expr
+            }
+            _ => return Err(expected("an expression", &tq)),
+        };
+        Ok((expr, tq.get_idx()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Stmt {
+    Let(Name, Expr),
+    Assign(Name, Expr),
+    Print(Expr),
+    Block(Vec<Stmt>),
+}
+
+impl Stmt {
+    fn parse(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let stmt = match &**tq.peek()? {
+            Token::Let => {
+                tq.increment()?;
+                let name = tq.parse(Name::parse)?;
+                Self::expect(&mut tq, Token::Eq, "`=`")?;
+                Self::Let(name, tq.parse(Expr::parse)?)
+            }
+            Token::Print => {
+                tq.increment()?;
+                Self::Print(tq.parse(Expr::parse)?)
+            }
+            Token::LBrace => {
+                tq.increment()?;
+                let stmts = tq.parse(Self::parse_block)?;
+                Self::expect(&mut tq, Token::RBrace, "a statement or `}`")?;
+                return Ok((Self::Block(stmts), tq.get_idx()));
+            }
+            _ => {
+                let name = tq.parse(Name::parse)?;
+                Self::expect(&mut tq, Token::Eq, "`=`")?;
+                Self::Assign(name, tq.parse(Expr::parse)?)
+            }
+        };
+        Self::expect(&mut tq, Token::Semi, "an operator or `;`")?;
+        Ok((stmt, tq.get_idx()))
+    }
+
+    /// Parse statements up to a `}` or the end of input.
+    fn parse_block(tq: &Tokens) -> ParseResult<Vec<Self>> {
+        let mut tq = tq.clone();
+        let mut stmts = Vec::new();
+        while !tq.is_consumed() && !tq.at(&Token::RBrace) {
+            stmts.push(tq.parse(Self::parse)?);
+        }
+        Ok((stmts, tq.get_idx()))
+    }
+
+    fn expect(
+        tq: &mut Tokens,
+        token: Token,
+        what: &str,
+    ) -> Result<(), ParseError> {
+        tq.consume_eq(token).map_err(|_| expected(what, tq))
+    }
+}
+
+/// The environment programs are evaluated in.
+#[derive(Default)]
+struct Env {
+    vars: ScopeStack<String, f64>,
+    /// The values printed so far.
+    output: Vec<f64>,
+}
+
+impl Env {
+    /// Report `name` as undefined, suggesting a similar variable in scope.
+    fn undefined(&self, name: &Name) -> Diagnostic {
+        let diagnostic = Diagnostic::error(
+            format!("`{}` is not defined!", name.name),
+            name.span,
+        );
+        let visible = self.vars.visible().map(String::as_str);
+        match Suggestion::did_you_mean(&name.name, name.span, visible) {
+            Some(suggestion) => diagnostic.with_suggestion(suggestion),
+            None => diagnostic,
+        }
+    }
+}
+
+impl Eval<Env, Result<f64, Diagnostic>> for Expr {
+    fn eval(&self, env: &mut Env) -> Result<f64, Diagnostic> {
+        match self {
+            Self::Num(val) => Ok(*val),
+            Self::Var(name) => match env.vars.lookup(name.name.as_str()) {
+                Some(binding) => Ok(binding.value),
+                None => Err(env.undefined(name)),
+            },
+            Self::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.eval(env)?, rhs.eval(env)?);
+                Ok(match op {
+                    Op::Add => lhs + rhs,
+                    Op::Sub => lhs - rhs,
+                    Op::Mul => lhs * rhs,
+                    Op::Div => lhs / rhs,
+                })
+            }
+        }
+    }
+}
+
+impl Eval<Env, Result<(), Diagnostic>> for Stmt {
+    fn eval(&self, env: &mut Env) -> Result<(), Diagnostic> {
+        match self {
+            Self::Let(name, expr) => {
+                let val = expr.eval(env)?;
+                env.vars
+                    .declare(name.name.clone(), val, name.span)
+                    .map_err(|err| err.diagnostic(&name.name))
+            }
+            Self::Assign(name, expr) => {
+                let val = expr.eval(env)?;
+                match env.vars.lookup_mut(name.name.as_str()) {
+                    Some(binding) => binding.value = val,
+                    None => return Err(env.undefined(name)),
+                }
+                Ok(())
+            }
+            Self::Print(expr) => {
+                let val = expr.eval(env)?;
+                env.output.push(val);
+                Ok(())
+            }
+            Self::Block(stmts) => {
+                env.vars.push_scope();
+                let result = stmts[..].eval(env);
+                env.vars.pop_scope();
+                result.map(|_| ())
+            }
+        }
+    }
+}
+
+/// Lex, parse, and run the program in `source`, returning the values it
+/// printed.
+fn run(source: &SourceFile) -> Result<Vec<f64>, Diagnostic> {
+    let tokens = setup_lexer()
+        .lex_spanned(source.text())
+        .map_err(|err| Diagnostic::error(err.to_string(), Span::new(0, 0)))?;
+    let mut tq = TokenQueue::from(tokens);
+    let stmts = tq
+        .parse(Stmt::parse_block)
+        .and_then(|stmts| match tq.is_consumed() {
+            true => Ok(stmts),
+            false => Err(expected("a statement", &tq)),
+        })
+        .map_err(|err| Diagnostic::error(err.message(), tq.error_span(&err)))?;
+
+    let mut env = Env::default();
+    stmts[..].eval(&mut env)?;
+    Ok(env.output)
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or(anyhow::anyhow!("Usage: interpreter <file>"))?;
+    let source = SourceFile::new(&path, std::fs::read_to_string(&path)?);
+    match run(&source) {
+        Ok(output) => output.iter().for_each(|val| println!("{val}")),
+        Err(diagnostic) => print!("{}", diagnostic.render(&source)),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_str(s: &str) -> Result<Vec<f64>, Diagnostic> {
+        run(&SourceFile::new("test", s))
+    }
+
+    #[test]
+    fn eval_test() -> Result<(), Diagnostic> {
+        let output = run_str(
+            "let x = 2 + 3;
+            {
+                let y = x * 2;
+                x = y - 1;
+                print y;
+            }
+            print x;
+            print (x - 1) / 2;",
+        )?;
+        assert!(output == [10.0, 9.0, 4.0]);
+
+        // shadowing ends with the block
+        let output = run_str("let x = 1; { let x = 2; print x; } print x;")?;
+        assert!(output == [2.0, 1.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn error_test() {
+        let err = run_str("let count = 1; print cuont;").unwrap_err();
+        assert!(err.message == "`cuont` is not defined!");
+        assert!(err.span == Span::new(21, 26));
+        assert!(err.suggestions[0].replacement == "count");
+
+        // `y` goes out of scope with the block
+        let err = run_str("{ let y = 1; } y = 2;").unwrap_err();
+        assert!(err.message == "`y` is not defined!");
+
+        let err = run_str("let x = 1; let x = 2;").unwrap_err();
+        assert!(err.message == "`x` is already declared!");
+
+        let err = run_str("let x = 1 print x;").unwrap_err();
+        assert!(err.message == "Expected an operator or `;`!");
+        assert!(err.span == Span::new(10, 15));
+    }
+}
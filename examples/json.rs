@@ -0,0 +1,369 @@
+//! A JSON parser built on `rlrl`, following RFC 8259.
+//!
+//! It lexes into spanned tokens so errors can report byte offsets, and can be
+//! copied as a template for other languages with string escapes and nested
+//! structures.
+//!
+//! ```text
+//! $ echo '{"a": [1, 2.5e1, "é"]}' | cargo run --example json
+//! {"a":[1,25,"é"]}
+//! ```
+use rlrl::prelude::*;
+use std::fmt::Display;
+use std::io::Read;
+
+#[derive(Debug, PartialEq, Clone)]
+enum Token {
+    LBrace,
+    RBrace,
+    LBracket,
+    RBracket,
+    Colon,
+    Comma,
+    True,
+    False,
+    Null,
+    Num(f64),
+    Str(String),
+}
+
+fn setup_lexer() -> Lexer<Token> {
+    let mut lexer = Lexer::new();
+
+    lexer.add_rule(r"[ \t\n\r]+", |_| LexResult::Ignore);
+
+    lexer.add_rule(r"\{", |_| LexResult::Token(Token::LBrace));
+    lexer.add_rule(r"\}", |_| LexResult::Token(Token::RBrace));
+    lexer.add_rule(r"\[", |_| LexResult::Token(Token::LBracket));
+    lexer.add_rule(r"\]", |_| LexResult::Token(Token::RBracket));
+    lexer.add_rule(r":", |_| LexResult::Token(Token::Colon));
+    lexer.add_rule(r",", |_| LexResult::Token(Token::Comma));
+    lexer.add_rule(r"true", |_| LexResult::Token(Token::True));
+    lexer.add_rule(r"false", |_| LexResult::Token(Token::False));
+    lexer.add_rule(r"null", |_| LexResult::Token(Token::Null));
+
+    lexer.add_try_rule(
+        r"-?(?:0|[1-9][0-9]*)(?:\.[0-9]+)?(?:[eE][+-]?[0-9]+)?",
+        |re_match| {
+            let num: f64 = re_match.as_str().parse()?;
+            if !num.is_finite() {
+                return Err(LexError::custom("Number out of range!"));
+            }
+            Ok(Some(Token::Num(num)))
+        },
+    );
+    lexer.add_try_rule(r#""(?:[^"\\\x00-\x1f]|\\.)*""#, |re_match| {
+        let s = re_match.as_str();
+        Ok(Some(Token::Str(unescape(&s[1..s.len() - 1])?)))
+    });
+
+    lexer
+}
+
+/// Read the four hex digits of a `\u` escape.
+fn read_hex4(chars: &mut std::str::Chars) -> anyhow::Result<u32> {
+    let hex: String = chars.take(4).collect();
+    if hex.len() != 4 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(anyhow::anyhow!("Invalid \\u escape!"));
+    }
+    Ok(u32::from_str_radix(&hex, 16)?)
+}
+
+/// Resolve the escape sequences in the body of a string literal.
+fn unescape(s: &str) -> anyhow::Result<String> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        let c = match chars.next() {
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some('/') => '/',
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('u') => {
+                let high = read_hex4(&mut chars)?;
+                let code = match high {
+                    // a high surrogate must be followed by a low surrogate
+                    0xd800..=0xdbff => {
+                        if chars.next() != Some('\\')
+                            || chars.next() != Some('u')
+                        {
+                            return Err(anyhow::anyhow!("Unpaired surrogate!"));
+                        }
+                        let low = read_hex4(&mut chars)?;
+                        if !(0xdc00..=0xdfff).contains(&low) {
+                            return Err(anyhow::anyhow!("Unpaired surrogate!"));
+                        }
+                        0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+                    }
+                    0xdc00..=0xdfff => {
+                        return Err(anyhow::anyhow!("Unpaired surrogate!"));
+                    }
+                    code => code,
+                };
+                char::from_u32(code)
+                    .ok_or(anyhow::anyhow!("Invalid code point {code:#x}!"))?
+            }
+            Some(c) => return Err(anyhow::anyhow!("Unknown escape `\\{c}`!")),
+            None => return Err(anyhow::anyhow!("Unterminated escape!")),
+        };
+        out.push(c);
+    }
+    Ok(out)
+}
+
+#[derive(Debug, PartialEq, Clone)]
+enum Value {
+    Null,
+    Bool(bool),
+    Num(f64),
+    Str(String),
+    Array(Vec<Value>),
+    /// Members in document order.
+    Object(Vec<(String, Value)>),
+}
+
+type Tokens = TokenQueue<Spanned<Token>>;
+
+/// Build an error describing the token at the front of `tq`.
+fn unexpected(tq: &Tokens, expected: &str) -> ParseError {
+    match tq.peek() {
+        Ok(token) => ParseError::new(format!(
+            "Expected {expected} at byte {} but found {:?}!",
+            token.span.start, token.value
+        )),
+        Err(_) => {
+            ParseError::new(format!("Expected {expected} but reached the end!"))
+        }
+    }
+}
+
+impl Value {
+    fn parse(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let value = match tq.peek().map(|token| &token.value) {
+            Ok(Token::Null) => Value::Null,
+            Ok(Token::True) => Value::Bool(true),
+            Ok(Token::False) => Value::Bool(false),
+            Ok(Token::Num(val)) => Value::Num(*val),
+            Ok(Token::Str(s)) => Value::Str(s.clone()),
+            Ok(Token::LBracket) => return Value::parse_array(&tq),
+            Ok(Token::LBrace) => return Value::parse_object(&tq),
+            _ => return Err(unexpected(&tq, "a value")),
+        };
+        tq.increment()?;
+
+        Ok((value, tq.get_idx()))
+    }
+
+    fn parse_array(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        tq.consume_eq(Token::LBracket)?;
+
+        let items = tq.parse_separated(
+            Value::parse,
+            Token::Comma,
+            Separated::default(),
+        )?;
+        tq.consume_eq(Token::RBracket)
+            .map_err(|_| unexpected(&tq, "`,` or `]`"))?;
+
+        Ok((Value::Array(items), tq.get_idx()))
+    }
+
+    fn parse_object(tq: &Tokens) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        tq.consume_eq(Token::LBrace)?;
+
+        let members = tq.parse_separated(
+            Value::parse_member,
+            Token::Comma,
+            Separated::default(),
+        )?;
+        tq.consume_eq(Token::RBrace)
+            .map_err(|_| unexpected(&tq, "`,` or `}`"))?;
+
+        Ok((Value::Object(members), tq.get_idx()))
+    }
+
+    fn parse_member(tq: &Tokens) -> ParseResult<(String, Value)> {
+        let mut tq = tq.clone();
+
+        let Ok(Token::Str(key)) = tq.peek().map(|token| &token.value) else {
+            return Err(unexpected(&tq, "a string key"));
+        };
+        let key = key.clone();
+        tq.increment()?;
+        tq.consume_eq(Token::Colon)
+            .map_err(|_| unexpected(&tq, "`:`"))?;
+        let value = tq.parse(Value::parse)?;
+
+        Ok(((key, value), tq.get_idx()))
+    }
+}
+
+impl Display for Value {
+    /// Write the value as compact JSON.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Null => write!(f, "null"),
+            Self::Bool(val) => write!(f, "{val}"),
+            Self::Num(val) => write!(f, "{val}"),
+            Self::Str(s) => write_str(f, s),
+            Self::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Self::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (key, val)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_str(f, key)?;
+                    write!(f, ":{val}")?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
+}
+
+fn write_str(f: &mut std::fmt::Formatter<'_>, s: &str) -> std::fmt::Result {
+    write!(f, "\"")?;
+    for c in s.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{c}")?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// Parse a complete JSON document.
+fn parse_json(s: &str) -> anyhow::Result<Value> {
+    let mut tq = TokenQueue::from(setup_lexer().lex_spanned(s)?);
+    let value = tq.parse(Value::parse)?;
+    if !tq.is_consumed() {
+        return Err(unexpected(&tq, "the end of the document").into());
+    }
+    Ok(value)
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut input = String::new();
+    std::io::stdin().read_to_string(&mut input)?;
+    println!("{}", parse_json(&input)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_test() -> anyhow::Result<()> {
+        let value = parse_json(r#" {"a": [1, -2.5e1, true, null], "b": {}} "#)?;
+
+        assert!(
+            value
+                == Value::Object(vec![
+                    (
+                        "a".to_owned(),
+                        Value::Array(vec![
+                            Value::Num(1.0),
+                            Value::Num(-25.0),
+                            Value::Bool(true),
+                            Value::Null
+                        ])
+                    ),
+                    ("b".to_owned(), Value::Object(vec![]))
+                ])
+        );
+        assert!(value.to_string() == r#"{"a":[1,-25,true,null],"b":{}}"#);
+
+        Ok(())
+    }
+
+    #[test]
+    fn escapes_test() -> anyhow::Result<()> {
+        let value = parse_json(r#""a\"\\\/\b\f\n\r\té😀""#)?;
+        assert!(value == Value::Str("a\"\\/\u{8}\u{c}\n\r\té😀".to_owned()));
+
+        for invalid in [
+            r#""\x""#,
+            r#""\u12""#,
+            r#""\ud83d""#,
+            r#""\ude00""#,
+            "\"\t\"",
+        ] {
+            assert!(parse_json(invalid).is_err(), "accepted {invalid}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn conformance_test() {
+        for valid in [
+            "0",
+            "-0",
+            "1.5E+3",
+            "\"\"",
+            "[]",
+            "[[[]]]",
+            r#"{"":""}"#,
+            " \t\r\n[ 1 , 2 ] ",
+        ] {
+            assert!(parse_json(valid).is_ok(), "rejected {valid}");
+        }
+
+        for invalid in [
+            "",
+            "01",
+            "1.",
+            ".5",
+            "+1",
+            "[1,]",
+            "[1 2]",
+            r#"{"a" 1}"#,
+            r#"{"a":1,}"#,
+            "{1:2}",
+            "[1]]",
+            "tru",
+            "'a'",
+            "NaN",
+            "1e400",
+            "-1e400",
+            r#""\u+041""#,
+            r#""\u12""#,
+        ] {
+            assert!(parse_json(invalid).is_err(), "accepted {invalid}");
+        }
+    }
+
+    #[test]
+    fn error_position_test() {
+        let err = parse_json(r#"{"a": 1 "b": 2}"#).unwrap_err();
+        assert!(err.to_string().contains("byte 8"));
+    }
+}
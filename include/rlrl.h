@@ -0,0 +1,33 @@
+/* C API for rlrl lexers, built with the `ffi` feature. See src/ffi.rs.
+ * Link against the library built by the `rlrl-ffi` crate: -lrlrl_ffi. */
+#ifndef RLRL_H
+#define RLRL_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+typedef struct RlrlLexer RlrlLexer;
+typedef struct RlrlTokens RlrlTokens;
+
+typedef struct {
+    const char *pattern;
+    uint32_t kind;
+    bool ignore;
+} RlrlRule;
+
+typedef struct {
+    uint32_t kind;
+    size_t start;
+    size_t end;
+} RlrlToken;
+
+RlrlLexer *rlrl_lexer_new(const RlrlRule *rules, size_t count);
+void rlrl_lexer_free(RlrlLexer *lexer);
+RlrlTokens *rlrl_lexer_lex(const RlrlLexer *lexer, const uint8_t *input,
+                           size_t len, size_t *error_pos);
+size_t rlrl_tokens_len(const RlrlTokens *tokens);
+bool rlrl_tokens_get(const RlrlTokens *tokens, size_t idx, RlrlToken *out);
+void rlrl_tokens_free(RlrlTokens *tokens);
+
+#endif
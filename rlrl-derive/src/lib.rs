@@ -0,0 +1,266 @@
+//! Derive macros for `rlrl`, enabled with its `derive` feature.
+//!
+//! `#[derive(Parse)]` implements `rlrl::parse::Parse` for a struct by
+//! parsing its fields in order, each with its type's `Parse` impl, and for an
+//! enum by trying each variant in order, like an alternation. It also
+//! implements `TryFrom<&mut TokenQueue<_>>` by parsing from the queue.
+//!
+//! ```ignore
+//! #[derive(Parse)]
+//! #[parse(token = Token)]
+//! struct IntRange {
+//!     start: Int,
+//!     #[token(Comma)]
+//!     end: Int,
+//! }
+//! ```
+//!
+//! - `#[parse(token = Token)]` on the struct (required) names the token type.
+//! - `#[token(Comma)]` on a field consumes a token equal to `Token::Comma`
+//!   before the field. A path with `::`, or any other expression, is used as
+//!   written. Fields can have several, consumed in order.
+//!
+//! Enum variants are parsed like structs, and can also have `#[token(...)]`
+//! attributes, consumed before their fields:
+//!
+//! ```ignore
+//! #[derive(Parse)]
+//! #[parse(token = Token)]
+//! enum Stmt {
+//!     #[lookahead(Let)]
+//!     Let(#[token(Let)] Ident, #[token(Eq)] Expr),
+//!     Expr(Expr),
+//! }
+//! ```
+//!
+//! - `#[lookahead(Let)]` on a variant only tries it if the front token
+//!   equals `Token::Let`, and commits to it if so: its error is returned
+//!   instead of trying the variants after it. Variants can have several,
+//!   and are tried if any of them match.
+//!
+//! A variant whose error is cut, because a parse function inside it called
+//! `TokenQueue::cut`, is also committed to.
+//!
+//! If no variant parses, the error of the variant that got furthest is
+//! returned, or, if none consumed any tokens, an error listing the variants,
+//! like "Expected one of `Let`, `Expr`!".
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    Attribute, Data, DataEnum, DeriveInput, Error, Expr, Fields, Path,
+    parse_macro_input,
+};
+
+#[proc_macro_derive(Parse, attributes(parse, token, lookahead))]
+pub fn derive_parse(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let token = token_type(input)?;
+    let body = match &input.data {
+        Data::Struct(data) => {
+            parse_fields(&[], &data.fields, &token, quote!(Self))?
+        }
+        Data::Enum(data) => parse_variants(data, &token)?,
+        Data::Union(_) => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "#[derive(Parse)] doesn't support unions",
+            ));
+        }
+    };
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) =
+        input.generics.split_for_impl();
+    let mut try_from_generics = input.generics.clone();
+    try_from_generics.params.insert(0, syn::parse_quote!('__tq));
+    let (try_from_impl_generics, _, _) = try_from_generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::rlrl::parse::Parse<#token>
+            for #name #ty_generics #where_clause
+        {
+            fn parse(
+                tq: &::rlrl::parse::TokenQueue<#token>,
+            ) -> ::rlrl::parse::ParseResult<Self> {
+                #body
+            }
+        }
+
+        impl #try_from_impl_generics
+            ::core::convert::TryFrom<&'__tq mut ::rlrl::parse::TokenQueue<#token>>
+            for #name #ty_generics #where_clause
+        {
+            type Error = ::rlrl::parse::ParseError;
+
+            fn try_from(
+                tq: &'__tq mut ::rlrl::parse::TokenQueue<#token>,
+            ) -> ::core::result::Result<Self, Self::Error> {
+                tq.parse(<Self as ::rlrl::parse::Parse<#token>>::parse)
+            }
+        }
+    })
+}
+
+/// Get the token type named by `#[parse(token = ...)]`.
+fn token_type(input: &DeriveInput) -> syn::Result<Path> {
+    let mut token = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("parse") {
+            continue;
+        }
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("token") {
+                token = Some(meta.value()?.parse::<Path>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown `parse` attribute"))
+            }
+        })?;
+    }
+    token.ok_or_else(|| {
+        Error::new_spanned(
+            &input.ident,
+            "#[derive(Parse)] requires #[parse(token = TokenType)]",
+        )
+    })
+}
+
+/// Generate the body of a parse function for an enum, trying each of its
+/// variants in order.
+fn parse_variants(data: &DataEnum, token: &Path) -> syn::Result<TokenStream2> {
+    let mut attempts = Vec::new();
+    let mut names = Vec::new();
+    for variant in &data.variants {
+        let ident = &variant.ident;
+        let body = parse_fields(
+            &variant.attrs,
+            &variant.fields,
+            token,
+            quote!(Self::#ident),
+        )?;
+        let attempt = quote! {
+            let attempt = (|| -> ::rlrl::parse::ParseResult<Self> { #body })();
+        };
+
+        let mut lookaheads = Vec::new();
+        for attr in &variant.attrs {
+            if attr.path().is_ident("lookahead") {
+                lookaheads.push(token_expr(&attr.parse_args()?, token));
+            }
+        }
+        attempts.push(match lookaheads.is_empty() {
+            true => quote! {
+                #attempt
+                match attempt {
+                    ::core::result::Result::Ok(parsed) => {
+                        return ::core::result::Result::Ok(parsed);
+                    }
+                    ::core::result::Result::Err(err) if err.is_cut() => {
+                        return ::core::result::Result::Err(err);
+                    }
+                    ::core::result::Result::Err(err) => {
+                        if err.idx() > farthest.idx() {
+                            farthest = err;
+                        }
+                    }
+                }
+            },
+            false => quote! {
+                if #(tq.at(&#lookaheads))||* {
+                    #attempt
+                    return attempt;
+                }
+            },
+        });
+        names.push(format!("`{ident}`"));
+    }
+
+    let message = format!("Expected one of {}!", names.join(", "));
+    Ok(quote! {
+        // only errors from variants that consumed tokens replace this
+        let mut farthest = ::rlrl::parse::ParseError::at(tq.get_idx(), #message);
+        #({ #attempts })*
+        ::core::result::Result::Err(farthest)
+    })
+}
+
+/// Generate the body of a parse function that consumes the tokens named by
+/// `attrs`, then parses `fields` in order and builds them into `ctor`.
+fn parse_fields(
+    attrs: &[Attribute],
+    fields: &Fields,
+    token: &Path,
+    ctor: TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let mut steps = token_attrs(attrs, token)?;
+    let mut vars = Vec::new();
+    for (i, field) in fields.iter().enumerate() {
+        for expected in token_attrs(&field.attrs, token)? {
+            steps.push(expected);
+        }
+        let var = format_ident!("field_{i}");
+        let ty = &field.ty;
+        steps.push(quote! {
+            let #var = tq.parse(<#ty as ::rlrl::parse::Parse<#token>>::parse)?;
+        });
+        vars.push(var);
+    }
+
+    let value = match fields {
+        Fields::Named(named) => {
+            let names = named.named.iter().map(|field| &field.ident);
+            quote!(#ctor { #(#names: #vars),* })
+        }
+        Fields::Unnamed(_) => quote!(#ctor(#(#vars),*)),
+        Fields::Unit => ctor,
+    };
+    Ok(quote! {
+        let mut tq = tq.clone();
+        #(#steps)*
+        ::core::result::Result::Ok((#value, tq.get_idx()))
+    })
+}
+
+/// Generate statements consuming the tokens named by `#[token(...)]`
+/// attributes in `attrs`.
+fn token_attrs(
+    attrs: &[Attribute],
+    token: &Path,
+) -> syn::Result<Vec<TokenStream2>> {
+    let mut steps = Vec::new();
+    for attr in attrs {
+        if !attr.path().is_ident("token") {
+            continue;
+        }
+        let expr: Expr = attr.parse_args()?;
+        let message = format!("Expected `{}`!", quote!(#expr));
+        let expr = token_expr(&expr, token);
+        steps.push(quote! {
+            if tq.consume_eq(#expr).is_err() {
+                return ::core::result::Result::Err(
+                    ::rlrl::parse::ParseError::at(tq.get_idx(), #message),
+                );
+            }
+        });
+    }
+    Ok(steps)
+}
+
+/// Resolve a token named in an attribute: a bare identifier is a variant of
+/// the token type, and anything else is used as written.
+fn token_expr(expr: &Expr, token: &Path) -> TokenStream2 {
+    match expr {
+        Expr::Path(path) if path.path.get_ident().is_some() => {
+            quote!(#token::#expr)
+        }
+        _ => quote!(#expr),
+    }
+}
@@ -0,0 +1,138 @@
+use rlrl::prelude::*;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Comma,
+    Dots,
+    Minus,
+    Let,
+}
+
+#[derive(Debug, PartialEq)]
+struct Int(i64);
+
+impl Parse<Token> for Int {
+    fn parse(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+        let int = tq.consume_map("an integer", |token| match token {
+            Token::Int(int) => Some(*int),
+            _ => None,
+        })?;
+        Ok((Int(int), tq.get_idx()))
+    }
+}
+
+#[derive(Debug, PartialEq, Parse)]
+#[parse(token = Token)]
+struct IntRange {
+    start: Int,
+    #[token(Dots)]
+    end: Int,
+}
+
+#[derive(Debug, PartialEq, Parse)]
+#[parse(token = Token)]
+struct Pair(IntRange, #[token(Token::Comma)] Int);
+
+#[derive(Debug, PartialEq, Parse)]
+#[parse(token = Token)]
+enum Expr {
+    Range(IntRange),
+    Int(Int),
+    #[token(Minus)]
+    Neg(Box<Expr>),
+}
+
+impl Parse<Token> for Box<Expr> {
+    fn parse(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let (expr, idx) = Expr::parse(tq)?;
+        Ok((Box::new(expr), idx))
+    }
+}
+
+#[derive(Debug, PartialEq, Parse)]
+#[parse(token = Token)]
+enum Stmt {
+    #[lookahead(Let)]
+    Let {
+        #[token(Let)]
+        value: Expr,
+    },
+    Expr(Expr),
+}
+
+#[test]
+fn test_derive_struct() -> anyhow::Result<()> {
+    let mut tq = TokenQueue::from(vec![
+        Token::Int(1),
+        Token::Dots,
+        Token::Int(5),
+        Token::Comma,
+        Token::Int(2),
+    ]);
+    let pair = Pair::try_from(&mut tq)?;
+    assert!(
+        pair == Pair(
+            IntRange {
+                start: Int(1),
+                end: Int(5)
+            },
+            Int(2)
+        )
+    );
+    assert!(tq.is_consumed());
+
+    let mut tq = TokenQueue::from(vec![Token::Int(1), Token::Comma]);
+    let err = tq.parse(IntRange::parse).unwrap_err();
+    assert!(err.message() == "Expected `Dots`!" && err.idx() == Some(1));
+    assert!(tq.get_idx() == 0);
+
+    Ok(())
+}
+
+#[test]
+fn test_derive_enum() -> anyhow::Result<()> {
+    let mut tq = TokenQueue::from(vec![
+        Token::Minus,
+        Token::Int(1),
+        Token::Dots,
+        Token::Int(2),
+    ]);
+    let expr = Expr::try_from(&mut tq)?;
+    assert!(
+        expr == Expr::Neg(Box::new(Expr::Range(IntRange {
+            start: Int(1),
+            end: Int(2)
+        })))
+    );
+
+    let mut tq = TokenQueue::from(vec![Token::Int(1), Token::Comma]);
+    assert!(tq.parse(Expr::parse)? == Expr::Int(Int(1)));
+
+    let mut tq = TokenQueue::from(vec![Token::Comma]);
+    let err = tq.parse(Expr::parse).unwrap_err();
+    assert!(err.message() == "Expected one of `Range`, `Int`, `Neg`!");
+    assert!(err.idx() == Some(0));
+
+    // the furthest error is kept
+    let mut tq = TokenQueue::from(vec![Token::Minus, Token::Comma]);
+    let err = tq.parse(Expr::parse).unwrap_err();
+    assert!(err.message() == "Expected one of `Range`, `Int`, `Neg`!");
+    assert!(err.idx() == Some(1));
+
+    let mut tq = TokenQueue::from(vec![Token::Let, Token::Int(3)]);
+    let stmt = tq.parse(Stmt::parse)?;
+    assert!(
+        stmt == Stmt::Let {
+            value: Expr::Int(Int(3))
+        }
+    );
+
+    // committing to `Let` skips `Expr`
+    let mut tq = TokenQueue::from(vec![Token::Let, Token::Comma]);
+    let err = tq.parse(Stmt::parse).unwrap_err();
+    assert!(err.idx() == Some(1) && tq.get_idx() == 0);
+
+    Ok(())
+}
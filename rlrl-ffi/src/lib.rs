@@ -0,0 +1,5 @@
+//! Builds `rlrl`'s C API, the [rlrl::ffi] module, as a shared and a static
+//! library (`librlrl_ffi.so` and `librlrl_ffi.a` on Linux) for C programs to
+//! link against, with the declarations in `include/rlrl.h`.
+
+pub use rlrl::ffi::*;
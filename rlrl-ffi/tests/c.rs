@@ -0,0 +1,44 @@
+//! Compiles `smoke.c` against `include/rlrl.h`, links it with the shared
+//! library, and runs it. Skipped where there's no `cc`.
+
+#![cfg(unix)]
+
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_c_smoke() -> anyhow::Result<()> {
+    if Command::new("cc").arg("--version").output().is_err() {
+        eprintln!("skipping: no C compiler found");
+        return Ok(());
+    }
+    let manifest = Path::new(env!("CARGO_MANIFEST_DIR"));
+    // the test binary is in `target/<profile>/deps`, next to which the
+    // libraries are built
+    let exe = std::env::current_exe()?;
+    let lib_dir = exe.parent().and_then(Path::parent).unwrap();
+    let out = Path::new(env!("CARGO_TARGET_TMPDIR")).join("rlrl-ffi-smoke");
+
+    let status = Command::new("cc")
+        .arg(manifest.join("tests/smoke.c"))
+        .arg("-I")
+        .arg(manifest.join("../include"))
+        .arg("-L")
+        .arg(lib_dir)
+        .arg(format!("-Wl,-rpath,{}", lib_dir.display()))
+        .arg("-lrlrl_ffi")
+        .arg("-o")
+        .arg(&out)
+        .status()?;
+    assert!(status.success(), "Couldn't compile and link smoke.c!");
+
+    let output = Command::new(&out).output()?;
+    assert!(
+        output.status.success(),
+        "smoke.c failed: {:?}",
+        output.status
+    );
+    assert!(output.stdout == b"ok\n");
+
+    Ok(())
+}
@@ -0,0 +1,40 @@
+/* Lexes "ab 12" through the C API, exiting with 0 if the tokens are right. */
+#include <stdio.h>
+#include <string.h>
+
+#include "rlrl.h"
+
+int main(void) {
+    RlrlRule rules[] = {
+        {"[a-z]+", 1, false},
+        {"[0-9]+", 2, false},
+        {" +", 0, true},
+    };
+    RlrlLexer *lexer = rlrl_lexer_new(rules, 3);
+    if (!lexer) {
+        return 1;
+    }
+
+    const char *input = "ab 12";
+    RlrlTokens *tokens = rlrl_lexer_lex(lexer, (const uint8_t *)input,
+                                        strlen(input), NULL);
+    RlrlToken token;
+    if (!tokens || rlrl_tokens_len(tokens) != 2 ||
+        !rlrl_tokens_get(tokens, 1, &token) || token.kind != 2 ||
+        token.start != 3 || token.end != 5) {
+        return 2;
+    }
+    rlrl_tokens_free(tokens);
+
+    size_t error_pos = 0;
+    input = "ab ?";
+    if (rlrl_lexer_lex(lexer, (const uint8_t *)input, strlen(input),
+                       &error_pos) ||
+        error_pos != 3) {
+        return 3;
+    }
+
+    rlrl_lexer_free(lexer);
+    puts("ok");
+    return 0;
+}
@@ -0,0 +1,84 @@
+//! Tokenizer for grammar prototyping and shell pipelines.
+//!
+//! Usage: `rlrl-lex <grammar.ebnf> [--csv] [input...]`
+//!
+//! Loads an EBNF grammar (see [rlrl::ebnf]) whose terminals are either quoted
+//! literals or identifiers defined by a `/regex/`, lexes each input file (or
+//! stdin if none are given) into the grammar's terminals, and prints the
+//! tokens as a JSON array of `{"file", "kind", "text", "start", "end"}`
+//! objects, or as CSV with `--csv`. Offsets are in bytes.
+use rlrl::dynamic::DynamicToken;
+use rlrl::grammar::{Grammar, TerminalLexer};
+use rlrl::json::json_string;
+use std::io::Read;
+
+const USAGE: &str = "Usage: rlrl-lex <grammar.ebnf> [--csv] [input...]";
+
+/// Quote `s` as a CSV field if it contains a delimiter, quote, or newline.
+fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n', '\r']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.to_owned(),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let grammar_path = args.next().ok_or(anyhow::anyhow!(USAGE))?;
+    let mut csv = false;
+    let mut inputs = Vec::new();
+    for arg in args {
+        match arg.as_str() {
+            "--csv" => csv = true,
+            _ => inputs.push(arg),
+        }
+    }
+
+    let grammar = Grammar::from_ebnf(&std::fs::read_to_string(grammar_path)?)?;
+    let lexer = TerminalLexer::new(&grammar)?;
+
+    let mut files: Vec<(String, Vec<DynamicToken>)> = Vec::new();
+    if inputs.is_empty() {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        files.push(("-".to_owned(), lexer.lex(&text)?));
+    }
+    for path in inputs {
+        let text = std::fs::read_to_string(&path)?;
+        let tokens = lexer
+            .lex(&text)
+            .map_err(|err| anyhow::anyhow!("{path}: {err}"))?;
+        files.push((path, tokens));
+    }
+
+    if csv {
+        println!("file,kind,text,start,end");
+    }
+    let mut entries = Vec::new();
+    for (file, tokens) in &files {
+        for token in tokens {
+            match csv {
+                true => println!(
+                    "{},{},{},{},{}",
+                    csv_field(file),
+                    csv_field(&token.kind),
+                    csv_field(&token.text),
+                    token.span.start,
+                    token.span.end
+                ),
+                false => entries.push(format!(
+                    r#"{{"file":{},"kind":{},"text":{},"start":{},"end":{}}}"#,
+                    json_string(file),
+                    json_string(&token.kind),
+                    json_string(&token.text),
+                    token.span.start,
+                    token.span.end
+                )),
+            }
+        }
+    }
+    if !csv {
+        println!("[{}]", entries.join(",\n "));
+    }
+    Ok(())
+}
@@ -0,0 +1,78 @@
+//! Interactive grammar debugger.
+//!
+//! Usage: `rlrl-repl <grammar.ebnf>`
+//!
+//! Loads an EBNF grammar (see [rlrl::ebnf]) whose terminals are either quoted
+//! literals or identifiers defined by a `/regex/`, then reads lines from
+//! stdin and prints the token stream and every parse tree (or a diagnostic)
+//! for each line.
+use rlrl::dynamic::DynamicToken;
+use rlrl::earley::{EarleyParser, ParseTree};
+use rlrl::grammar::{Grammar, TerminalLexer};
+use rlrl::prelude::*;
+use std::io::{BufRead, Write};
+
+/// Maximum number of parse trees printed for an ambiguous line.
+const MAX_TREES: usize = 4;
+
+fn print_tree(tree: &ParseTree, texts: &[String], indent: usize) {
+    let pad = "  ".repeat(indent);
+    match tree {
+        ParseTree::Leaf { terminal, idx } => {
+            println!("{pad}{terminal} {:?}", texts[*idx]);
+        }
+        ParseTree::Node { lhs, children, .. } => {
+            println!("{pad}{lhs}");
+            for child in children {
+                print_tree(child, texts, indent + 1);
+            }
+        }
+    }
+}
+
+fn run_line(
+    line: &str,
+    lexer: &TerminalLexer,
+    parser: &EarleyParser<DynamicToken>,
+) -> anyhow::Result<()> {
+    let tokens = lexer.lex(line)?;
+    let summary: Vec<String> = tokens
+        .iter()
+        .map(|token| format!("{}({:?})", token.kind, token.text))
+        .collect();
+    println!("tokens: {}", summary.join(" "));
+
+    let texts: Vec<String> =
+        tokens.iter().map(|token| token.text.clone()).collect();
+    let trees = parser.parse(&TokenQueue::from(tokens))?.trees(MAX_TREES);
+    if trees.len() > 1 {
+        println!("ambiguous: showing {} parses", trees.len());
+    }
+    for tree in &trees {
+        print_tree(tree, &texts, 0);
+    }
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or(anyhow::anyhow!("Usage: rlrl-repl <grammar.ebnf>"))?;
+    let grammar = Grammar::from_ebnf(&std::fs::read_to_string(&path)?)?;
+    let lexer = TerminalLexer::new(&grammar)?;
+    let parser = EarleyParser::new(&grammar, DynamicToken::kind)?;
+
+    let stdin = std::io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        print!("> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next() else {
+            break;
+        };
+        if let Err(err) = run_line(&line?, &lexer, &parser) {
+            println!("error: {err}");
+        }
+    }
+    Ok(())
+}
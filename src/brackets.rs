@@ -0,0 +1,148 @@
+use crate::diagnostic::Diagnostic;
+use crate::span::{Span, Spanned};
+
+/// Checks that bracket tokens in a lexed token stream are balanced and
+/// properly nested before parsing, so a missing `)` is reported where it was
+/// expected instead of as whatever syntax error the parser hits first.
+///
+/// ```
+/// use rlrl::brackets::BracketPairs;
+/// use rlrl::span::{Span, Spanned};
+///
+/// let source = "[(a]";
+/// let tokens: Vec<Spanned<char>> = source
+///     .char_indices()
+///     .map(|(i, c)| Spanned::new(c, Span::new(i, i + 1)))
+///     .collect();
+///
+/// let pairs = BracketPairs::new().with_pair('(', ')').with_pair('[', ']');
+/// let diagnostics = pairs.validate(source, &tokens);
+/// assert!(diagnostics[0].message == "Unclosed `(`!");
+/// assert!(diagnostics[0].span == Span::new(3, 4));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BracketPairs<T> {
+    pairs: Vec<(T, T)>,
+}
+
+impl<T> Default for BracketPairs<T> {
+    fn default() -> Self {
+        Self { pairs: Vec::new() }
+    }
+}
+
+impl<T> BracketPairs<T> {
+    /// Create a validator with no pairs.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check that tokens equal to `open` are closed by tokens equal to
+    /// `close`.
+    pub fn with_pair(mut self, open: T, close: T) -> Self {
+        self.pairs.push((open, close));
+        self
+    }
+}
+
+impl<T: PartialEq> BracketPairs<T> {
+    /// Check the brackets in `tokens`, lexed from `source`, returning an
+    /// error diagnostic for each problem in the order found.
+    ///
+    /// A bracket left open is reported where it should have been closed,
+    /// with a note giving where it was opened: at a closing bracket of an
+    /// enclosing pair, or at the end of input. A closing bracket without an
+    /// open bracket to close is reported at itself, and otherwise ignored.
+    pub fn validate(
+        &self,
+        source: &str,
+        tokens: &[Spanned<T>],
+    ) -> Vec<Diagnostic> {
+        let unclosed = |open: Span, at: Span| {
+            Diagnostic::error(
+                format!("Unclosed `{}`!", &source[open.start..open.end]),
+                at,
+            )
+            .with_note(format!("opened at {open}"))
+        };
+        let mut diagnostics = Vec::new();
+        // the pair index and span of each open bracket, innermost last
+        let mut open: Vec<(usize, Span)> = Vec::new();
+        for token in tokens {
+            if let Some(pair_idx) =
+                self.pairs.iter().position(|(open, _)| token.value == *open)
+            {
+                open.push((pair_idx, token.span));
+                continue;
+            }
+            let Some(pair_idx) = self
+                .pairs
+                .iter()
+                .position(|(_, close)| token.value == *close)
+            else {
+                continue;
+            };
+            match open.iter().rposition(|(open_idx, _)| *open_idx == pair_idx) {
+                Some(idx) => {
+                    for (_, span) in open.drain(idx..).skip(1).rev() {
+                        diagnostics.push(unclosed(span, token.span));
+                    }
+                }
+                None => diagnostics.push(Diagnostic::error(
+                    format!(
+                        "Unmatched `{}`!",
+                        &source[token.span.start..token.span.end]
+                    ),
+                    token.span,
+                )),
+            }
+        }
+        let end = Span::new(source.len(), source.len());
+        for (_, span) in open.into_iter().rev() {
+            diagnostics.push(unclosed(span, end));
+        }
+        diagnostics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::brackets::BracketPairs;
+    use crate::span::{Span, Spanned};
+
+    fn tokens(source: &str) -> Vec<Spanned<char>> {
+        source
+            .char_indices()
+            .filter(|(_, c)| !c.is_whitespace())
+            .map(|(i, c)| Spanned::new(c, Span::new(i, i + 1)))
+            .collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        let pairs = BracketPairs::new()
+            .with_pair('(', ')')
+            .with_pair('[', ']')
+            .with_pair('{', '}');
+        let validate = |source| pairs.validate(source, &tokens(source));
+
+        assert!(validate("{ a[(b)] }").is_empty());
+
+        let diagnostics = validate("{ [(a } )");
+        let messages: Vec<&str> = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert!(
+            messages == ["Unclosed `(`!", "Unclosed `[`!", "Unmatched `)`!"]
+        );
+        assert!(diagnostics[0].span == Span::new(6, 7));
+        assert!(diagnostics[0].notes == ["opened at 3..4"]);
+        assert!(diagnostics[1].notes == ["opened at 2..3"]);
+
+        let diagnostics = validate("(a\n");
+        assert!(
+            diagnostics.len() == 1 && diagnostics[0].span == Span::new(3, 3)
+        );
+    }
+}
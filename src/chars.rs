@@ -0,0 +1,113 @@
+use crate::parse::{Result, TokenQueue};
+use crate::span::{Span, Spanned};
+
+/// Parsing straight from characters, without a lexer. Each character is a
+/// token spanning its bytes in the input, so spans, checkpoints, and
+/// diagnostics work the same as with lexed tokens.
+impl TokenQueue<Spanned<char>> {
+    /// Create a queue of the characters in `s`.
+    pub fn from_chars(s: &str) -> Self {
+        let chars: Vec<Spanned<char>> = s
+            .char_indices()
+            .map(|(pos, c)| Spanned::new(c, Span::new(pos, pos + c.len_utf8())))
+            .collect();
+        Self::from(chars)
+    }
+
+    /// Consume characters while `f` returns true for them, returning them.
+    /// Consumes nothing, returning an empty string, if `f` is false for the
+    /// front character.
+    pub fn take_while(
+        &mut self,
+        mut f: impl FnMut(char) -> bool,
+    ) -> Result<String> {
+        let mut taken = String::new();
+        while self.peek().is_ok_and(|c| f(c.value)) {
+            taken.push(self.consume()?.value);
+        }
+        Ok(taken)
+    }
+
+    /// Consume whitespace characters, returning true if there were any.
+    pub fn skip_whitespace(&mut self) -> Result<bool> {
+        Ok(!self.take_while(char::is_whitespace)?.is_empty())
+    }
+
+    /// Consume an ASCII digit, otherwise return an error.
+    pub fn digit(&mut self) -> Result<char> {
+        self.consume_map("a digit", |c| {
+            c.value.is_ascii_digit().then_some(c.value)
+        })
+    }
+
+    /// Consume a character that can start an identifier (a letter or `_`),
+    /// otherwise return an error.
+    pub fn ident_start(&mut self) -> Result<char> {
+        self.consume_map("an identifier", |c| {
+            is_ident_start(c.value).then_some(c.value)
+        })
+    }
+
+    /// Consume an identifier: a letter or `_`, followed by any letters,
+    /// digits, or `_`s.
+    pub fn ident(&mut self) -> Result<String> {
+        let mut ident = String::from(self.ident_start()?);
+        ident.push_str(&self.take_while(is_ident_continue)?);
+        Ok(ident)
+    }
+}
+
+/// Return true if `c` can start an identifier.
+pub fn is_ident_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+/// Return true if `c` can continue an identifier.
+pub fn is_ident_continue(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{ParseError, ParseResult, TokenQueue};
+    use crate::span::{Span, Spanned};
+
+    /// Parses `name = digits`.
+    fn parse_binding(
+        tq: &TokenQueue<Spanned<char>>,
+    ) -> ParseResult<(String, u32)> {
+        let mut tq = tq.clone();
+        let name = tq.ident()?;
+        tq.skip_whitespace()?;
+        tq.consume_eq('=')?;
+        tq.skip_whitespace()?;
+        let mut digits = String::from(tq.digit()?);
+        digits.push_str(&tq.take_while(|c| c.is_ascii_digit())?);
+        Ok(((name, digits.parse().unwrap()), tq.get_idx()))
+    }
+
+    #[test]
+    fn test_chars() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from_chars("é_1 = 42");
+        let binding = tq.parse_spanned(parse_binding)?;
+        assert!(binding.value == ("é_1".to_owned(), 42));
+        assert!(binding.span == Span::new(0, 9) && tq.is_consumed());
+
+        let mut tq = TokenQueue::from_chars("1x");
+        let err = tq.ident().unwrap_err();
+        assert!(err == ParseError::at(0, "Expected an identifier!"));
+        assert!(tq.take_while(char::is_alphabetic)?.is_empty());
+        assert!(tq.digit()? == '1' && tq.peek_span()? == Span::new(1, 2));
+
+        // closures can capture state, like a limit on the length
+        let mut tq = TokenQueue::from_chars("abcd");
+        let mut left = 3;
+        let taken = tq.take_while(|_| {
+            left -= 1;
+            left >= 0
+        })?;
+        assert!(taken == "abc" && tq.peek()?.value == 'd');
+
+        Ok(())
+    }
+}
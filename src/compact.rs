@@ -0,0 +1,225 @@
+//! Compact storage for very large token streams.
+//!
+//! A `Vec` of a token enum with payloads, like `Ident(String)`, spends the
+//! size of the largest variant on every token, even the many that are just
+//! punctuation. [CompactTokens] instead stores the tokens as a struct of
+//! arrays: a small `Copy` kind per token, its span as two `u32`s, and an
+//! index into a separate list holding only the payloads that exist.
+//!
+//! Parse functions run over a `TokenQueue` of the kinds, which is as cheap
+//! to scan as a byte array, and look up spans and payloads by token index in
+//! the [CompactTokens], passed as the context of
+//! [TokenQueue::parse_with].
+//!
+//! ```
+//! use rlrl::compact::CompactTokens;
+//! use rlrl::parse::{ParseResult, TokenQueue};
+//! use rlrl::span::Span;
+//!
+//! #[derive(Debug, Clone, Copy, PartialEq)]
+//! enum Kind {
+//!     Ident,
+//!     Comma,
+//! }
+//!
+//! fn parse_ident(
+//!     tq: &TokenQueue<Kind>,
+//!     tokens: &CompactTokens<Kind, String>,
+//! ) -> ParseResult<String> {
+//!     let mut tq = tq.clone();
+//!     let name = tokens.payload(tq.get_idx()).cloned().unwrap_or_default();
+//!     tq.consume_eq(Kind::Ident)?;
+//!     Ok((name, tq.get_idx()))
+//! }
+//!
+//! let mut tokens = CompactTokens::new();
+//! tokens.push(Kind::Ident, Span::new(0, 1), Some("a".to_owned()));
+//! tokens.push(Kind::Comma, Span::new(1, 2), None);
+//! tokens.push(Kind::Ident, Span::new(2, 3), Some("b".to_owned()));
+//!
+//! let mut tq = tokens.queue();
+//! assert!(tq.parse_with(parse_ident, &tokens).unwrap() == "a");
+//! assert!(tq.consume_eq(Kind::Comma).is_ok());
+//! assert!(tq.parse_with(parse_ident, &tokens).unwrap() == "b");
+//! ```
+
+use crate::lex::LexerMatch;
+use crate::parse::{ParseError, TokenQueue};
+use crate::span::Span;
+
+/// Marks a token without a payload in [CompactTokens::payload_idxs].
+const NO_PAYLOAD: u32 = u32::MAX;
+
+/// A token stream stored as a struct of arrays, with kinds of type `K` and
+/// payloads of type `P`. Spans and payload indices are stored as `u32`s, so
+/// inputs are limited to 4 GiB and streams to 4 billion payloads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompactTokens<K, P> {
+    kinds: Vec<K>,
+    starts: Vec<u32>,
+    ends: Vec<u32>,
+    /// The index into `payloads` of each token's payload, or [NO_PAYLOAD].
+    payload_idxs: Vec<u32>,
+    payloads: Vec<P>,
+}
+
+impl<K, P> Default for CompactTokens<K, P> {
+    fn default() -> Self {
+        Self {
+            kinds: Vec::new(),
+            starts: Vec::new(),
+            ends: Vec::new(),
+            payload_idxs: Vec::new(),
+            payloads: Vec::new(),
+        }
+    }
+}
+
+impl<K: Copy, P> CompactTokens<K, P> {
+    /// Create an empty token stream.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the lexer matches `matches`, splitting each token into its kind
+    /// and payload (if any) with `split`.
+    pub fn from_matches<T>(
+        matches: impl IntoIterator<Item = LexerMatch<T>>,
+        split: impl Fn(T) -> (K, Option<P>),
+    ) -> Self {
+        let mut tokens = Self::new();
+        for lexer_match in matches {
+            let span = lexer_match.span();
+            let (kind, payload) = split(lexer_match.into_token());
+            tokens.push(kind, span, payload);
+        }
+        tokens
+    }
+
+    /// Add a token to the end of the stream.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the span ends past 4 GiB or the stream already has
+    /// `u32::MAX` payloads.
+    pub fn push(&mut self, kind: K, span: Span, payload: Option<P>) {
+        let offset = |pos: usize| {
+            u32::try_from(pos).expect("Span passed to CompactTokens too large")
+        };
+        let payload_idx = match payload {
+            Some(payload) => {
+                let idx = u32::try_from(self.payloads.len())
+                    .ok()
+                    .filter(|idx| *idx != NO_PAYLOAD)
+                    .expect("Too many payloads for CompactTokens");
+                self.payloads.push(payload);
+                idx
+            }
+            None => NO_PAYLOAD,
+        };
+        self.kinds.push(kind);
+        self.starts.push(offset(span.start));
+        self.ends.push(offset(span.end));
+        self.payload_idxs.push(payload_idx);
+    }
+
+    /// Get the number of tokens.
+    pub fn len(&self) -> usize {
+        self.kinds.len()
+    }
+
+    /// Return true if there are no tokens.
+    pub fn is_empty(&self) -> bool {
+        self.kinds.is_empty()
+    }
+
+    /// Get the kind of the token at index `idx`.
+    pub fn kind(&self, idx: usize) -> Option<K> {
+        self.kinds.get(idx).copied()
+    }
+
+    /// Get the span of the token at index `idx`, or an empty span at the end
+    /// of the last token if `idx` is past it, as for an error at the end of
+    /// input.
+    pub fn span(&self, idx: usize) -> Span {
+        match (self.starts.get(idx), self.ends.get(idx)) {
+            (Some(start), Some(end)) => {
+                Span::new(*start as usize, *end as usize)
+            }
+            _ => {
+                let end = self.ends.last().map_or(0, |end| *end as usize);
+                Span::new(end, end)
+            }
+        }
+    }
+
+    /// Borrow the payload of the token at index `idx`, if it has one.
+    pub fn payload(&self, idx: usize) -> Option<&P> {
+        match *self.payload_idxs.get(idx)? {
+            NO_PAYLOAD => None,
+            payload_idx => self.payloads.get(payload_idx as usize),
+        }
+    }
+
+    /// Get the span of the input to report `err` at, with `tq` a queue over
+    /// these tokens, like [TokenQueue::error_span].
+    pub fn error_span(&self, tq: &TokenQueue<K>, err: &ParseError) -> Span {
+        self.span(err.idx().unwrap_or(tq.get_idx()))
+    }
+
+    /// Create a queue over the kinds of the tokens, to parse with this
+    /// stream as context.
+    pub fn queue(&self) -> TokenQueue<K> {
+        TokenQueue::from(self.kinds.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compact::CompactTokens;
+    use crate::lex::{LexResult, Lexer};
+    use crate::parse::ParseError;
+    use crate::span::Span;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token {
+        Plus,
+        Int(i64),
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq)]
+    enum Kind {
+        Plus,
+        Int,
+    }
+
+    #[test]
+    fn test_from_matches() -> anyhow::Result<()> {
+        let mut lexer = Lexer::new();
+        lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+        lexer.add_rule(r"\+", |_| LexResult::Token(Token::Plus));
+        lexer.add_try_rule(r"[0-9]+", |int_match| {
+            Ok(Some(Token::Int(int_match.as_str().parse()?)))
+        });
+
+        let matches = lexer.lex_matches("1 + 23 +")?;
+        let tokens =
+            CompactTokens::from_matches(matches, |token| match token {
+                Token::Plus => (Kind::Plus, None),
+                Token::Int(val) => (Kind::Int, Some(val)),
+            });
+        assert!(tokens.len() == 4);
+        assert!(tokens.kind(2) == Some(Kind::Int) && tokens.kind(4).is_none());
+        assert!(tokens.payload(2) == Some(&23) && tokens.payload(1).is_none());
+        assert!(tokens.span(2) == Span::new(4, 6));
+        assert!(tokens.span(4) == Span::new(8, 8));
+
+        let mut tq = tokens.queue();
+        tq.go_to(4)?;
+        let err = ParseError::at(4, "Expected an int!");
+        assert!(tokens.error_span(&tq, &err) == Span::new(8, 8));
+        assert!(CompactTokens::<Kind, i64>::new().span(0) == Span::new(0, 0));
+
+        Ok(())
+    }
+}
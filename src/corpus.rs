@@ -0,0 +1,286 @@
+//! Regression testing of a language against a corpus of sample inputs: each
+//! sample is lexed and parsed by a function that renders the result (the
+//! tokens, the tree, or the errors) as text, which is compared against a
+//! snapshot stored next to the sample. Blessing the corpus writes the
+//! current output as the new snapshots, to accept an intended change.
+//!
+//! ```no_run
+//! use rlrl::corpus::Corpus;
+//! use rlrl::lex::Lexer;
+//!
+//! let lexer: Lexer<String> = Lexer::new();
+//! Corpus::new("tests/corpus")
+//!     .with_extension("calc")
+//!     .run(|text| match lexer.lex(text) {
+//!         Ok(tokens) => format!("{tokens:#?}"),
+//!         Err(err) => format!("error: {err}"),
+//!     })
+//!     .unwrap()
+//!     .assert_ok();
+//! ```
+
+use crate::Result;
+use std::fmt::Display;
+use std::path::{Path, PathBuf};
+
+/// The environment variable that blesses a [Corpus] by default when set, so
+/// snapshots can be updated with `RLRL_BLESS=1 cargo test`.
+pub const BLESS_VAR: &str = "RLRL_BLESS";
+
+/// The extension appended to a sample's file name to name its snapshot.
+pub const SNAPSHOT_EXTENSION: &str = "snap";
+
+/// A directory of sample inputs, each with a snapshot of its expected
+/// output in a file named after it with `.snap` appended, like
+/// `add.calc.snap` for `add.calc`. Subdirectories are searched too.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Corpus {
+    dir: PathBuf,
+    extension: Option<String>,
+    bless: bool,
+}
+
+impl Corpus {
+    /// Create a corpus of the files in `dir`, blessed if the [BLESS_VAR]
+    /// environment variable is set.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            extension: None,
+            bless: std::env::var_os(BLESS_VAR).is_some(),
+        }
+    }
+
+    /// Only treat files with the extension `extension` as samples, so other
+    /// files, like a README, can live in the directory.
+    pub fn with_extension(mut self, extension: impl Into<String>) -> Self {
+        self.extension = Some(extension.into());
+        self
+    }
+
+    /// Set whether running the corpus writes the output of each sample as
+    /// its snapshot, instead of comparing them.
+    pub fn with_bless(mut self, bless: bool) -> Self {
+        self.bless = bless;
+        self
+    }
+
+    /// Get the paths of the samples, in order.
+    pub fn samples(&self) -> Result<Vec<PathBuf>> {
+        let mut samples = Vec::new();
+        let mut dirs = vec![self.dir.clone()];
+        while let Some(dir) = dirs.pop() {
+            for entry in std::fs::read_dir(dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if self.is_sample(&path) {
+                    samples.push(path);
+                }
+            }
+        }
+        samples.sort();
+        Ok(samples)
+    }
+
+    /// Return true if `path` is a sample rather than a snapshot or a file
+    /// without the sample extension.
+    fn is_sample(&self, path: &Path) -> bool {
+        let extension = path.extension().and_then(|ext| ext.to_str());
+        match &self.extension {
+            Some(sample) => extension == Some(sample.as_str()),
+            None => extension != Some(SNAPSHOT_EXTENSION),
+        }
+    }
+
+    /// Render each sample with `render`, comparing the output with its
+    /// snapshot, or writing the snapshot if the corpus is blessed. Fails only
+    /// if reading or writing files fails; mismatches are in the report.
+    pub fn run(
+        &self,
+        mut render: impl FnMut(&str) -> String,
+    ) -> Result<CorpusReport> {
+        let mut report = CorpusReport::default();
+        for sample in self.samples()? {
+            let actual = render(&std::fs::read_to_string(&sample)?);
+            let snapshot = snapshot_path(&sample);
+            let expected = std::fs::read_to_string(&snapshot).ok();
+            if expected.as_ref() == Some(&actual) {
+                report.passed.push(sample);
+            } else if self.bless {
+                std::fs::write(&snapshot, &actual)?;
+                report.blessed.push(sample);
+            } else {
+                report.failed.push(Mismatch {
+                    sample,
+                    expected,
+                    actual,
+                });
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Get the path of the snapshot of the sample at `sample`.
+fn snapshot_path(sample: &Path) -> PathBuf {
+    let mut path = sample.as_os_str().to_owned();
+    path.push(".");
+    path.push(SNAPSHOT_EXTENSION);
+    path.into()
+}
+
+/// A sample whose output didn't match its snapshot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    pub sample: PathBuf,
+    /// The snapshot, or `None` if the sample has none yet.
+    pub expected: Option<String>,
+    pub actual: String,
+}
+
+impl Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sample = self.sample.display();
+        let Some(expected) = &self.expected else {
+            return write!(f, "{sample}: no snapshot");
+        };
+        let mut expected_lines = expected.lines();
+        let mut actual_lines = self.actual.lines();
+        for line in 1.. {
+            match (expected_lines.next(), actual_lines.next()) {
+                (None, None) => break,
+                (Some(expected), Some(actual)) if expected == actual => {}
+                (expected, actual) => {
+                    writeln!(f, "{sample}: line {line} differs")?;
+                    writeln!(f, "  expected: {}", expected.unwrap_or("<end>"))?;
+                    return write!(
+                        f,
+                        "  actual:   {}",
+                        actual.unwrap_or("<end>")
+                    );
+                }
+            }
+        }
+
+        // the lines are the same, so the line endings differ
+        let lines = expected.split('\n').zip(self.actual.split('\n'));
+        for (line, (expected, actual)) in (1..).zip(lines) {
+            if expected != actual {
+                writeln!(f, "{sample}: line {line} ending differs")?;
+                writeln!(f, "  expected: {}", line_ending(expected))?;
+                return write!(f, "  actual:   {}", line_ending(actual));
+            }
+        }
+        let trailing = |text: &str| match text.ends_with('\n') {
+            true => "a trailing newline",
+            false => "no trailing newline",
+        };
+        writeln!(f, "{sample}: trailing newline differs")?;
+        writeln!(f, "  expected: {}", trailing(expected))?;
+        write!(f, "  actual:   {}", trailing(&self.actual))
+    }
+}
+
+/// Name the line ending of `line`, split from text at `\n`.
+fn line_ending(line: &str) -> &'static str {
+    match line.ends_with('\r') {
+        true => "CRLF",
+        false => "LF",
+    }
+}
+
+/// The outcome of running a [Corpus]: the samples that matched their
+/// snapshots, those that didn't, and those whose snapshots were written.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CorpusReport {
+    pub passed: Vec<PathBuf>,
+    pub failed: Vec<Mismatch>,
+    pub blessed: Vec<PathBuf>,
+}
+
+impl CorpusReport {
+    /// Return true if no sample failed.
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Panic with the report if any sample failed, for use in tests.
+    pub fn assert_ok(&self) {
+        assert!(self.is_ok(), "{self}");
+    }
+}
+
+impl Display for CorpusReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} passed, {} failed, {} blessed",
+            self.passed.len(),
+            self.failed.len(),
+            self.blessed.len()
+        )?;
+        for mismatch in &self.failed {
+            write!(f, "\n{mismatch}")?;
+        }
+        if !self.is_ok() {
+            write!(f, "\nset {BLESS_VAR}=1 to update the snapshots")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::corpus::Corpus;
+    use std::fs;
+
+    #[test]
+    fn test_corpus() -> anyhow::Result<()> {
+        let dir = std::env::temp_dir()
+            .join(format!("rlrl-test-corpus-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested"))?;
+        fs::write(dir.join("a.txt"), "ab")?;
+        fs::write(dir.join("nested/b.txt"), "c")?;
+        fs::write(dir.join("README"), "not a sample")?;
+        let corpus = Corpus::new(&dir).with_extension("txt").with_bless(false);
+        let render =
+            |text: &str| text.chars().map(|c| format!("{c}\n")).collect();
+
+        let report = corpus.run(render)?;
+        assert!(!report.is_ok() && report.failed[0].expected.is_none());
+        assert!(report.to_string().starts_with("0 passed, 2 failed"));
+
+        let report = corpus.clone().with_bless(true).run(render)?;
+        assert!(report.blessed.len() == 2);
+        assert!(fs::read_to_string(dir.join("a.txt.snap"))? == "a\nb\n");
+        corpus.run(render)?.assert_ok();
+
+        fs::write(dir.join("a.txt"), "ax")?;
+        let report = corpus.run(render)?;
+        assert!(report.passed.len() == 1);
+        assert!(
+            report.failed[0].to_string().ends_with(
+                "a.txt: line 2 differs\n  expected: b\n  actual:   x"
+            )
+        );
+
+        // differences the lines alone don't show
+        fs::write(dir.join("a.txt"), "ab")?;
+        fs::write(dir.join("a.txt.snap"), "a\r\nb\r\n")?;
+        let report = corpus.run(render)?;
+        assert!(report.failed[0].to_string().ends_with(
+            "a.txt: line 1 ending differs\n  expected: CRLF\n  actual:   LF"
+        ));
+        fs::write(dir.join("a.txt.snap"), "a\nb")?;
+        let report = corpus.run(render)?;
+        assert!(report.failed[0].to_string().ends_with(
+            "a.txt: trailing newline differs\n  \
+             expected: no trailing newline\n  \
+             actual:   a trailing newline"
+        ));
+
+        fs::remove_dir_all(&dir)?;
+        Ok(())
+    }
+}
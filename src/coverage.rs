@@ -0,0 +1,193 @@
+//! Coverage of a [Grammar] by parses, for finding the parts of a language a
+//! test suite never exercises. Each production is a branch of the rule on
+//! its left hand side, so a production no test input derives is an
+//! untested alternative, and a rule with none derived is untested entirely.
+//!
+//! ```
+//! use rlrl::coverage::Coverage;
+//! use rlrl::earley::EarleyParser;
+//! use rlrl::grammar::{Grammar, Symbol};
+//! use rlrl::parse::TokenQueue;
+//!
+//! let mut grammar = Grammar::new("list");
+//! grammar.add_production("list", vec![Symbol::t("x")]);
+//! grammar.add_production("list", vec![Symbol::t("x"), Symbol::nt("list")]);
+//! let parser = EarleyParser::new(&grammar, |_: &char| "x").unwrap();
+//!
+//! let forest = parser.parse(&TokenQueue::from(vec!['x'])).unwrap();
+//! let mut coverage = Coverage::new(&grammar);
+//! coverage.record_forest(&forest);
+//! assert!(coverage.to_string().starts_with("1 of 2 productions covered"));
+//! ```
+
+use crate::earley::{Forest, ParseTree};
+use crate::grammar::{Grammar, Production};
+use std::fmt::Display;
+
+/// Counts how many times each production of a grammar was used by the
+/// parses recorded. Displays as a report of the productions and rules never
+/// used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coverage<'a> {
+    grammar: &'a Grammar,
+    hits: Vec<usize>,
+}
+
+impl<'a> Coverage<'a> {
+    /// Create a record of coverage of `grammar`, with no productions used.
+    pub fn new(grammar: &'a Grammar) -> Self {
+        Self {
+            grammar,
+            hits: vec![0; grammar.productions().len()],
+        }
+    }
+
+    /// Record a use of the production with index `production`.
+    pub fn record(&mut self, production: usize) {
+        self.hits[production] += 1;
+    }
+
+    /// Record the productions used by `tree`, a parse tree from an
+    /// [EarleyParser](crate::earley::EarleyParser).
+    pub fn record_tree(&mut self, tree: &ParseTree) {
+        if let ParseTree::Node {
+            production,
+            children,
+            ..
+        } = tree
+        {
+            self.record(*production);
+            for child in children {
+                self.record_tree(child);
+            }
+        }
+    }
+
+    /// Record the productions used by every derivation in `forest`, once
+    /// per node of the forest deriving them.
+    pub fn record_forest(&mut self, forest: &Forest) {
+        for node in forest.nodes() {
+            for family in &node.families {
+                self.record(family.production);
+            }
+        }
+    }
+
+    /// Add the uses recorded by `other`, like coverage from another test,
+    /// of the same grammar.
+    pub fn merge(&mut self, other: &Coverage) {
+        for (hits, other) in self.hits.iter_mut().zip(&other.hits) {
+            *hits += other;
+        }
+    }
+
+    /// Get the number of uses of the production with index `production`.
+    pub fn hits(&self, production: usize) -> usize {
+        self.hits[production]
+    }
+
+    /// Get the index and production of each production never used.
+    pub fn uncovered_productions(&self) -> Vec<(usize, &'a Production)> {
+        let productions = self.grammar.productions().iter().enumerate();
+        productions
+            .filter(|(production, _)| self.hits[*production] == 0)
+            .collect()
+    }
+
+    /// Get the name of each rule (non-terminal) none of whose productions
+    /// were used.
+    pub fn uncovered_rules(&self) -> Vec<&'a str> {
+        let grammar: &'a Grammar = self.grammar;
+        grammar
+            .nonterminals()
+            .into_iter()
+            .filter(|rule| {
+                grammar.productions().iter().zip(&self.hits).all(
+                    |(production, hits)| production.lhs != *rule || *hits == 0,
+                )
+            })
+            .collect()
+    }
+}
+
+impl Display for Coverage<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let total = self.hits.len();
+        let covered = self.hits.iter().filter(|hits| **hits > 0).count();
+        writeln!(f, "{covered} of {total} productions covered")?;
+        for rule in self.uncovered_rules() {
+            writeln!(f, "  rule never used: {rule}")?;
+        }
+        for (idx, production) in self.uncovered_productions() {
+            writeln!(f, "  production {idx} never used: {production}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::coverage::Coverage;
+    use crate::earley::EarleyParser;
+    use crate::grammar::{Grammar, Symbol};
+    use crate::lr::{LrKind, LrParser, LrTable};
+    use crate::parse::TokenQueue;
+
+    fn classify(token: &char) -> &str {
+        match token {
+            '+' => "+",
+            '-' => "-",
+            _ => "num",
+        }
+    }
+
+    fn grammar() -> Grammar {
+        let mut grammar = Grammar::new("expr");
+        grammar.add_production(
+            "expr",
+            vec![Symbol::nt("expr"), Symbol::t("+"), Symbol::nt("atom")],
+        );
+        grammar.add_production("expr", vec![Symbol::nt("atom")]);
+        grammar.add_production("atom", vec![Symbol::t("num")]);
+        grammar.add_production("atom", vec![Symbol::t("-"), Symbol::nt("neg")]);
+        grammar.add_production("neg", vec![Symbol::t("num")]);
+        grammar
+    }
+
+    #[test]
+    fn test_coverage() -> anyhow::Result<()> {
+        let grammar = grammar();
+        let tq = TokenQueue::from(vec!['1', '+', '2']);
+
+        let parser = EarleyParser::new(&grammar, classify)?;
+        let mut earley = Coverage::new(&grammar);
+        for tree in parser.parse(&tq)?.trees(1) {
+            earley.record_tree(&tree);
+        }
+        assert!(earley.hits(2) == 2);
+        assert!(earley.uncovered_rules() == ["neg"]);
+        assert!(
+            earley.to_string()
+                == "3 of 5 productions covered\n  \
+                    rule never used: neg\n  \
+                    production 3 never used: atom -> \"-\" neg\n  \
+                    production 4 never used: neg -> \"num\"\n"
+        );
+
+        let table = LrTable::build(&grammar, LrKind::Lalr)?;
+        let mut parser = LrParser::new(table, classify, |_| ());
+        for production in 0..grammar.productions().len() {
+            parser.on_reduce(production, |_| Ok(()));
+        }
+        let mut lr = Coverage::new(&grammar);
+        parser
+            .parse_with_coverage(&TokenQueue::from(vec!['-', '1']), &mut lr)?;
+        assert!(lr.uncovered_productions().len() == 2);
+
+        earley.merge(&lr);
+        assert!(earley.uncovered_productions().is_empty());
+        assert!(earley.uncovered_rules().is_empty());
+
+        Ok(())
+    }
+}
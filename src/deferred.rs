@@ -0,0 +1,151 @@
+//! Deferred parsing of regions of the input, for fast "outline only" parses:
+//! an IDE building a symbol index can parse every function signature in a
+//! large file while skipping the bodies, and parse a body only when it's
+//! opened.
+//!
+//! ```
+//! use rlrl::deferred::Deferred;
+//! use rlrl::parse::{ParseResult, TokenQueue};
+//!
+//! /// Counts the letters in a `{` ... `}` body.
+//! fn parse_body(tq: &TokenQueue<char>) -> ParseResult<usize> {
+//!     let mut tq = tq.clone();
+//!     tq.consume_eq('{')?;
+//!     let mut letters = 0;
+//!     while tq.consume_eq('}').is_err() {
+//!         tq.consume_matching(char::is_ascii_alphabetic)?;
+//!         letters += 1;
+//!     }
+//!     Ok((letters, tq.get_idx()))
+//! }
+//!
+//! let mut tq = TokenQueue::from("f{ab}g{c".chars().collect::<Vec<_>>());
+//! tq.consume_eq('f').unwrap();
+//! let body = Deferred::skip(&mut tq, '{', '}', parse_body).unwrap();
+//! assert!(tq.at(&'g') && !body.is_parsed());
+//! assert!(*body.get().unwrap() == 2);
+//! ```
+
+use crate::parse::{ParseError, ParseFn, Result, TokenQueue};
+use std::cell::OnceCell;
+use std::ops::Range;
+
+/// A region of a token queue recorded now and parsed on demand with a
+/// [ParseFn], at most once. The region is parsed from a clone of the queue
+/// it was recorded from, so it keeps the queue's settings, like which
+/// tokens are newlines.
+pub struct Deferred<L, T> {
+    tq: TokenQueue<L>,
+    range: Range<usize>,
+    parse_fn: ParseFn<L, T>,
+    result: OnceCell<Result<T>>,
+}
+
+impl<L, T> Deferred<L, T> {
+    /// Record the tokens at the indices in `range` of `tq`, to be parsed
+    /// with `parse_fn`.
+    pub fn new(
+        tq: &TokenQueue<L>,
+        range: Range<usize>,
+        parse_fn: ParseFn<L, T>,
+    ) -> Self {
+        Self {
+            tq: tq.clone(),
+            range,
+            parse_fn,
+            result: OnceCell::new(),
+        }
+    }
+
+    /// Record the balanced region at the front of `tq`, from an `open` token
+    /// to its matching `close` token, and move `tq` past it, like
+    /// [TokenQueue::skip_balanced].
+    pub fn skip<U>(
+        tq: &mut TokenQueue<L>,
+        open: U,
+        close: U,
+        parse_fn: ParseFn<L, T>,
+    ) -> Result<Self>
+    where
+        L: PartialEq<U>,
+    {
+        let range = tq.skip_balanced(open, close)?;
+        Ok(Self::new(tq, range, parse_fn))
+    }
+
+    /// Get the range of token indices recorded.
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Return true if the region has been parsed.
+    pub fn is_parsed(&self) -> bool {
+        self.result.get().is_some()
+    }
+
+    /// Parse the region if it hasn't been yet, borrowing the value parsed.
+    /// Parsing must consume exactly the recorded tokens. Errors refer to
+    /// token indices in the original queue.
+    pub fn get(&self) -> Result<&T> {
+        self.result
+            .get_or_init(|| {
+                let mut tq = self.tq.clone();
+                tq.go_to(self.range.start)?;
+                let val = tq.parse(self.parse_fn)?;
+                match tq.get_idx() == self.range.end {
+                    true => Ok(val),
+                    false => Err(ParseError::at(
+                        tq.get_idx(),
+                        "Expected the end of the deferred region!",
+                    )),
+                }
+            })
+            .as_ref()
+            .map_err(Clone::clone)
+    }
+
+    /// Parse the region if it hasn't been yet, returning the value parsed.
+    pub fn into_inner(self) -> Result<T> {
+        self.get()?;
+        self.result.into_inner().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deferred::Deferred;
+    use crate::parse::{ParseResult, TokenQueue};
+
+    /// Parses `(` and a letter, leaving the `)`.
+    fn parse_open(tq: &TokenQueue<char>) -> ParseResult<char> {
+        let mut tq = tq.clone();
+        tq.consume_eq('(')?;
+        let letter = *tq.consume()?;
+        Ok((letter, tq.get_idx()))
+    }
+
+    /// Parses a letter in parentheses.
+    fn parse_group(tq: &TokenQueue<char>) -> ParseResult<char> {
+        let mut tq = tq.clone();
+        let letter = tq.parse(parse_open)?;
+        tq.consume_eq(')')?;
+        Ok((letter, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_deferred() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("(a)(b".chars().collect::<Vec<_>>());
+
+        let group = Deferred::skip(&mut tq, '(', ')', parse_group)?;
+        assert!(group.range() == (0..3) && tq.get_idx() == 3);
+        assert!(*group.get()? == 'a' && group.is_parsed());
+        assert!(group.into_inner()? == 'a');
+        assert!(Deferred::skip(&mut tq, '(', ')', parse_group).is_err());
+
+        let open = Deferred::new(&tq, 0..3, parse_open);
+        let err = open.get().unwrap_err();
+        assert!(err.idx() == Some(2) && open.get().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,206 @@
+use crate::span::Spanned;
+use std::fmt::{self, Write};
+
+/// Implemented by token types that can be written back as source text, so a
+/// token stream can be turned into source again with a [Detokenizer].
+///
+/// Tokens whose `Display` gives their source text can implement this with
+/// `write!(out, "{self}")`.
+pub trait Detokenize {
+    /// Write the token's source text to `out`.
+    fn detokenize(&self, out: &mut dyn Write) -> fmt::Result;
+}
+
+impl<T: Detokenize> Detokenize for Spanned<T> {
+    fn detokenize(&self, out: &mut dyn Write) -> fmt::Result {
+        self.value.detokenize(out)
+    }
+}
+
+/// How a [Detokenizer] separates tokens that have no source text between
+/// them.
+pub enum Spacing<T> {
+    /// Write tokens with nothing between them.
+    Tight,
+    /// Write a single space between tokens.
+    Single,
+    /// Write the string returned for each pair of adjacent tokens between
+    /// them.
+    Custom(fn(&T, &T) -> &'static str),
+}
+
+impl<T> Clone for Spacing<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Spacing<T> {}
+
+impl<T> Spacing<T> {
+    fn between(&self, prev: &T, next: &T) -> &'static str {
+        match self {
+            Self::Tight => "",
+            Self::Single => " ",
+            Self::Custom(spacing) => spacing(prev, next),
+        }
+    }
+}
+
+/// Reconstructs source text from tokens of type `T`. The default separates
+/// tokens with single spaces.
+#[derive(Clone, Copy)]
+pub struct Detokenizer<T> {
+    spacing: Spacing<T>,
+}
+
+impl<T> Default for Detokenizer<T> {
+    fn default() -> Self {
+        Self {
+            spacing: Spacing::Single,
+        }
+    }
+}
+
+impl<T> Detokenizer<T> {
+    /// Set how tokens are separated.
+    pub fn with_spacing(mut self, spacing: Spacing<T>) -> Self {
+        self.spacing = spacing;
+        self
+    }
+}
+
+impl<T: Detokenize> Detokenizer<T> {
+    /// Write `tokens` to `out`, separated according to the spacing.
+    pub fn write(&self, tokens: &[T], out: &mut dyn Write) -> fmt::Result {
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 {
+                out.write_str(self.spacing.between(&tokens[i - 1], token))?;
+            }
+            token.detokenize(out)?;
+        }
+        Ok(())
+    }
+
+    /// Reconstruct source text from `tokens`, separated according to the
+    /// spacing.
+    pub fn detokenize(&self, tokens: &[T]) -> String {
+        let mut out = String::new();
+        self.write(tokens, &mut out)
+            .expect("Writing to a String can't fail");
+        out
+    }
+
+    /// Write `tokens`, lexed from `source`, to `out`, copying the source
+    /// text around and between them (whitespace, comments) from `source`.
+    /// Where a token's span doesn't follow the previous token's, as for a
+    /// token inserted or moved by a transformation, tokens are separated
+    /// according to the spacing instead.
+    pub fn write_spanned(
+        &self,
+        source: &str,
+        tokens: &[Spanned<T>],
+        out: &mut dyn Write,
+    ) -> fmt::Result {
+        let mut pos = 0;
+        for (i, token) in tokens.iter().enumerate() {
+            if token.span.start >= pos && token.span.start <= source.len() {
+                out.write_str(&source[pos..token.span.start])?;
+            } else if i > 0 {
+                out.write_str(
+                    self.spacing.between(&tokens[i - 1].value, &token.value),
+                )?;
+            }
+            token.detokenize(out)?;
+            pos = token.span.end.max(pos);
+        }
+        if pos < source.len() {
+            out.write_str(&source[pos..])?;
+        }
+        Ok(())
+    }
+
+    /// Reconstruct source text from `tokens`, lexed from `source`, like
+    /// [Detokenizer::write_spanned].
+    pub fn detokenize_spanned(
+        &self,
+        source: &str,
+        tokens: &[Spanned<T>],
+    ) -> String {
+        let mut out = String::new();
+        self.write_spanned(source, tokens, &mut out)
+            .expect("Writing to a String can't fail");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::detokenize::{Detokenize, Detokenizer, Spacing};
+    use crate::lex::{LexResult, Lexer};
+    use crate::span::{Span, Spanned};
+    use std::error::Error;
+    use std::fmt::{self, Write};
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token {
+        Ident(String),
+        Semi,
+    }
+
+    impl Detokenize for Token {
+        fn detokenize(&self, out: &mut dyn Write) -> fmt::Result {
+            match self {
+                Self::Ident(name) => out.write_str(name),
+                Self::Semi => out.write_char(';'),
+            }
+        }
+    }
+
+    fn setup_lexer() -> Lexer<Token> {
+        let mut lexer = Lexer::c_style();
+        lexer.add_rule(r"[a-z]+", |ident_match| {
+            LexResult::Token(Token::Ident(ident_match.as_str().to_owned()))
+        });
+        lexer.add_rule(";", |_| LexResult::Token(Token::Semi));
+        lexer
+    }
+
+    #[test]
+    fn test_detokenize() {
+        let tokens = vec![
+            Token::Ident("a".to_owned()),
+            Token::Ident("b".to_owned()),
+            Token::Semi,
+        ];
+
+        assert!(Detokenizer::default().detokenize(&tokens) == "a b ;");
+        let detokenizer =
+            Detokenizer::default().with_spacing(Spacing::Custom(|_, next| {
+                match next {
+                    Token::Semi => "",
+                    _ => " ",
+                }
+            }));
+        assert!(detokenizer.detokenize(&tokens) == "a b;");
+        let detokenizer = Detokenizer::default().with_spacing(Spacing::Tight);
+        assert!(detokenizer.detokenize(&tokens) == "ab;");
+    }
+
+    #[test]
+    fn test_detokenize_spanned() -> Result<(), Box<dyn Error>> {
+        let source = " a /* x */ b;\n// end\n";
+        let mut tokens = setup_lexer().lex_spanned(source)?;
+        let detokenizer = Detokenizer::default();
+        assert!(detokenizer.detokenize_spanned(source, &tokens) == source);
+
+        tokens[1].value = Token::Ident("renamed".to_owned());
+        tokens.insert(1, Spanned::new(Token::Semi, Span::new(0, 0)));
+        assert!(
+            detokenizer.detokenize_spanned(source, &tokens)
+                == " a ; /* x */ renamed;\n// end\n"
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,530 @@
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::suggest::suggest;
+use crate::{Error, Result};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Write};
+
+/// The severity of a [Diagnostic].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Note => write!(f, "note"),
+            Self::Warning => write!(f, "warning"),
+            Self::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// How confident a [Suggestion] is, which decides whether tools may apply
+/// it without asking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Applicability {
+    /// The suggestion is certainly what was meant, and can be applied
+    /// automatically.
+    MachineApplicable,
+    /// The suggestion may not be what was meant, so the user should confirm
+    /// it.
+    MaybeIncorrect,
+    /// The replacement has placeholders for the user to fill in.
+    HasPlaceholders,
+}
+
+/// A suggested edit fixing the problem a [Diagnostic] reports: replace the
+/// input in `span` with `replacement`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Suggestion {
+    /// Describes the edit, like "did you mean `let`?".
+    pub message: String,
+    /// Byte span of the input to replace. An empty span inserts.
+    pub span: Span,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    /// Create a suggestion to replace `span` with `replacement`.
+    pub fn new(
+        message: impl Into<String>,
+        span: Span,
+        replacement: impl Into<String>,
+        applicability: Applicability,
+    ) -> Self {
+        Self {
+            message: message.into(),
+            span,
+            replacement: replacement.into(),
+            applicability,
+        }
+    }
+
+    /// Suggest replacing `word`, at `span`, with the candidate closest to it
+    /// by edit distance, like a keyword it misspells. Returns `None` if no
+    /// candidate is close enough, as decided by [suggest].
+    pub fn did_you_mean<'a>(
+        word: &str,
+        span: Span,
+        candidates: impl IntoIterator<Item = &'a str>,
+    ) -> Option<Self> {
+        let closest = *suggest(word, candidates).first()?;
+        Some(Self::new(
+            format!("did you mean `{closest}`?"),
+            span,
+            closest,
+            Applicability::MaybeIncorrect,
+        ))
+    }
+}
+
+/// Apply the [Applicability::MachineApplicable] suggestions of
+/// `diagnostics` to `source`, returning the fixed source. Suggestions
+/// overlapping one applied before them are skipped.
+pub fn apply_suggestions(source: &str, diagnostics: &[Diagnostic]) -> String {
+    let mut suggestions: Vec<&Suggestion> = diagnostics
+        .iter()
+        .flat_map(|diagnostic| &diagnostic.suggestions)
+        .filter(|suggestion| {
+            suggestion.applicability == Applicability::MachineApplicable
+        })
+        .collect();
+    suggestions.sort_by_key(|suggestion| suggestion.span.start);
+
+    let mut out = String::new();
+    let mut pos = 0;
+    for suggestion in suggestions {
+        if suggestion.span.start < pos {
+            continue;
+        }
+        out.push_str(&source[pos..suggestion.span.start]);
+        out.push_str(&suggestion.replacement);
+        pos = suggestion.span.end;
+    }
+    out.push_str(&source[pos..]);
+    out
+}
+
+/// Represents a message about a span of the input, such as a syntax error,
+/// to be reported to the user.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// A stable code identifying the kind of problem, like `E0001`, shown
+    /// with the severity. See [CodeRegistry].
+    pub code: Option<String>,
+    pub message: String,
+    /// Byte span of the input the diagnostic refers to.
+    pub span: Span,
+    pub notes: Vec<String>,
+    /// Edits that would fix the problem.
+    pub suggestions: Vec<Suggestion>,
+}
+
+impl Diagnostic {
+    /// Create a diagnostic with severity `severity`.
+    pub fn new(
+        severity: Severity,
+        message: impl Into<String>,
+        span: Span,
+    ) -> Self {
+        Self {
+            severity,
+            code: None,
+            message: message.into(),
+            span,
+            notes: Vec::new(),
+            suggestions: Vec::new(),
+        }
+    }
+
+    /// Create a diagnostic with severity [Severity::Error].
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Error, message, span)
+    }
+
+    /// Create a diagnostic with severity [Severity::Warning].
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self::new(Severity::Warning, message, span)
+    }
+
+    /// Set the code identifying the kind of problem.
+    pub fn with_code(mut self, code: impl Into<String>) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// Add a note to be printed below the diagnostic.
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Add a suggested edit, printed below the diagnostic as help.
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
+
+    /// Render the diagnostic rustc-style, quoting the line of `source` where
+    /// the span starts and underlining the span (up to the end of the line).
+    ///
+    /// ```text
+    /// error[E0001]: expected `=`
+    ///  --> config.ini:3:5
+    ///   |
+    /// 3 | key value
+    ///   |     ^^^^^
+    ///   = note: entries are written `key = value`
+    ///   = help: insert `=`
+    /// ```
+    pub fn render(&self, source: &SourceFile) -> String {
+        let line = source.line_index(self.span.start);
+        let (line_no, col) = source.line_col(self.span.start);
+        // columns count tabs to the tab width, so the line is printed with
+        // them expanded for the underline to line up
+        let text = expand_tabs(source.line(line), source.tab_width());
+        let gutter = " ".repeat(line_no.to_string().len());
+
+        // underline at least one column, and at most to the end of the line
+        let end_col = match source.line_col(self.span.end) {
+            (end_line, end_col) if end_line == line_no => end_col,
+            _ => text.chars().count() + 1,
+        };
+        let width = end_col.saturating_sub(col).max(1);
+
+        let mut out = String::new();
+        writeln!(out, "{}: {}", self.heading(), self.message).unwrap();
+        writeln!(out, "{gutter}--> {}:{line_no}:{col}", source.name()).unwrap();
+        writeln!(out, "{gutter} |").unwrap();
+        writeln!(out, "{line_no} | {text}").unwrap();
+        writeln!(
+            out,
+            "{gutter} | {}{}",
+            " ".repeat(col - 1),
+            "^".repeat(width)
+        )
+        .unwrap();
+        for note in &self.notes {
+            writeln!(out, "{gutter} = note: {note}").unwrap();
+        }
+        for suggestion in &self.suggestions {
+            writeln!(out, "{gutter} = help: {}", suggestion.message).unwrap();
+        }
+        out
+    }
+
+    /// Get the severity followed by the code, if any, like `error[E0001]`.
+    fn heading(&self) -> String {
+        match &self.code {
+            Some(code) => format!("{}[{code}]", self.severity),
+            None => self.severity.to_string(),
+        }
+    }
+}
+
+/// Replace the tabs in `text` with spaces up to the next multiple of `width`
+/// columns, as [SourceFile::line_col] counts them.
+fn expand_tabs(text: &str, width: usize) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut col = 0;
+    for c in text.chars() {
+        match c {
+            '\t' => {
+                let next = (col / width + 1) * width;
+                out.extend(std::iter::repeat_n(' ', next - col));
+                col = next;
+            }
+            c => {
+                out.push(c);
+                col += 1;
+            }
+        }
+    }
+    out
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} at {}", self.heading(), self.message, self.span)
+    }
+}
+
+/// A kind of diagnostic a language reports, registered in a [CodeRegistry].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeInfo {
+    /// The stable code, like `E0001`.
+    pub code: String,
+    /// The category the code belongs to, like `syntax` or `unused`, so
+    /// related codes can be suppressed together.
+    pub category: String,
+    /// The severity diagnostics with the code are created with.
+    pub severity: Severity,
+    /// A one-line description, for documentation.
+    pub description: String,
+}
+
+/// The diagnostic codes of a language, populated by the language, and the
+/// codes and categories suppressed by the user. Displays as a list of the
+/// codes, for documentation.
+///
+/// ```
+/// use rlrl::diagnostic::{CodeRegistry, Severity};
+/// use rlrl::span::Span;
+///
+/// let mut registry = CodeRegistry::new();
+/// registry
+///     .register("E0001", "syntax", Severity::Error, "Expected a token")
+///     .unwrap();
+/// registry
+///     .register("W0001", "unused", Severity::Warning, "Unused variable")
+///     .unwrap();
+///
+/// let unused = registry.diagnostic("W0001", "`x` is unused", Span::new(4, 5));
+/// assert!(unused.to_string() == "warning[W0001]: `x` is unused at 4..5");
+/// registry.suppress("unused");
+/// assert!(registry.filter(vec![unused]).is_empty());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CodeRegistry {
+    codes: BTreeMap<String, CodeInfo>,
+    suppressed: HashSet<String>,
+}
+
+impl CodeRegistry {
+    /// Create a registry with no codes.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register the code `code` in the category `category`, for diagnostics
+    /// of severity `severity`. Fails if the code is already registered.
+    pub fn register(
+        &mut self,
+        code: impl Into<String>,
+        category: impl Into<String>,
+        severity: Severity,
+        description: impl Into<String>,
+    ) -> Result<()> {
+        let code = code.into();
+        if self.codes.contains_key(&code) {
+            return Err(Error::build(format!(
+                "Diagnostic code `{code}` is already registered!"
+            )));
+        }
+        let info = CodeInfo {
+            code: code.clone(),
+            category: category.into(),
+            severity,
+            description: description.into(),
+        };
+        self.codes.insert(code, info);
+        Ok(())
+    }
+
+    /// Get the registered code `code`, if any.
+    pub fn get(&self, code: &str) -> Option<&CodeInfo> {
+        self.codes.get(code)
+    }
+
+    /// Iterate over the registered codes, in order.
+    pub fn codes(&self) -> impl Iterator<Item = &CodeInfo> {
+        self.codes.values()
+    }
+
+    /// Create a diagnostic with the code `code` and its registered severity.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `code` isn't registered.
+    pub fn diagnostic(
+        &self,
+        code: &str,
+        message: impl Into<String>,
+        span: Span,
+    ) -> Diagnostic {
+        let info = self.get(code).unwrap_or_else(|| {
+            panic!("Unregistered diagnostic code `{code}`!")
+        });
+        Diagnostic::new(info.severity, message, span).with_code(code)
+    }
+
+    /// Suppress the diagnostics with the code `name`, or with any code in
+    /// the category `name`.
+    pub fn suppress(&mut self, name: impl Into<String>) {
+        self.suppressed.insert(name.into());
+    }
+
+    /// Return true if `diagnostic` has a code that is suppressed or in a
+    /// suppressed category. Diagnostics without codes are never suppressed.
+    pub fn is_suppressed(&self, diagnostic: &Diagnostic) -> bool {
+        let Some(code) = &diagnostic.code else {
+            return false;
+        };
+        self.suppressed.contains(code)
+            || self
+                .get(code)
+                .is_some_and(|info| self.suppressed.contains(&info.category))
+    }
+
+    /// Remove the suppressed diagnostics from `diagnostics`.
+    pub fn filter(&self, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+        diagnostics
+            .into_iter()
+            .filter(|diagnostic| !self.is_suppressed(diagnostic))
+            .collect()
+    }
+}
+
+impl Display for CodeRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for info in self.codes() {
+            writeln!(
+                f,
+                "{} ({} {}): {}",
+                info.code, info.category, info.severity, info.description
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{
+        Applicability, CodeRegistry, Diagnostic, Severity, Suggestion,
+        apply_suggestions,
+    };
+    use crate::source::SourceFile;
+    use crate::span::Span;
+
+    #[test]
+    fn test_render() {
+        let source = SourceFile::new("config.ini", "[main]\n\nkey value\n");
+        let diagnostic = Diagnostic::error("expected `=`", Span::new(12, 17))
+            .with_note("entries are written `key = value`");
+
+        assert!(
+            diagnostic.render(&source)
+                == "error: expected `=`\n \
+                    --> config.ini:3:5\n  \
+                    |\n\
+                    3 | key value\n  \
+                    |     ^^^^^\n  \
+                    = note: entries are written `key = value`\n"
+        );
+
+        // spans running past the line are cut off at the line end
+        let diagnostic = Diagnostic::warning("unclosed", Span::new(0, 11));
+        assert!(
+            diagnostic
+                .render(&source)
+                .contains("1 | [main]\n  | ^^^^^^\n")
+        );
+
+        // tabs are expanded so the underline lines up with the text
+        let source = SourceFile::new("a.txt", "\tx\ty").with_tab_width(4);
+        let diagnostic = Diagnostic::error("unknown", Span::new(3, 4));
+        assert!(
+            diagnostic
+                .render(&source)
+                .contains("1 |     x   y\n  |         ^\n")
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let keywords = ["let", "fn", "return"];
+        let suggestion =
+            Suggestion::did_you_mean("retrun", Span::new(4, 10), keywords);
+        assert!(
+            suggestion
+                == Some(Suggestion::new(
+                    "did you mean `return`?",
+                    Span::new(4, 10),
+                    "return",
+                    Applicability::MaybeIncorrect
+                ))
+        );
+        assert!(
+            Suggestion::did_you_mean("foo", Span::new(0, 3), keywords)
+                .is_none()
+        );
+
+        let source = "a = 1\nb 2\nc = 3 4";
+        let diagnostics = [
+            Diagnostic::error("expected `=`", Span::new(8, 9)).with_suggestion(
+                Suggestion::new(
+                    "insert `=`",
+                    Span::new(8, 8),
+                    "= ",
+                    Applicability::MachineApplicable,
+                ),
+            ),
+            Diagnostic::error("unexpected `4`", Span::new(17, 18))
+                .with_suggestion(Suggestion::new(
+                    "remove `4`",
+                    Span::new(16, 18),
+                    "",
+                    Applicability::MaybeIncorrect,
+                )),
+        ];
+        assert!(
+            apply_suggestions(source, &diagnostics) == "a = 1\nb = 2\nc = 3 4"
+        );
+        assert!(
+            diagnostics[0]
+                .render(&SourceFile::new("a", source))
+                .ends_with("  = help: insert `=`\n")
+        );
+    }
+
+    #[test]
+    fn test_codes() -> anyhow::Result<()> {
+        let mut registry = CodeRegistry::new();
+        registry.register("E0001", "syntax", Severity::Error, "Expected =")?;
+        registry.register("W0001", "style", Severity::Warning, "Bad name")?;
+        registry.register("W0002", "style", Severity::Warning, "Long line")?;
+        assert!(
+            registry
+                .register("E0001", "syntax", Severity::Error, "Again")
+                .is_err()
+        );
+        assert!(registry.get("W0002").unwrap().category == "style");
+
+        let source = SourceFile::new("config.ini", "key value\n");
+        let error =
+            registry.diagnostic("E0001", "expected `=`", Span::new(4, 9));
+        assert!(
+            error
+                .render(&source)
+                .starts_with("error[E0001]: expected `=`\n")
+        );
+
+        let diagnostics = vec![
+            error,
+            registry.diagnostic("W0001", "bad name", Span::new(0, 3)),
+            registry.diagnostic("W0002", "long line", Span::new(0, 9)),
+            Diagnostic::warning("no code", Span::new(0, 0)),
+        ];
+        registry.suppress("W0002");
+        assert!(registry.filter(diagnostics.clone()).len() == 3);
+        registry.suppress("style");
+        let kept = registry.filter(diagnostics);
+        assert!(kept.len() == 2 && kept[1].code.is_none());
+
+        assert!(
+            registry.to_string()
+                == "E0001 (syntax error): Expected =\n\
+                    W0001 (style warning): Bad name\n\
+                    W0002 (style warning): Long line\n"
+        );
+
+        Ok(())
+    }
+}
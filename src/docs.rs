@@ -0,0 +1,188 @@
+//! Documentation export for a [Grammar]: a Markdown summary of the
+//! productions and SVG railroad diagrams for each non-terminal.
+use crate::grammar::{Grammar, Symbol};
+use std::fmt::Write;
+
+const CHAR_WIDTH: usize = 8;
+const BOX_HEIGHT: usize = 24;
+const BOX_PADDING: usize = 10;
+const GAP: usize = 20;
+const ROW_HEIGHT: usize = 40;
+const MARGIN: usize = 20;
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn box_width(symbol: &Symbol) -> usize {
+    symbol.to_string().chars().count() * CHAR_WIDTH + 2 * BOX_PADDING
+}
+
+impl Grammar {
+    /// Render a Markdown summary of the grammar: one section per non-terminal
+    /// listing its alternatives, followed by a table of terminal patterns.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# Grammar\n").unwrap();
+        writeln!(out, "Start symbol: `{}`", self.start()).unwrap();
+
+        let mut nonterminals: Vec<&str> = vec![self.start()];
+        for production in self.productions() {
+            if !nonterminals.contains(&production.lhs.as_str()) {
+                nonterminals.push(&production.lhs);
+            }
+        }
+        for nonterminal in nonterminals {
+            writeln!(out, "\n## `{nonterminal}`\n\n```text").unwrap();
+            for (i, (_, production)) in
+                self.productions_for(nonterminal).enumerate()
+            {
+                let separator = match i {
+                    0 => "=",
+                    _ => "|",
+                };
+                let pad = match i {
+                    0 => nonterminal.to_owned(),
+                    _ => " ".repeat(nonterminal.chars().count()),
+                };
+                let rhs = match production.rhs.is_empty() {
+                    true => "ε".to_owned(),
+                    false => production
+                        .rhs
+                        .iter()
+                        .map(Symbol::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                };
+                writeln!(out, "{pad} {separator} {rhs}").unwrap();
+            }
+            writeln!(out, "```").unwrap();
+        }
+
+        if !self.terminal_patterns().is_empty() {
+            writeln!(out, "\n## Terminals\n").unwrap();
+            writeln!(out, "| Terminal | Pattern |\n| --- | --- |").unwrap();
+            for (name, pattern) in self.terminal_patterns() {
+                let pattern = pattern.replace('|', r"\|");
+                writeln!(out, "| `{name}` | `{pattern}` |").unwrap();
+            }
+        }
+        out
+    }
+
+    /// Render an SVG railroad diagram for the non-terminal `nonterminal`,
+    /// with one track per alternative. Returns `None` if the non-terminal has
+    /// no productions.
+    pub fn railroad_svg(&self, nonterminal: &str) -> Option<String> {
+        let rows: Vec<&[Symbol]> = self
+            .productions_for(nonterminal)
+            .map(|(_, production)| production.rhs.as_slice())
+            .collect();
+        if rows.is_empty() {
+            return None;
+        }
+
+        let row_width = |row: &[Symbol]| -> usize {
+            row.iter()
+                .map(|symbol| box_width(symbol) + GAP)
+                .sum::<usize>()
+                + GAP
+        };
+        let inner_width = rows.iter().map(|row| row_width(row)).max()?;
+        let width = inner_width + 2 * MARGIN;
+        let height = rows.len() * ROW_HEIGHT + 2 * MARGIN;
+        let left = MARGIN;
+        let right = MARGIN + inner_width;
+
+        let mut svg = String::new();
+        writeln!(
+            svg,
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" \
+             height=\"{height}\" viewBox=\"0 0 {width} {height}\">"
+        )
+        .unwrap();
+        writeln!(
+            svg,
+            "<title>{}</title>\n<g fill=\"none\" stroke=\"black\" \
+             font-family=\"monospace\" font-size=\"13\">",
+            escape_xml(nonterminal)
+        )
+        .unwrap();
+
+        let top = MARGIN + ROW_HEIGHT / 2;
+        let bottom = top + (rows.len() - 1) * ROW_HEIGHT;
+        // entry and exit rails joining the alternatives
+        writeln!(
+            svg,
+            "<path d=\"M0 {top} H{left} M{left} {top} V{bottom} M{right} \
+             {top} V{bottom} M{right} {top} H{width}\"/>"
+        )
+        .unwrap();
+
+        for (i, row) in rows.iter().enumerate() {
+            let y = top + i * ROW_HEIGHT;
+            let mut x = left;
+            for symbol in row.iter() {
+                let w = box_width(symbol);
+                writeln!(svg, "<path d=\"M{x} {y} H{}\"/>", x + GAP).unwrap();
+                x += GAP;
+                let rx = match symbol.is_terminal() {
+                    true => BOX_HEIGHT / 2,
+                    false => 0,
+                };
+                writeln!(
+                    svg,
+                    "<rect x=\"{x}\" y=\"{}\" width=\"{w}\" \
+                     height=\"{BOX_HEIGHT}\" rx=\"{rx}\"/>",
+                    y - BOX_HEIGHT / 2
+                )
+                .unwrap();
+                writeln!(
+                    svg,
+                    "<text x=\"{}\" y=\"{}\" fill=\"black\" stroke=\"none\" \
+                     text-anchor=\"middle\">{}</text>",
+                    x + w / 2,
+                    y + 4,
+                    escape_xml(&symbol.to_string())
+                )
+                .unwrap();
+                x += w;
+            }
+            writeln!(svg, "<path d=\"M{x} {y} H{right}\"/>").unwrap();
+        }
+        writeln!(svg, "</g>\n</svg>").unwrap();
+        Some(svg)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammar::Grammar;
+
+    #[test]
+    fn test_docs() -> anyhow::Result<()> {
+        let grammar = Grammar::from_ebnf(
+            r#"
+            expr   = expr "+" term | term ;
+            term   = NUMBER | "(" expr ")" ;
+            NUMBER = /[0-9]+/ ;
+            "#,
+        )?;
+
+        let markdown = grammar.to_markdown();
+        assert!(markdown.contains("## `expr`"));
+        assert!(markdown.contains("expr = expr \"+\" term\n     | term\n"));
+        assert!(markdown.contains("| `NUMBER` | `[0-9]+` |"));
+
+        let svg = grammar.railroad_svg("term").unwrap();
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.matches("<rect").count() == 4);
+        assert!(svg.contains("&quot;(&quot;"));
+        assert!(grammar.railroad_svg("missing").is_none());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,319 @@
+//! Lexers defined at runtime, from a [LexerSpec] loaded from a configuration
+//! file, producing [DynamicToken]s instead of a compile-time token enum.
+//!
+//! Together with a [Grammar](crate::grammar::Grammar) loaded from EBNF and
+//! an [EarleyParser](crate::earley::EarleyParser), this makes fully
+//! data-driven pipelines possible: parse the tokens with [DynamicToken::kind]
+//! as the classify function, and turn the resulting parse tree into a
+//! [DynamicAst].
+//!
+//! With the `serde` feature, [LexerSpec] implements `Deserialize`, so it can
+//! be read from TOML, JSON, or any other format with a `serde` crate:
+//!
+//! ```toml
+//! [[rules]]
+//! kind = "whitespace"
+//! pattern = '\s+'
+//! ignore = true
+//!
+//! [[rules]]
+//! kind = "number"
+//! pattern = '[0-9]+'
+//! ```
+
+use crate::earley::ParseTree;
+use crate::lex::{LexResult, Lexer, RuleOptions};
+use crate::span::{HasSpan, Span};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// A token lexed by a runtime-defined lexer: the kind of the rule that
+/// matched it, and its text.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct DynamicToken {
+    pub kind: Arc<str>,
+    pub text: String,
+    /// Byte span of the input the token was lexed from.
+    pub span: Span,
+}
+
+impl DynamicToken {
+    /// Borrow the token's kind. Usable as the classify function of a parser
+    /// whose terminals are the kinds.
+    pub fn kind(&self) -> &str {
+        &self.kind
+    }
+}
+
+impl HasSpan for DynamicToken {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+/// Represents a syntax tree built from [DynamicToken]s, for grammars loaded
+/// at runtime.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DynamicAst {
+    /// A token matched by a terminal.
+    Token(DynamicToken),
+    /// A non-terminal, with the trees of the symbols it was derived from.
+    Node {
+        rule: String,
+        children: Vec<DynamicAst>,
+        /// Byte span of the input the node covers.
+        span: Span,
+    },
+}
+
+impl DynamicAst {
+    /// Build a tree from the parse tree `tree`, whose leaves index into
+    /// `tokens`.
+    pub fn from_tree(tree: &ParseTree, tokens: &[DynamicToken]) -> Self {
+        Self::build(tree, tokens, 0)
+    }
+
+    /// Build a tree from `tree`, where `pos` is the end of the input before
+    /// it, which an empty node's span starts at.
+    fn build(tree: &ParseTree, tokens: &[DynamicToken], pos: usize) -> Self {
+        match tree {
+            ParseTree::Leaf { idx, .. } => Self::Token(tokens[*idx].clone()),
+            ParseTree::Node { lhs, children, .. } => {
+                let mut end = pos;
+                let children: Vec<Self> = children
+                    .iter()
+                    .map(|child| {
+                        let child = Self::build(child, tokens, end);
+                        end = child.span().end;
+                        child
+                    })
+                    .collect();
+                let start =
+                    children.first().map_or(pos, |child| child.span().start);
+                Self::Node {
+                    rule: lhs.clone(),
+                    children,
+                    span: Span::new(start, end),
+                }
+            }
+        }
+    }
+
+    /// Borrow the kind of the token, or the rule name of the node.
+    pub fn kind(&self) -> &str {
+        match self {
+            Self::Token(token) => &token.kind,
+            Self::Node { rule, .. } => rule,
+        }
+    }
+
+    /// Get the byte span of the input the tree covers.
+    pub fn span(&self) -> Span {
+        match self {
+            Self::Token(token) => token.span,
+            Self::Node { span, .. } => *span,
+        }
+    }
+
+    /// Borrow the node's children, or nothing for a token.
+    pub fn children(&self) -> &[DynamicAst] {
+        match self {
+            Self::Token(_) => &[],
+            Self::Node { children, .. } => children,
+        }
+    }
+
+    /// Iterate over the tokens at the leaves of the tree, in order.
+    pub fn tokens(&self) -> Box<dyn Iterator<Item = &DynamicToken> + '_> {
+        match self {
+            Self::Token(token) => Box::new(std::iter::once(token)),
+            Self::Node { children, .. } => {
+                Box::new(children.iter().flat_map(|child| child.tokens()))
+            }
+        }
+    }
+}
+
+/// Prints the tree as an S-expression, like `(sum (num "1") "+" (num "2"))`,
+/// with tokens as their quoted text.
+impl Display for DynamicAst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Token(token) => write!(f, "{:?}", token.text),
+            Self::Node { rule, children, .. } => {
+                write!(f, "({rule}")?;
+                for child in children {
+                    write!(f, " {child}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+/// Describes a lexer whose rules are given as data. See [Lexer::from_spec].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct LexerSpec {
+    pub rules: Vec<RuleSpec>,
+}
+
+/// Describes one rule of a [LexerSpec].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Deserialize, serde::Serialize))]
+pub struct RuleSpec {
+    /// The kind given to tokens the rule matches, which also names the rule.
+    pub kind: String,
+    /// The rule's regex.
+    pub pattern: String,
+    /// The rule's priority. Rules are ordered by priority, lowest first, and
+    /// then in the order they're listed, so the default of 0 keeps the
+    /// listed order.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub priority: i64,
+    /// Whether input the rule matches is skipped instead of producing a
+    /// token.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub ignore: bool,
+    /// Whether the rule's regex matches letters regardless of case.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub case_insensitive: bool,
+    /// The group the rule is in, if any, so it can be left out when lexing
+    /// with [Lexer::lex_matches_without_groups].
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub group: Option<String>,
+}
+
+impl Lexer<DynamicToken> {
+    /// Create a lexer from the rules in `spec`, returning an error if a
+    /// pattern is invalid.
+    pub fn from_spec(spec: &LexerSpec) -> crate::Result<Self> {
+        let mut rules: Vec<&RuleSpec> = spec.rules.iter().collect();
+        rules.sort_by_key(|rule| rule.priority);
+
+        let mut lexer = Lexer::new();
+        for rule in rules {
+            let mut options = RuleOptions::default()
+                .with_name(&rule.kind)
+                .with_case_insensitive(rule.case_insensitive);
+            if let Some(group) = &rule.group {
+                options = options.with_group(group);
+            }
+            let kind: Arc<str> = rule.kind.as_str().into();
+            match rule.ignore {
+                true => {
+                    lexer.add_rule_with_options(&rule.pattern, options, |_| {
+                        LexResult::Ignore
+                    })
+                }
+                false => lexer.add_rule_with_options(
+                    &rule.pattern,
+                    options,
+                    move |re_match| {
+                        LexResult::Token(DynamicToken {
+                            kind: kind.clone(),
+                            text: re_match.as_str().to_owned(),
+                            span: re_match.range().into(),
+                        })
+                    },
+                ),
+            }?;
+        }
+        Ok(lexer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::{DynamicAst, DynamicToken, LexerSpec, RuleSpec};
+    use crate::earley::EarleyParser;
+    use crate::grammar::Grammar;
+    use crate::lex::{LexError, Lexer};
+    use crate::parse::TokenQueue;
+    use crate::span::Span;
+
+    fn rule(kind: &str, pattern: &str) -> RuleSpec {
+        RuleSpec {
+            kind: kind.to_owned(),
+            pattern: pattern.to_owned(),
+            ..RuleSpec::default()
+        }
+    }
+
+    #[test]
+    fn test_from_spec() -> anyhow::Result<()> {
+        let spec = LexerSpec {
+            rules: vec![
+                rule("ident", "[a-z]+"),
+                RuleSpec {
+                    priority: -1,
+                    case_insensitive: true,
+                    ..rule("keyword", "select|from")
+                },
+                RuleSpec {
+                    ignore: true,
+                    ..rule("whitespace", r"\s+")
+                },
+            ],
+        };
+        let lexer = Lexer::from_spec(&spec)?;
+
+        let tokens = lexer.lex("SELECT a from")?;
+        let kinds: Vec<&str> =
+            tokens.iter().map(|token| token.kind.as_ref()).collect();
+        assert!(kinds == vec!["keyword", "ident", "keyword"]);
+        assert!(tokens[1].text == "a" && tokens[1].span == Span::new(7, 8));
+        assert!(matches!(
+            lexer.lex("a 1"),
+            Err(LexError::Unmatched { pos: 2 })
+        ));
+
+        // rules in a group can be left out
+        let mut spec = spec;
+        spec.rules[1].group = Some("sql".to_owned());
+        let lexer = Lexer::from_spec(&spec)?;
+        assert!(&*lexer.lex("select a")?[0].kind == "keyword");
+        let tokens = lexer.lex_matches_without_groups("select a", &["sql"])?;
+        assert!(&*tokens[0].token().kind == "ident");
+
+        let spec = LexerSpec {
+            rules: vec![rule("bad", "(")],
+        };
+        assert!(Lexer::from_spec(&spec).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dynamic_ast() -> anyhow::Result<()> {
+        let spec = LexerSpec {
+            rules: vec![
+                RuleSpec {
+                    ignore: true,
+                    ..rule("ws", r"\s+")
+                },
+                rule("num", "[0-9]+"),
+                rule("+", r"\+"),
+            ],
+        };
+        let grammar =
+            Grammar::from_ebnf(r#"sum = num rest ; rest = | "+" num rest ;"#)?;
+        let lexer = Lexer::from_spec(&spec)?;
+        let parser = EarleyParser::new(&grammar, DynamicToken::kind)?;
+
+        let tokens = lexer.lex("1 + 22")?;
+        let tree = parser
+            .parse(&TokenQueue::from(tokens.clone()))?
+            .trees(1)
+            .remove(0);
+        let ast = DynamicAst::from_tree(&tree, &tokens);
+        assert!(ast.to_string() == r#"(sum "1" (rest "+" "22" (rest)))"#);
+        assert!(ast.kind() == "sum" && ast.span() == Span::new(0, 6));
+        let rest = &ast.children()[1];
+        assert!(rest.span() == Span::new(2, 6));
+        assert!(rest.children()[2].span() == Span::new(6, 6));
+        assert!(ast.tokens().count() == 3);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+use crate::grammar::{Grammar, Symbol};
+use crate::lr::ClassifyFn;
+use crate::parse::{ParseError, Result, TokenQueue};
+use crate::span::Span;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// An Earley item: (production, dot position, origin).
+type Item = (usize, usize, usize);
+
+/// Represents a parse tree extracted from a [Forest].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseTree {
+    /// A terminal matched against the token at `idx`.
+    Leaf { terminal: String, idx: usize },
+    /// A non-terminal derived using the production with index `production`.
+    Node {
+        lhs: String,
+        production: usize,
+        children: Vec<ParseTree>,
+    },
+}
+
+/// One way of deriving a [ForestNode]: a production and the nodes matching
+/// each symbol on its right hand side.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Family {
+    pub production: usize,
+    pub children: Vec<usize>,
+}
+
+/// Represents a symbol spanning a range of tokens in a [Forest].
+/// Terminal nodes have no families; ambiguous non-terminal nodes have more
+/// than one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ForestNode {
+    pub symbol: Symbol,
+    pub span: Span,
+    pub families: Vec<Family>,
+}
+
+/// Represents a shared packed parse forest: every parse of the input, with
+/// nodes for identical (symbol, span) pairs shared between parses.
+#[derive(Debug, Clone)]
+pub struct Forest {
+    nodes: Vec<ForestNode>,
+}
+
+impl Forest {
+    /// Borrow the root node, which derives the start symbol over the whole
+    /// input.
+    pub fn root(&self) -> &ForestNode {
+        &self.nodes[0]
+    }
+
+    /// Borrow the node with id `id`.
+    pub fn node(&self, id: usize) -> &ForestNode {
+        &self.nodes[id]
+    }
+
+    /// Borrow every node in the forest.
+    pub fn nodes(&self) -> &[ForestNode] {
+        &self.nodes
+    }
+
+    /// Return true if some node in the forest has more than one derivation.
+    pub fn is_ambiguous(&self) -> bool {
+        self.nodes.iter().any(|node| node.families.len() > 1)
+    }
+
+    /// Extract up to `limit` distinct parse trees from the forest.
+    /// Derivations that loop through a cyclic production are skipped.
+    pub fn trees(&self, limit: usize) -> Vec<ParseTree> {
+        self.trees_of(0, limit, &mut Vec::new())
+    }
+
+    fn trees_of(
+        &self,
+        id: usize,
+        limit: usize,
+        path: &mut Vec<usize>,
+    ) -> Vec<ParseTree> {
+        let node = &self.nodes[id];
+        if let Symbol::Terminal(terminal) = &node.symbol {
+            return vec![ParseTree::Leaf {
+                terminal: terminal.clone(),
+                idx: node.span.start,
+            }];
+        }
+        if path.contains(&id) {
+            return Vec::new();
+        }
+        path.push(id);
+        let mut trees = Vec::new();
+        for family in &node.families {
+            // cartesian product of the children's trees
+            let mut partials: Vec<Vec<ParseTree>> = vec![Vec::new()];
+            for &child in &family.children {
+                let child_trees = self.trees_of(child, limit, path);
+                partials = partials
+                    .iter()
+                    .flat_map(|partial| {
+                        child_trees.iter().map(move |tree| {
+                            let mut partial = partial.clone();
+                            partial.push(tree.clone());
+                            partial
+                        })
+                    })
+                    .take(limit)
+                    .collect();
+            }
+            for children in partials {
+                if trees.len() == limit {
+                    break;
+                }
+                trees.push(ParseTree::Node {
+                    lhs: node.symbol.name().to_owned(),
+                    production: family.production,
+                    children,
+                });
+            }
+        }
+        path.pop();
+        trees
+    }
+}
+
+/// Represents an Earley parser, which accepts any context-free [Grammar]
+/// (including ambiguous and left-recursive ones) over a queue of tokens with
+/// type `L`.
+pub struct EarleyParser<L> {
+    grammar: Grammar,
+    classify: ClassifyFn<L>,
+}
+
+impl<L> EarleyParser<L> {
+    /// Create a parser for `grammar`, returning an error if the grammar is
+    /// invalid. `classify` maps each token to its terminal.
+    pub fn new(
+        grammar: &Grammar,
+        classify: ClassifyFn<L>,
+    ) -> crate::Result<Self> {
+        grammar.validate()?;
+        Ok(Self {
+            grammar: grammar.clone(),
+            classify,
+        })
+    }
+
+    /// Parse all the remaining tokens in the queue, returning a [Forest] of
+    /// every parse, or an error if there are none.
+    pub fn parse(&self, tq: &TokenQueue<L>) -> Result<Forest> {
+        let mut tq = tq.clone();
+        let base = tq.get_idx();
+        let mut input: Vec<String> = Vec::new();
+        while !tq.is_consumed() {
+            input.push((self.classify)(tq.consume()?).to_owned());
+        }
+
+        let productions = self.grammar.productions();
+        let nullable = self.grammar.nullable();
+        let start = self.grammar.start();
+
+        let mut sets: Vec<BTreeSet<Item>> =
+            vec![BTreeSet::new(); input.len() + 1];
+        for (idx, _) in self.grammar.productions_for(start) {
+            sets[0].insert((idx, 0, 0));
+        }
+        for i in 0..=input.len() {
+            let mut work: Vec<Item> = sets[i].iter().copied().collect();
+            while let Some((prod, dot, origin)) = work.pop() {
+                let mut added = Vec::new();
+                match productions[prod].rhs.get(dot) {
+                    // predict
+                    Some(Symbol::NonTerminal(name)) => {
+                        for (idx, _) in self.grammar.productions_for(name) {
+                            added.push((idx, 0, i));
+                        }
+                        // Aycock-Horspool: skip over nullable non-terminals
+                        if nullable.contains(name.as_str()) {
+                            added.push((prod, dot + 1, origin));
+                        }
+                    }
+                    // scan
+                    Some(Symbol::Terminal(name)) => {
+                        if input.get(i) == Some(name) {
+                            sets[i + 1].insert((prod, dot + 1, origin));
+                        }
+                    }
+                    // complete
+                    None => {
+                        let lhs = &productions[prod].lhs;
+                        for &(q, d, o) in &sets[origin] {
+                            if let Some(Symbol::NonTerminal(name)) =
+                                productions[q].rhs.get(d)
+                                && name == lhs
+                            {
+                                added.push((q, d + 1, o));
+                            }
+                        }
+                    }
+                }
+                for item in added {
+                    if sets[i].insert(item) {
+                        work.push(item);
+                    }
+                }
+            }
+            if i < input.len() && sets[i + 1].is_empty() {
+                return Err(ParseError::at(
+                    base + i,
+                    format!("Unexpected `{}`!", input[i]),
+                ));
+            }
+        }
+
+        // (production, start, end) for every completed item
+        let completed: BTreeSet<(usize, usize, usize)> = sets
+            .iter()
+            .enumerate()
+            .flat_map(|(end, set)| {
+                set.iter()
+                    .filter(|(prod, dot, _)| {
+                        *dot == productions[*prod].rhs.len()
+                    })
+                    .map(move |&(prod, _, origin)| (prod, origin, end))
+            })
+            .collect();
+        if !completed.iter().any(|&(prod, s, e)| {
+            productions[prod].lhs == start && s == 0 && e == input.len()
+        }) {
+            return Err(ParseError::at(
+                base + input.len(),
+                "Unexpected end of input!",
+            ));
+        }
+
+        let builder = ForestBuilder {
+            grammar: &self.grammar,
+            input: &input,
+            completed: &completed,
+            spans: completed
+                .iter()
+                .map(|&(prod, s, e)| (productions[prod].lhs.as_str(), s, e))
+                .collect(),
+            ids: BTreeMap::new(),
+            nodes: Vec::new(),
+        };
+        Ok(builder.build(base))
+    }
+}
+
+/// Builds a [Forest] by decomposing completed Earley items into children.
+struct ForestBuilder<'a> {
+    grammar: &'a Grammar,
+    input: &'a [String],
+    completed: &'a BTreeSet<(usize, usize, usize)>,
+    /// (non-terminal, start, end) for every completed item
+    spans: BTreeSet<(&'a str, usize, usize)>,
+    ids: BTreeMap<(Symbol, usize, usize), usize>,
+    nodes: Vec<ForestNode>,
+}
+
+impl ForestBuilder<'_> {
+    fn build(mut self, base: usize) -> Forest {
+        let root = Symbol::nt(self.grammar.start());
+        self.node_id(root, 0, self.input.len());
+        let mut i = 0;
+        while i < self.nodes.len() {
+            if let Symbol::NonTerminal(_) = self.nodes[i].symbol {
+                self.nodes[i].families = self.families(i);
+            }
+            i += 1;
+        }
+        for node in &mut self.nodes {
+            node.span = Span::new(node.span.start + base, node.span.end + base);
+        }
+        Forest { nodes: self.nodes }
+    }
+
+    fn node_id(&mut self, symbol: Symbol, start: usize, end: usize) -> usize {
+        let key = (symbol, start, end);
+        if let Some(&id) = self.ids.get(&key) {
+            return id;
+        }
+        self.nodes.push(ForestNode {
+            symbol: key.0.clone(),
+            span: Span::new(start, end),
+            families: Vec::new(),
+        });
+        self.ids.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn families(&mut self, id: usize) -> Vec<Family> {
+        let (name, start, end) = {
+            let node = &self.nodes[id];
+            (
+                node.symbol.name().to_owned(),
+                node.span.start,
+                node.span.end,
+            )
+        };
+        let mut families = Vec::new();
+        let matching: Vec<usize> = self
+            .completed
+            .iter()
+            .filter(|&&(prod, s, e)| {
+                s == start
+                    && e == end
+                    && self.grammar.productions()[prod].lhs == name
+            })
+            .map(|&(prod, _, _)| prod)
+            .collect();
+        for prod in matching {
+            let rhs = &self.grammar.productions()[prod].rhs;
+            let mut splits = Vec::new();
+            self.decompose(rhs, start, end, &mut Vec::new(), &mut splits);
+            for split in splits {
+                let children = rhs
+                    .iter()
+                    .zip(split.windows(2))
+                    .map(|(symbol, w)| self.node_id(symbol.clone(), w[0], w[1]))
+                    .collect();
+                families.push(Family {
+                    production: prod,
+                    children,
+                });
+            }
+        }
+        families
+    }
+
+    /// Find every sequence of boundaries splitting `start..end` so that each
+    /// symbol in `rhs` spans the tokens between consecutive boundaries.
+    fn decompose(
+        &self,
+        rhs: &[Symbol],
+        start: usize,
+        end: usize,
+        prefix: &mut Vec<usize>,
+        out: &mut Vec<Vec<usize>>,
+    ) {
+        prefix.push(start);
+        match rhs.first() {
+            None if start == end => out.push(prefix.clone()),
+            None => {}
+            Some(Symbol::Terminal(name))
+                if start < end && &self.input[start] == name =>
+            {
+                self.decompose(&rhs[1..], start + 1, end, prefix, out);
+            }
+            Some(Symbol::Terminal(_)) => {}
+            Some(Symbol::NonTerminal(name)) => {
+                for mid in start..=end {
+                    if self.spans.contains(&(name.as_str(), start, mid)) {
+                        self.decompose(&rhs[1..], mid, end, prefix, out);
+                    }
+                }
+            }
+        }
+        prefix.pop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::earley::{EarleyParser, ParseTree};
+    use crate::grammar::{Grammar, Symbol};
+    use crate::parse::TokenQueue;
+    use crate::span::Span;
+
+    fn classify(token: &char) -> &str {
+        match token {
+            '+' => "+",
+            _ => "num",
+        }
+    }
+
+    #[test]
+    fn test_ambiguous_parse() -> anyhow::Result<()> {
+        let mut grammar = Grammar::new("expr");
+        grammar.add_production(
+            "expr",
+            vec![Symbol::nt("expr"), Symbol::t("+"), Symbol::nt("expr")],
+        );
+        grammar.add_production("expr", vec![Symbol::t("num")]);
+        let parser = EarleyParser::new(&grammar, classify)?;
+
+        let forest = parser.parse(&TokenQueue::from(vec!['1', '+', '2']))?;
+        assert!(!forest.is_ambiguous());
+        assert!(forest.trees(10).len() == 1);
+
+        // 1 + 2 + 3 has two parses: (1 + 2) + 3 and 1 + (2 + 3)
+        let forest =
+            parser.parse(&TokenQueue::from(vec!['1', '+', '2', '+', '3']))?;
+        assert!(forest.is_ambiguous());
+        assert!(forest.root().span == Span::new(0, 5));
+        assert!(forest.trees(10).len() == 2);
+        assert!(forest.trees(1).len() == 1);
+
+        assert!(parser.parse(&TokenQueue::from(vec!['1', '+'])).is_err());
+        assert!(parser.parse(&TokenQueue::from(vec!['+'])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nullable_parse() -> anyhow::Result<()> {
+        let mut grammar = Grammar::new("list");
+        grammar.add_production("list", vec![]);
+        grammar
+            .add_production("list", vec![Symbol::nt("list"), Symbol::t("num")]);
+        let parser = EarleyParser::new(&grammar, classify)?;
+
+        let trees = parser.parse(&TokenQueue::from(vec!['1', '2']))?.trees(10);
+        assert!(trees.len() == 1);
+        let ParseTree::Node { children, .. } = &trees[0] else {
+            panic!("Expected a node at the root of the parse tree!");
+        };
+        assert!(
+            children[1]
+                == ParseTree::Leaf {
+                    terminal: "num".to_owned(),
+                    idx: 1
+                }
+        );
+
+        assert!(parser.parse(&TokenQueue::from(vec![]))?.trees(10).len() == 1);
+
+        Ok(())
+    }
+}
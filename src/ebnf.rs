@@ -0,0 +1,406 @@
+//! Import of grammars written in EBNF, parsed with this crate's own lexer and
+//! token queue.
+//!
+//! ```text
+//! (* comments are ignored *)
+//! list   = item { "," item } [ "," ] ;
+//! item   = NUMBER | "(" list ")" ;
+//! NUMBER = /[0-9]+/ ;
+//! ```
+//!
+//! The first rule defines the start symbol. Identifiers defined by a rule are
+//! non-terminals, identifiers defined by a `/regex/` are terminals with a
+//! pattern, and any other identifier or quoted string is a terminal. Rules may
+//! be written with `=` or `::=`, and support alternation (`|`), grouping
+//! (`( )`), options (`[ ]` or `?`), and repetition (`{ }`, `*`, or `+`), which
+//! are desugared into fresh non-terminals.
+use crate::error::Error;
+use crate::grammar::{Grammar, Symbol};
+use crate::lex::{LexError, LexResult, Lexer};
+use crate::parse::{ParseError, ParseResult, TokenQueue};
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Regex(String),
+    Define,
+    Pipe,
+    Semi,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+    LBrace,
+    RBrace,
+    Star,
+    Plus,
+    Question,
+}
+
+impl Token {
+    fn starts_term(&self) -> bool {
+        matches!(
+            self,
+            Self::Ident(_)
+                | Self::Str(_)
+                | Self::LParen
+                | Self::LBracket
+                | Self::LBrace
+        )
+    }
+}
+
+fn setup_lexer() -> Lexer<Token> {
+    let mut lexer = Lexer::new();
+
+    lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+    lexer.add_rule(r"\(\*(?s:.)*?\*\)", |_| LexResult::Ignore);
+
+    lexer.add_rule(r"[A-Za-z_][A-Za-z0-9_]*", |re_match| {
+        LexResult::Token(Token::Ident(re_match.as_str().to_owned()))
+    });
+    lexer.add_try_rule(r#""(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*'"#, |re_match| {
+        let s = re_match.as_str();
+        Ok(Some(Token::Str(unescape(&s[1..s.len() - 1])?)))
+    });
+    lexer.add_rule(r"/(?:[^/\\\n]|\\.)+/", |re_match| {
+        let s = re_match.as_str();
+        LexResult::Token(Token::Regex(s[1..s.len() - 1].replace(r"\/", "/")))
+    });
+
+    lexer.add_rule(r"::=|=", |_| LexResult::Token(Token::Define));
+    lexer.add_rule(r"\|", |_| LexResult::Token(Token::Pipe));
+    lexer.add_rule(r";", |_| LexResult::Token(Token::Semi));
+    lexer.add_rule(r"\(", |_| LexResult::Token(Token::LParen));
+    lexer.add_rule(r"\)", |_| LexResult::Token(Token::RParen));
+    lexer.add_rule(r"\[", |_| LexResult::Token(Token::LBracket));
+    lexer.add_rule(r"\]", |_| LexResult::Token(Token::RBracket));
+    lexer.add_rule(r"\{", |_| LexResult::Token(Token::LBrace));
+    lexer.add_rule(r"\}", |_| LexResult::Token(Token::RBrace));
+    lexer.add_rule(r"\*", |_| LexResult::Token(Token::Star));
+    lexer.add_rule(r"\+", |_| LexResult::Token(Token::Plus));
+    lexer.add_rule(r"\?", |_| LexResult::Token(Token::Question));
+
+    lexer
+}
+
+fn unescape(s: &str) -> Result<String, LexError> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some(c @ ('\\' | '"' | '\'')) => out.push(c),
+            Some(c) => {
+                return Err(LexError::custom(format!(
+                    "Unknown escape `\\{c}`!"
+                )));
+            }
+            None => return Err(LexError::custom("Unterminated escape!")),
+        }
+    }
+    Ok(out)
+}
+
+#[derive(Debug, PartialEq)]
+enum Expr {
+    Ident(String),
+    Literal(String),
+    Alt(Vec<Expr>),
+    Seq(Vec<Expr>),
+    Opt(Box<Expr>),
+    Rep(Box<Expr>),
+    Rep1(Box<Expr>),
+}
+
+impl Expr {
+    fn parse_alt(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let mut alts = vec![tq.parse(Expr::parse_seq)?];
+        while tq.consume_eq(Token::Pipe).is_ok() {
+            alts.push(tq.parse(Expr::parse_seq)?);
+        }
+
+        match alts.len() {
+            1 => Ok((alts.pop().unwrap(), tq.get_idx())),
+            _ => Ok((Expr::Alt(alts), tq.get_idx())),
+        }
+    }
+
+    fn parse_seq(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let mut items = Vec::new();
+        while tq.peek().is_ok_and(Token::starts_term) {
+            items.push(tq.parse(Expr::parse_term)?);
+        }
+
+        match items.len() {
+            1 => Ok((items.pop().unwrap(), tq.get_idx())),
+            _ => Ok((Expr::Seq(items), tq.get_idx())),
+        }
+    }
+
+    fn parse_term(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let factor = match tq.consume()?.clone() {
+            Token::Ident(name) => Expr::Ident(name),
+            Token::Str(text) => Expr::Literal(text),
+            Token::LParen => {
+                let expr = tq.parse(Expr::parse_alt)?;
+                tq.consume_eq(Token::RParen)?;
+                expr
+            }
+            Token::LBracket => {
+                let expr = tq.parse(Expr::parse_alt)?;
+                tq.consume_eq(Token::RBracket)?;
+                Expr::Opt(expr.into())
+            }
+            Token::LBrace => {
+                let expr = tq.parse(Expr::parse_alt)?;
+                tq.consume_eq(Token::RBrace)?;
+                Expr::Rep(expr.into())
+            }
+            token => {
+                return Err(ParseError::new(format!(
+                    "Unexpected {token:?} at position {}!",
+                    tq.get_idx() - 1
+                )));
+            }
+        };
+
+        let expr = match tq.peek() {
+            Ok(Token::Star) => Expr::Rep(factor.into()),
+            Ok(Token::Plus) => Expr::Rep1(factor.into()),
+            Ok(Token::Question) => Expr::Opt(factor.into()),
+            _ => return Ok((factor, tq.get_idx())),
+        };
+        tq.increment()?;
+        Ok((expr, tq.get_idx()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Rule {
+    Syntax(String, Expr),
+    Pattern(String, String),
+}
+
+impl Rule {
+    fn parse(tq: &TokenQueue<Token>) -> ParseResult<Self> {
+        let mut tq = tq.clone();
+
+        let name = tq.consume_map("a rule name", |token| match token {
+            Token::Ident(name) => Some(name.clone()),
+            _ => None,
+        })?;
+        tq.consume_eq(Token::Define)?;
+
+        let rule = match tq.peek()? {
+            Token::Regex(pattern) => {
+                let rule = Rule::Pattern(name, pattern.clone());
+                tq.increment()?;
+                rule
+            }
+            _ => Rule::Syntax(name, tq.parse(Expr::parse_alt)?),
+        };
+
+        tq.consume_eq(Token::Semi).map_err(|_| {
+            ParseError::new(format!(
+                "Expected `;` at end of rule at position {}!",
+                tq.get_idx()
+            ))
+        })?;
+        Ok((rule, tq.get_idx()))
+    }
+}
+
+/// Lowers parsed rules into a [Grammar], desugaring EBNF operators.
+struct Lowering {
+    grammar: Grammar,
+    nonterminals: BTreeSet<String>,
+    fresh: usize,
+}
+
+impl Lowering {
+    fn alternatives(&mut self, rule: &str, expr: &Expr) -> Vec<Vec<Symbol>> {
+        match expr {
+            Expr::Alt(alts) => {
+                alts.iter().map(|alt| self.sequence(rule, alt)).collect()
+            }
+            _ => vec![self.sequence(rule, expr)],
+        }
+    }
+
+    fn sequence(&mut self, rule: &str, expr: &Expr) -> Vec<Symbol> {
+        match expr {
+            Expr::Seq(items) => {
+                items.iter().map(|item| self.symbol(rule, item)).collect()
+            }
+            _ => vec![self.symbol(rule, expr)],
+        }
+    }
+
+    fn symbol(&mut self, rule: &str, expr: &Expr) -> Symbol {
+        let (kind, alternatives) = match expr {
+            Expr::Ident(name) if self.nonterminals.contains(name) => {
+                return Symbol::nt(name);
+            }
+            Expr::Ident(name) | Expr::Literal(name) => return Symbol::t(name),
+            Expr::Alt(_) | Expr::Seq(_) => {
+                ("group", self.alternatives(rule, expr))
+            }
+            Expr::Opt(inner) => {
+                let mut alternatives = vec![vec![]];
+                alternatives.extend(self.alternatives(rule, inner));
+                ("opt", alternatives)
+            }
+            Expr::Rep(inner) | Expr::Rep1(inner) => {
+                let item = self.symbol(rule, inner);
+                let name = self.fresh_name(rule, "rep");
+                let first = match expr {
+                    Expr::Rep(_) => vec![],
+                    _ => vec![item.clone()],
+                };
+                self.grammar.add_production(&name, first);
+                self.grammar
+                    .add_production(&name, vec![Symbol::nt(&name), item]);
+                return Symbol::nt(&name);
+            }
+        };
+        let name = self.fresh_name(rule, kind);
+        for rhs in alternatives {
+            self.grammar.add_production(&name, rhs);
+        }
+        Symbol::nt(&name)
+    }
+
+    fn fresh_name(&mut self, rule: &str, kind: &str) -> String {
+        self.fresh += 1;
+        format!("{rule}__{kind}{}", self.fresh)
+    }
+}
+
+impl Grammar {
+    /// Parse a grammar from its EBNF description. See the [module
+    /// documentation](crate::ebnf) for the supported syntax.
+    pub fn from_ebnf(s: &str) -> crate::Result<Self> {
+        let lexer = setup_lexer();
+        let mut tq = TokenQueue::from(lexer.lex(s)?);
+
+        let mut rules = Vec::new();
+        while !tq.is_consumed() {
+            rules.push(tq.parse(Rule::parse)?);
+        }
+
+        let start = match rules.first() {
+            Some(Rule::Syntax(name, _)) => name.clone(),
+            _ => {
+                return Err(Error::build(
+                    "Expected a syntax rule defining the start symbol!",
+                ));
+            }
+        };
+
+        let mut lowering = Lowering {
+            grammar: Grammar::new(&start),
+            nonterminals: rules
+                .iter()
+                .filter_map(|rule| match rule {
+                    Rule::Syntax(name, _) => Some(name.clone()),
+                    Rule::Pattern(..) => None,
+                })
+                .collect(),
+            fresh: 0,
+        };
+        for rule in &rules {
+            match rule {
+                Rule::Syntax(name, expr) => {
+                    for rhs in lowering.alternatives(name, expr) {
+                        lowering.grammar.add_production(name, rhs);
+                    }
+                }
+                Rule::Pattern(name, pattern) => {
+                    if lowering.nonterminals.contains(name) {
+                        return Err(Error::build(format!(
+                            "`{name}` is defined as both a pattern and a \
+                             syntax rule!"
+                        )));
+                    }
+                    lowering.grammar.add_terminal_pattern(name, pattern);
+                }
+            }
+        }
+
+        lowering.grammar.validate()?;
+        Ok(lowering.grammar)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::earley::EarleyParser;
+    use crate::grammar::{Grammar, Symbol};
+    use crate::parse::TokenQueue;
+
+    fn classify<'a>(token: &'a &str) -> &'a str {
+        match token.parse::<i32>() {
+            Ok(_) => "NUMBER",
+            Err(_) => token,
+        }
+    }
+
+    #[test]
+    fn test_from_ebnf() -> anyhow::Result<()> {
+        let grammar = Grammar::from_ebnf(
+            r#"
+            (* a comma separated list *)
+            list   ::= item { "," item } [ ',' ] ;
+            item   = NUMBER | "(" list ")" ;
+            NUMBER = /[0-9]+/ ;
+            "#,
+        )?;
+
+        assert!(grammar.start() == "list");
+        assert!(
+            grammar.terminal_patterns()
+                == [("NUMBER".to_owned(), "[0-9]+".to_owned())]
+        );
+        assert!(
+            grammar
+                .productions_for("item")
+                .any(|(_, production)| production.rhs
+                    == vec![Symbol::t("NUMBER")])
+        );
+
+        let parser = EarleyParser::new(&grammar, classify)?;
+        for input in [
+            vec!["1"],
+            vec!["1", ",", "2", ","],
+            vec!["(", "1", ",", "2", ")", ",", "3"],
+        ] {
+            parser.parse(&TokenQueue::from(input))?;
+        }
+        assert!(parser.parse(&TokenQueue::from(vec!["1", "2"])).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_ebnf_errors() {
+        assert!(Grammar::from_ebnf("").is_err());
+        assert!(Grammar::from_ebnf("a = b").is_err());
+        assert!(Grammar::from_ebnf("a = ( b ;").is_err());
+        assert!(Grammar::from_ebnf("a = b ; a = /x/ ;").is_err());
+    }
+}
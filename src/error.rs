@@ -0,0 +1,67 @@
+use crate::lex::LexError;
+use crate::parse::ParseError;
+use crate::span::Span;
+
+/// Represents any error returned by `rlrl`, by category, so applications can
+/// handle lexing, parsing, I/O, and grammar construction errors through one
+/// type.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The input couldn't be lexed.
+    #[error(transparent)]
+    Lex(#[from] LexError),
+    /// The tokens couldn't be parsed.
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    /// Reading the input failed.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// A grammar couldn't be built from its description.
+    #[error("{0}")]
+    Build(String),
+}
+
+impl Error {
+    /// Create a [Error::Build] error with the message `message`.
+    pub fn build(message: impl Into<String>) -> Self {
+        Self::Build(message.into())
+    }
+
+    /// Get the position in the input the error refers to, if known. Lex
+    /// errors give byte offsets and parse errors give token indices.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Self::Lex(
+                LexError::Unmatched { pos }
+                | LexError::InRule { pos, .. }
+                | LexError::LimitExceeded { pos, .. },
+            ) => Some(Span::new(*pos, *pos)),
+            Self::Parse(err) => err.idx().map(|idx| Span::new(idx, idx)),
+            _ => None,
+        }
+    }
+}
+
+/// Result type returned by `rlrl` outside the parsing API.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+#[cfg(test)]
+mod tests {
+    use crate::error::Error;
+    use crate::grammar::Grammar;
+    use crate::lex::LexError;
+    use crate::span::Span;
+
+    #[test]
+    fn test_error() {
+        let err = Grammar::from_ebnf("a = 'x' ; @").unwrap_err();
+        assert!(matches!(err, Error::Lex(LexError::Unmatched { pos: 10 })));
+        assert!(err.span() == Some(Span::new(10, 10)));
+
+        let err = Grammar::from_ebnf("a = 'x'").unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+
+        let err = Grammar::from_ebnf("a = b ; a = /x/ ;").unwrap_err();
+        assert!(matches!(err, Error::Build(_)) && err.span().is_none());
+    }
+}
@@ -0,0 +1,85 @@
+//! Tree-walking evaluation, the last step of a lex → parse → eval pipeline.
+//! See `examples/interpreter.rs` for an interpreter built this way, with
+//! variables kept in a [ScopeStack](crate::scope::ScopeStack).
+
+/// Implemented by syntax trees that can be evaluated in a context of type
+/// `Ctx`, like an environment of variables, producing a value of type `Out`,
+/// typically a `Result`.
+///
+/// A tree can implement `Eval` more than once, for different contexts or
+/// outputs, like an interpreter and a type checker over the same AST.
+///
+/// ```
+/// use rlrl::eval::Eval;
+///
+/// enum Expr {
+///     Num(i64),
+///     Var,
+///     Add(Box<Expr>, Box<Expr>),
+/// }
+///
+/// impl Eval<i64, i64> for Expr {
+///     fn eval(&self, x: &mut i64) -> i64 {
+///         match self {
+///             Expr::Num(val) => *val,
+///             Expr::Var => *x,
+///             Expr::Add(lhs, rhs) => lhs.eval(x) + rhs.eval(x),
+///         }
+///     }
+/// }
+///
+/// let expr = Expr::Add(Box::new(Expr::Var), Box::new(Expr::Num(2)));
+/// assert!(expr.eval(&mut 3) == 5);
+/// ```
+pub trait Eval<Ctx, Out> {
+    /// Evaluate the tree in the context `ctx`.
+    fn eval(&self, ctx: &mut Ctx) -> Out;
+}
+
+impl<Ctx, Out, T: Eval<Ctx, Out> + ?Sized> Eval<Ctx, Out> for Box<T> {
+    fn eval(&self, ctx: &mut Ctx) -> Out {
+        (**self).eval(ctx)
+    }
+}
+
+/// Evaluates the items in order, stopping at the first error, like the
+/// statements of a block.
+impl<Ctx, T, E, I> Eval<Ctx, Result<Vec<T>, E>> for [I]
+where
+    I: Eval<Ctx, Result<T, E>>,
+{
+    fn eval(&self, ctx: &mut Ctx) -> Result<Vec<T>, E> {
+        self.iter().map(|item| item.eval(ctx)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::eval::Eval;
+
+    /// Pushes its value onto the context, failing on negative values.
+    struct Push(i64);
+
+    impl Eval<Vec<i64>, Result<(), String>> for Push {
+        fn eval(&self, ctx: &mut Vec<i64>) -> Result<(), String> {
+            if self.0 < 0 {
+                return Err(format!("Can't push {}!", self.0));
+            }
+            ctx.push(self.0);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_eval_slice() {
+        let mut ctx = Vec::new();
+        let items = [Push(1), Push(2)];
+        assert!(items[..].eval(&mut ctx) == Ok(vec![(), ()]));
+        assert!(ctx == [1, 2]);
+
+        let items = [Box::new(Push(3)), Box::new(Push(-1)), Box::new(Push(4))];
+        let result: Result<Vec<()>, String> = items[..].eval(&mut ctx);
+        assert!(result == Err("Can't push -1!".into()));
+        assert!(ctx == [1, 2, 3]);
+    }
+}
@@ -0,0 +1,276 @@
+//! A C API for embedding lexers built with `rlrl` in programs written in
+//! other languages.
+//!
+//! A lexer is created from a table of [RlrlRule]s with [rlrl_lexer_new],
+//! used to lex buffers with [rlrl_lexer_lex], and freed with
+//! [rlrl_lexer_free]. Each successful lex returns an [RlrlTokens] list, read
+//! with [rlrl_tokens_len] and [rlrl_tokens_get] and freed with
+//! [rlrl_tokens_free]. Rules are resolved as documented on
+//! [Lexer].
+//!
+//! Each rule only maps matches to its `kind`, or skips them if `ignore` is
+//! set: the lexer has no [LexLimits](crate::lex::LexLimits), so inputs of
+//! any size are lexed, and every rule is always used, as rules can't be put
+//! in [groups](crate::lex::RuleOptions::with_group).
+//!
+//! The declarations for C are in `include/rlrl.h`. The `rlrl-ffi` crate in
+//! this workspace builds the module as a shared and a static library for C
+//! programs to link against.
+
+use crate::lex::{LexError, LexResult, Lexer, RuleOptions};
+use std::ffi::{CStr, c_char};
+
+/// A lexer rule, as passed to [rlrl_lexer_new].
+#[repr(C)]
+pub struct RlrlRule {
+    /// The rule's regex, as a NUL-terminated UTF-8 string.
+    pub pattern: *const c_char,
+    /// The kind given to tokens the rule matches.
+    pub kind: u32,
+    /// Whether input the rule matches is skipped instead of producing a
+    /// token.
+    pub ignore: bool,
+}
+
+/// A token, as returned by [rlrl_tokens_get].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RlrlToken {
+    /// The kind of the rule that matched the token.
+    pub kind: u32,
+    /// The byte offset of the start of the token.
+    pub start: usize,
+    /// The byte offset of the end of the token.
+    pub end: usize,
+}
+
+/// An opaque lexer created by [rlrl_lexer_new].
+pub struct RlrlLexer {
+    /// Lexes the kinds of tokens.
+    lexer: Lexer<u32>,
+}
+
+/// An opaque list of tokens returned by [rlrl_lexer_lex].
+pub struct RlrlTokens {
+    tokens: Vec<RlrlToken>,
+}
+
+/// Create a lexer from the `count` rules at `rules`, in priority order.
+/// Returns null if a pattern isn't valid UTF-8 or isn't a valid regex.
+///
+/// # Safety
+///
+/// `rules` must point to `count` rules, each with a `pattern` pointing to a
+/// NUL-terminated string.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_lexer_new(
+    rules: *const RlrlRule,
+    count: usize,
+) -> *mut RlrlLexer {
+    let rules = match count {
+        0 => &[],
+        _ => unsafe { std::slice::from_raw_parts(rules, count) },
+    };
+    let mut lexer = Lexer::new();
+    for rule in rules {
+        let Ok(pattern) = unsafe { CStr::from_ptr(rule.pattern) }.to_str()
+        else {
+            return std::ptr::null_mut();
+        };
+        let kind = rule.kind;
+        let added = match rule.ignore {
+            true => lexer.add_rule_with_options(
+                pattern,
+                RuleOptions::default(),
+                |_| LexResult::Ignore,
+            ),
+            false => lexer.add_rule_with_options(
+                pattern,
+                RuleOptions::default(),
+                move |_| LexResult::Token(kind),
+            ),
+        };
+        if added.is_err() {
+            return std::ptr::null_mut();
+        }
+    }
+    Box::into_raw(Box::new(RlrlLexer { lexer }))
+}
+
+/// Free a lexer created by [rlrl_lexer_new]. Does nothing if `lexer` is
+/// null.
+///
+/// # Safety
+///
+/// `lexer` must be null or returned by [rlrl_lexer_new], and not already
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_lexer_free(lexer: *mut RlrlLexer) {
+    if !lexer.is_null() {
+        drop(unsafe { Box::from_raw(lexer) });
+    }
+}
+
+/// Lex the `len` bytes of UTF-8 at `input`. Returns the tokens, or null if
+/// the input isn't valid UTF-8 or no rule matches part of it, in which case
+/// the byte offset of the problem is written to `error_pos` if it isn't
+/// null.
+///
+/// # Safety
+///
+/// `lexer` must be a live lexer returned by [rlrl_lexer_new], `input` must
+/// point to `len` readable bytes, and `error_pos` must be null or writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_lexer_lex(
+    lexer: *const RlrlLexer,
+    input: *const u8,
+    len: usize,
+    error_pos: *mut usize,
+) -> *mut RlrlTokens {
+    let lexer = unsafe { &*lexer };
+    let input = match len {
+        0 => &[],
+        _ => unsafe { std::slice::from_raw_parts(input, len) },
+    };
+    let fail = |pos: usize| {
+        if !error_pos.is_null() {
+            unsafe { *error_pos = pos };
+        }
+        std::ptr::null_mut()
+    };
+    let input = match std::str::from_utf8(input) {
+        Ok(input) => input,
+        Err(err) => return fail(err.valid_up_to()),
+    };
+
+    let matches = match lexer.lexer.lex_matches(input) {
+        Ok(matches) => matches,
+        Err(LexError::Unmatched { pos }) => return fail(pos),
+        // there are no limits or fallible handlers to fail otherwise
+        Err(_) => return fail(0),
+    };
+    let tokens = matches
+        .iter()
+        .map(|token| RlrlToken {
+            kind: *token.token(),
+            start: token.span().start,
+            end: token.span().end,
+        })
+        .collect();
+    Box::into_raw(Box::new(RlrlTokens { tokens }))
+}
+
+/// Get the number of tokens in `tokens`.
+///
+/// # Safety
+///
+/// `tokens` must be a live list returned by [rlrl_lexer_lex].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_tokens_len(tokens: *const RlrlTokens) -> usize {
+    unsafe { &*tokens }.tokens.len()
+}
+
+/// Write the token at index `idx` in `tokens` to `out`, returning false if
+/// `idx` is out of bounds.
+///
+/// # Safety
+///
+/// `tokens` must be a live list returned by [rlrl_lexer_lex], and `out` must
+/// be writable.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_tokens_get(
+    tokens: *const RlrlTokens,
+    idx: usize,
+    out: *mut RlrlToken,
+) -> bool {
+    match unsafe { &*tokens }.tokens.get(idx) {
+        Some(token) => {
+            unsafe { *out = *token };
+            true
+        }
+        None => false,
+    }
+}
+
+/// Free a list of tokens returned by [rlrl_lexer_lex]. Does nothing if
+/// `tokens` is null.
+///
+/// # Safety
+///
+/// `tokens` must be null or returned by [rlrl_lexer_lex], and not already
+/// freed.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rlrl_tokens_free(tokens: *mut RlrlTokens) {
+    if !tokens.is_null() {
+        drop(unsafe { Box::from_raw(tokens) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ffi::{
+        RlrlRule, RlrlToken, rlrl_lexer_free, rlrl_lexer_lex, rlrl_lexer_new,
+        rlrl_tokens_free, rlrl_tokens_get, rlrl_tokens_len,
+    };
+
+    fn rule(
+        pattern: &'static std::ffi::CStr,
+        kind: u32,
+        ignore: bool,
+    ) -> RlrlRule {
+        RlrlRule {
+            pattern: pattern.as_ptr(),
+            kind,
+            ignore,
+        }
+    }
+
+    #[test]
+    fn test_ffi() {
+        let rules = [
+            rule(c"\\s+", 0, true),
+            rule(c"[0-9]+", 1, false),
+            rule(c"[a-z]+", 2, false),
+        ];
+
+        unsafe {
+            let lexer = rlrl_lexer_new(rules.as_ptr(), rules.len());
+            assert!(!lexer.is_null());
+
+            let input = "ab 12";
+            let mut error_pos = usize::MAX;
+            let tokens = rlrl_lexer_lex(
+                lexer,
+                input.as_ptr(),
+                input.len(),
+                &mut error_pos,
+            );
+            assert!(!tokens.is_null() && rlrl_tokens_len(tokens) == 2);
+            let mut token = RlrlToken::default();
+            assert!(rlrl_tokens_get(tokens, 1, &mut token));
+            assert!(
+                token
+                    == RlrlToken {
+                        kind: 1,
+                        start: 3,
+                        end: 5
+                    }
+            );
+            assert!(!rlrl_tokens_get(tokens, 2, &mut token));
+            rlrl_tokens_free(tokens);
+
+            let input = "ab ?";
+            let tokens = rlrl_lexer_lex(
+                lexer,
+                input.as_ptr(),
+                input.len(),
+                &mut error_pos,
+            );
+            assert!(tokens.is_null() && error_pos == 3);
+            rlrl_lexer_free(lexer);
+
+            let bad = [rule(c"(", 0, false)];
+            assert!(rlrl_lexer_new(bad.as_ptr(), bad.len()).is_null());
+        }
+    }
+}
@@ -0,0 +1,604 @@
+use crate::diagnostic::Diagnostic;
+use crate::dynamic::DynamicToken;
+use crate::lex::{LexError, LexResult, Lexer, RuleOptions};
+use crate::span::Span;
+use crate::version::{Version, VersionRange};
+use crate::{Error, Result};
+use regex::Match;
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Display;
+use std::sync::Arc;
+
+/// A symbol appearing on the right hand side of a [Production].
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Symbol {
+    /// A symbol matched directly against a token in the input.
+    Terminal(String),
+    /// A symbol defined by one or more productions in the grammar.
+    NonTerminal(String),
+}
+
+impl Symbol {
+    /// Construct a [Symbol::Terminal] named `name`.
+    pub fn t(name: &str) -> Self {
+        Self::Terminal(name.to_owned())
+    }
+
+    /// Construct a [Symbol::NonTerminal] named `name`.
+    pub fn nt(name: &str) -> Self {
+        Self::NonTerminal(name.to_owned())
+    }
+
+    /// Borrow the name of the symbol.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Terminal(name) => name,
+            Self::NonTerminal(name) => name,
+        }
+    }
+
+    /// Return true if the symbol is a terminal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Terminal(_))
+    }
+}
+
+impl Display for Symbol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Terminal(name) => write!(f, "{name:?}"),
+            Self::NonTerminal(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+/// Represents a production `lhs -> rhs` in a [Grammar].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Production {
+    pub lhs: String,
+    pub rhs: Vec<Symbol>,
+    /// The language versions the production is part of.
+    pub versions: VersionRange,
+}
+
+impl Display for Production {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ->", self.lhs)?;
+        if self.rhs.is_empty() {
+            return write!(f, " ε");
+        }
+        for symbol in &self.rhs {
+            write!(f, " {symbol}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A likely mistake in a [Grammar], found by [Grammar::lint].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GrammarLint<'a> {
+    /// The non-terminal has productions but can't be derived from the start
+    /// symbol, so they're dead.
+    Unreachable(&'a str),
+    /// The terminal has a pattern but no production consumes it.
+    UnusedTerminal(&'a str),
+    /// Each non-terminal in the cycle derives the next without consuming
+    /// input, the last deriving the first, so the grammar is ambiguous:
+    /// any parse through the cycle can loop around it any number of times.
+    NullableCycle(Vec<&'a str>),
+}
+
+impl GrammarLint<'_> {
+    /// Create a warning diagnostic for the lint. Grammars don't record where
+    /// they were declared, so the span is empty.
+    pub fn diagnostic(&self) -> Diagnostic {
+        let diagnostic = Diagnostic::warning(self.to_string(), Span::default());
+        match self {
+            Self::NullableCycle(_) => diagnostic
+                .with_note("the grammar is ambiguous around the cycle"),
+            _ => diagnostic,
+        }
+    }
+}
+
+impl Display for GrammarLint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable(name) => {
+                write!(f, "Non-terminal `{name}` is unreachable!")
+            }
+            Self::UnusedTerminal(name) => {
+                write!(f, "Terminal {name:?} isn't used by any production!")
+            }
+            Self::NullableCycle(cycle) => {
+                write!(f, "Nullable cycle ")?;
+                for name in cycle {
+                    write!(f, "`{name}` → ")?;
+                }
+                write!(f, "`{}`!", cycle[0])
+            }
+        }
+    }
+}
+
+/// Represents a context-free grammar declared as a list of productions.
+///
+/// Terminals are identified by name; parsers built from the grammar are given
+/// a function that maps each token to the name of its terminal.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Grammar {
+    start: String,
+    productions: Vec<Production>,
+    terminal_patterns: Vec<(String, String)>,
+}
+
+impl Grammar {
+    /// Create an empty grammar whose start symbol is `start`.
+    pub fn new(start: &str) -> Self {
+        Self {
+            start: start.to_owned(),
+            productions: Vec::new(),
+            terminal_patterns: Vec::new(),
+        }
+    }
+
+    /// Add the production `lhs -> rhs`, returning its index.
+    pub fn add_production(&mut self, lhs: &str, rhs: Vec<Symbol>) -> usize {
+        self.add_versioned_production(lhs, rhs, VersionRange::default())
+    }
+
+    /// Add the production `lhs -> rhs` like [Grammar::add_production],
+    /// making it part of the language versions in `versions` only. See
+    /// [Grammar::for_version].
+    pub fn add_versioned_production(
+        &mut self,
+        lhs: &str,
+        rhs: Vec<Symbol>,
+        versions: VersionRange,
+    ) -> usize {
+        self.productions.push(Production {
+            lhs: lhs.to_owned(),
+            rhs,
+            versions,
+        });
+        self.productions.len() - 1
+    }
+
+    /// Create a grammar with only the productions that are part of the
+    /// language version `version`, so parsers built from it accept that
+    /// dialect. Production indices refer to the new grammar.
+    pub fn for_version(&self, version: &Version) -> Self {
+        Self {
+            start: self.start.clone(),
+            productions: self
+                .productions
+                .iter()
+                .filter(|production| production.versions.contains(version))
+                .cloned()
+                .collect(),
+            terminal_patterns: self.terminal_patterns.clone(),
+        }
+    }
+
+    /// Record the regex `pattern` that matches the terminal `name`. Patterns
+    /// are informational; tools that build a lexer from the grammar use them.
+    pub fn add_terminal_pattern(&mut self, name: &str, pattern: &str) {
+        self.terminal_patterns
+            .push((name.to_owned(), pattern.to_owned()));
+    }
+
+    /// Borrow the (terminal, pattern) pairs in the order they were added.
+    pub fn terminal_patterns(&self) -> &[(String, String)] {
+        &self.terminal_patterns
+    }
+
+    /// Borrow the name of the start symbol.
+    pub fn start(&self) -> &str {
+        &self.start
+    }
+
+    /// Borrow the productions in the order they were added.
+    pub fn productions(&self) -> &[Production] {
+        &self.productions
+    }
+
+    /// Iterate over the productions (and their indices) for the non-terminal
+    /// `lhs`.
+    pub fn productions_for<'a>(
+        &'a self,
+        lhs: &'a str,
+    ) -> impl Iterator<Item = (usize, &'a Production)> {
+        self.productions
+            .iter()
+            .enumerate()
+            .filter(move |(_, production)| production.lhs == lhs)
+    }
+
+    /// Get the set of non-terminals defined by the grammar.
+    pub fn nonterminals(&self) -> BTreeSet<&str> {
+        self.productions
+            .iter()
+            .map(|production| production.lhs.as_str())
+            .collect()
+    }
+
+    /// Get the set of terminals used by the grammar.
+    pub fn terminals(&self) -> BTreeSet<&str> {
+        self.productions
+            .iter()
+            .flat_map(|production| &production.rhs)
+            .filter(|symbol| symbol.is_terminal())
+            .map(|symbol| symbol.name())
+            .collect()
+    }
+
+    /// Compute the set of non-terminals that can derive the empty string.
+    pub fn nullable(&self) -> BTreeSet<&str> {
+        let mut nullable = BTreeSet::new();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                if nullable.contains(production.lhs.as_str()) {
+                    continue;
+                }
+                if production.rhs.iter().all(|symbol| match symbol {
+                    Symbol::Terminal(_) => false,
+                    Symbol::NonTerminal(name) => {
+                        nullable.contains(name.as_str())
+                    }
+                }) {
+                    nullable.insert(production.lhs.as_str());
+                    changed = true;
+                }
+            }
+        }
+        nullable
+    }
+
+    /// Compute the FIRST set of each non-terminal: the terminals that can
+    /// begin a string derived from it.
+    pub fn first_sets(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let nullable = self.nullable();
+        let mut first: BTreeMap<&str, BTreeSet<&str>> = self
+            .nonterminals()
+            .into_iter()
+            .map(|name| (name, BTreeSet::new()))
+            .collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for production in &self.productions {
+                let mut additions = BTreeSet::new();
+                for symbol in &production.rhs {
+                    match symbol {
+                        Symbol::Terminal(name) => {
+                            additions.insert(name.as_str());
+                            break;
+                        }
+                        Symbol::NonTerminal(name) => {
+                            if let Some(set) = first.get(name.as_str()) {
+                                additions.extend(set.iter().copied());
+                            }
+                            if !nullable.contains(name.as_str()) {
+                                break;
+                            }
+                        }
+                    }
+                }
+                let set = first.entry(production.lhs.as_str()).or_default();
+                for terminal in additions {
+                    changed |= set.insert(terminal);
+                }
+            }
+        }
+        first
+    }
+
+    /// Return `Ok(())` if the start symbol and every non-terminal referenced
+    /// by a production are defined, else return an error.
+    pub fn validate(&self) -> Result<()> {
+        let nonterminals = self.nonterminals();
+        if !nonterminals.contains(self.start.as_str()) {
+            return Err(Error::build(format!(
+                "Start symbol `{}` has no productions!",
+                self.start
+            )));
+        }
+        for production in &self.productions {
+            for symbol in &production.rhs {
+                if let Symbol::NonTerminal(name) = symbol
+                    && !nonterminals.contains(name.as_str())
+                {
+                    return Err(Error::build(format!(
+                        "Non-terminal `{name}` used in `{production}` has no \
+                         productions!"
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the grammar for likely mistakes, returning each one found in
+    /// order: non-terminals unreachable from the start symbol, terminals
+    /// with a pattern that no production uses, and nullable cycles.
+    pub fn lint(&self) -> Vec<GrammarLint<'_>> {
+        let mut lints = Vec::new();
+
+        let mut reachable = BTreeSet::from([self.start.as_str()]);
+        let mut queue = VecDeque::from([self.start.as_str()]);
+        while let Some(lhs) = queue.pop_front() {
+            for (_, production) in self.productions_for(lhs) {
+                for symbol in &production.rhs {
+                    if let Symbol::NonTerminal(name) = symbol
+                        && reachable.insert(name.as_str())
+                    {
+                        queue.push_back(name.as_str());
+                    }
+                }
+            }
+        }
+        lints.extend(
+            self.nonterminals()
+                .into_iter()
+                .filter(|name| !reachable.contains(name))
+                .map(GrammarLint::Unreachable),
+        );
+
+        let terminals = self.terminals();
+        let mut unused = BTreeSet::new();
+        for (name, _) in &self.terminal_patterns {
+            if !terminals.contains(name.as_str()) && unused.insert(name) {
+                lints.push(GrammarLint::UnusedTerminal(name.as_str()));
+            }
+        }
+
+        // non-terminals each one derives with the rest of a production
+        // nullable
+        let nullable = self.nullable();
+        let mut unit: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for production in &self.productions {
+            for (i, symbol) in production.rhs.iter().enumerate() {
+                let Symbol::NonTerminal(name) = symbol else {
+                    continue;
+                };
+                let rest_nullable = production
+                    .rhs
+                    .iter()
+                    .enumerate()
+                    .filter(|(j, _)| *j != i)
+                    .all(|(_, other)| match other {
+                        Symbol::Terminal(_) => false,
+                        Symbol::NonTerminal(other) => {
+                            nullable.contains(other.as_str())
+                        }
+                    });
+                if rest_nullable {
+                    unit.entry(production.lhs.as_str())
+                        .or_default()
+                        .insert(name.as_str());
+                }
+            }
+        }
+        let mut in_cycle = BTreeSet::new();
+        for &start in unit.keys() {
+            if in_cycle.contains(start) {
+                continue;
+            }
+            // search for a path back to `start`, recording each
+            // non-terminal's parent
+            let mut parents: BTreeMap<&str, &str> = BTreeMap::new();
+            let mut queue = VecDeque::from([start]);
+            let mut last = None;
+            'search: while let Some(lhs) = queue.pop_front() {
+                for &name in unit.get(lhs).into_iter().flatten() {
+                    if name == start {
+                        last = Some(lhs);
+                        break 'search;
+                    }
+                    if !parents.contains_key(name) {
+                        parents.insert(name, lhs);
+                        queue.push_back(name);
+                    }
+                }
+            }
+            let Some(mut name) = last else {
+                continue;
+            };
+            let mut cycle = vec![name];
+            while name != start {
+                name = parents[name];
+                cycle.push(name);
+            }
+            cycle.reverse();
+            in_cycle.extend(cycle.iter().copied());
+            lints.push(GrammarLint::NullableCycle(cycle));
+        }
+
+        lints
+    }
+}
+
+impl Display for Grammar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for production in &self.productions {
+            writeln!(f, "{production}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Lexes input into [DynamicToken]s whose kinds are the terminals of a
+/// grammar, ignoring whitespace.
+/// Terminals with a pattern (see [Grammar::add_terminal_pattern]) are matched
+/// by it, and other terminals are matched literally by name. A literal
+/// terminal wins a tie with a pattern, so keywords aren't lexed as
+/// identifiers.
+pub struct TerminalLexer {
+    lexer: Lexer<DynamicToken>,
+}
+
+impl TerminalLexer {
+    /// Create a lexer for the terminals of `grammar`, returning an error if
+    /// a terminal's pattern is invalid.
+    pub fn new(grammar: &Grammar) -> Result<Self> {
+        let mut lexer = Lexer::new();
+        lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+
+        let patterns = grammar.terminal_patterns();
+        for terminal in grammar.terminals() {
+            if patterns.iter().any(|(name, _)| name == terminal) {
+                continue;
+            }
+            lexer
+                .add_rule(&regex::escape(terminal), terminal_handler(terminal));
+        }
+        for (name, pattern) in patterns {
+            let options = RuleOptions::default().with_name(name);
+            lexer
+                .add_rule_with_options(pattern, options, terminal_handler(name))
+                .map_err(|err| {
+                    Error::build(format!("Invalid pattern for `{name}`: {err}"))
+                })?;
+        }
+
+        Ok(Self { lexer })
+    }
+
+    /// Lex the string `s` into tokens of the grammar's terminals.
+    pub fn lex(
+        &self,
+        s: &str,
+    ) -> std::result::Result<Vec<DynamicToken>, LexError> {
+        self.lexer.lex(s)
+    }
+}
+
+/// Make a handler lexing matches as tokens of the terminal `name`.
+fn terminal_handler(
+    name: &str,
+) -> impl Fn(Match) -> LexResult<DynamicToken> + Send + Sync + use<> {
+    let kind: Arc<str> = name.into();
+    move |re_match| {
+        LexResult::Token(DynamicToken {
+            kind: kind.clone(),
+            text: re_match.as_str().to_owned(),
+            span: re_match.range().into(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::earley::EarleyParser;
+    use crate::grammar::{Grammar, GrammarLint, Symbol, TerminalLexer};
+    use crate::parse::TokenQueue;
+    use crate::span::Span;
+    use crate::version::{Version, VersionRange};
+
+    #[test]
+    fn test_first_sets() -> anyhow::Result<()> {
+        let mut grammar = Grammar::new("list");
+        grammar.add_production("list", vec![]);
+        grammar.add_production(
+            "list",
+            vec![Symbol::nt("item"), Symbol::nt("list")],
+        );
+        grammar.add_production("item", vec![Symbol::t("num")]);
+        grammar.validate()?;
+
+        assert!(grammar.nullable().contains("list"));
+        assert!(!grammar.nullable().contains("item"));
+        assert!(grammar.first_sets()["list"].contains("num"));
+
+        grammar.add_production("item", vec![Symbol::nt("missing")]);
+        assert!(grammar.validate().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut grammar = Grammar::new("expr");
+        grammar.add_production("expr", vec![Symbol::nt("term")]);
+        grammar.add_production("term", vec![Symbol::t("num")]);
+        grammar.add_production(
+            "term",
+            vec![Symbol::nt("opt"), Symbol::nt("expr"), Symbol::nt("opt")],
+        );
+        grammar.add_production("opt", vec![]);
+        grammar.add_production("opt", vec![Symbol::t("+")]);
+        grammar.add_production("dead", vec![Symbol::t("num")]);
+        grammar.add_terminal_pattern("num", "[0-9]+");
+        grammar.add_terminal_pattern("ident", "[a-z]+");
+
+        let lints = grammar.lint();
+        assert!(
+            lints
+                == [
+                    GrammarLint::Unreachable("dead"),
+                    GrammarLint::UnusedTerminal("ident"),
+                    GrammarLint::NullableCycle(vec!["expr", "term"]),
+                ]
+        );
+        assert!(
+            lints[2].to_string() == "Nullable cycle `expr` → `term` → `expr`!"
+        );
+        assert!(lints[2].diagnostic().notes.len() == 1);
+
+        let mut grammar = Grammar::new("list");
+        grammar.add_production("list", vec![]);
+        grammar
+            .add_production("list", vec![Symbol::t("num"), Symbol::nt("list")]);
+        assert!(grammar.lint().is_empty());
+    }
+
+    #[test]
+    fn test_for_version() -> anyhow::Result<()> {
+        // `print x` is a statement until 2.0, which makes it a call
+        let mut grammar = Grammar::new("stmt");
+        grammar.add_versioned_production(
+            "stmt",
+            vec![Symbol::t("print"), Symbol::t("x")],
+            VersionRange::default().with_until(Version::parse("1.x")?),
+        );
+        grammar.add_production(
+            "stmt",
+            vec![
+                Symbol::t("print"),
+                Symbol::t("("),
+                Symbol::t("x"),
+                Symbol::t(")"),
+            ],
+        );
+
+        let classify = String::as_str;
+        let v1 = grammar.for_version(&Version::parse("1.5")?);
+        let v2 = grammar.for_version(&Version::parse("2")?);
+        assert!(v1.productions().len() == 2 && v2.productions().len() == 1);
+        let tokens = TokenQueue::from(vec!["print".to_owned(), "x".to_owned()]);
+        assert!(EarleyParser::new(&v1, classify)?.parse(&tokens).is_ok());
+        assert!(EarleyParser::new(&v2, classify)?.parse(&tokens).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_terminal_lexer() -> anyhow::Result<()> {
+        let grammar = Grammar::from_ebnf(
+            r#"stmt = "let" ident "=" ident ; ident = /[a-z]+/ ;"#,
+        )?;
+        let lexer = TerminalLexer::new(&grammar)?;
+
+        let tokens = lexer.lex("let letter = x")?;
+        let terminals: Vec<&str> =
+            tokens.iter().map(|token| token.kind()).collect();
+        assert!(terminals == vec!["let", "ident", "=", "ident"]);
+        assert!(tokens[1].text == "letter");
+        assert!(tokens[1].span == Span::new(4, 10));
+        assert!(lexer.lex("let ?").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+//! Lexing identifiers the way modern languages specify them: decoding
+//! `\u{...}` escapes, normalizing, and warning about identifiers that mix
+//! scripts or look like others.
+//!
+//! Unicode normalization and confusable tables are large, so they're hooks:
+//! pass a function like `|s| s.nfc().collect()` from the
+//! `unicode-normalization` crate to [IdentPolicy::with_normalizer].
+//!
+//! ```
+//! use rlrl::ident::IdentPolicy;
+//! use rlrl::lex::Lexer;
+//!
+//! let mut lexer: Lexer<String> = Lexer::with_common_ignores();
+//! let policy = IdentPolicy::default()
+//!     .with_escapes(true)
+//!     .with_mixed_script_check(true);
+//! lexer.add_ident_rule(r"(\w|\\u\{[0-9a-fA-F]+\})+", policy, |ident| ident);
+//!
+//! assert!(lexer.lex(r"caf\u{e9}").unwrap() == ["café"]);
+//! // the `а` is Cyrillic
+//! let (_, warnings) = lexer.lex_with_warnings("pаy").unwrap();
+//! assert!(warnings[0].message.contains("Latin and Cyrillic"));
+//! ```
+
+use crate::lex::{HandlerError, LexError, LexResult, Lexer};
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+/// A function normalizing an identifier, like to NFC or NFKC.
+pub type Normalizer = fn(&str) -> String;
+
+/// A function checking whether an identifier is confusable with another,
+/// returning a warning to report if it is.
+pub type ConfusableCheck = fn(&str) -> Option<String>;
+
+/// The script of a character, as far as telling mixed-script identifiers
+/// apart goes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Script {
+    /// Digits, `_`, and other characters used with any script.
+    Common,
+    Latin,
+    Greek,
+    Cyrillic,
+    Armenian,
+    Hebrew,
+    Arabic,
+    Hiragana,
+    Katakana,
+    Han,
+    Hangul,
+    /// Any script not listed.
+    Other,
+}
+
+impl Script {
+    /// Get the script of the character `c`.
+    pub fn of(c: char) -> Self {
+        match c {
+            'a'..='z' | 'A'..='Z' => Self::Latin,
+            '\u{0}'..='\u{7f}' | '×' | '÷' => Self::Common,
+            '\u{c0}'..='\u{24f}' | '\u{1e00}'..='\u{1eff}' => Self::Latin,
+            '\u{370}'..='\u{3ff}' | '\u{1f00}'..='\u{1fff}' => Self::Greek,
+            '\u{400}'..='\u{52f}' => Self::Cyrillic,
+            '\u{530}'..='\u{58f}' => Self::Armenian,
+            '\u{590}'..='\u{5ff}' => Self::Hebrew,
+            '\u{600}'..='\u{6ff}' => Self::Arabic,
+            '\u{3040}'..='\u{309f}' => Self::Hiragana,
+            '\u{30a0}'..='\u{30ff}' => Self::Katakana,
+            '\u{3400}'..='\u{4dbf}' | '\u{4e00}'..='\u{9fff}' => Self::Han,
+            '\u{ac00}'..='\u{d7af}' => Self::Hangul,
+            _ => Self::Other,
+        }
+    }
+
+    /// Get the scripts used in `s`, ignoring [Script::Common] characters, if
+    /// they aren't scripts written together: Han with Hiragana and Katakana
+    /// (Japanese) or with Hangul (Korean).
+    pub fn mixed(s: &str) -> Option<BTreeSet<Script>> {
+        let scripts: BTreeSet<Script> = s
+            .chars()
+            .map(Self::of)
+            .filter(|script| *script != Self::Common)
+            .collect();
+        let japanese = [Self::Han, Self::Hiragana, Self::Katakana];
+        let korean = [Self::Han, Self::Hangul];
+        let written_together = |group: &[Script]| {
+            scripts.iter().all(|script| group.contains(script))
+        };
+        match scripts.len() > 1
+            && !written_together(&japanese)
+            && !written_together(&korean)
+        {
+            true => Some(scripts),
+            false => None,
+        }
+    }
+}
+
+impl Display for Script {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+/// Decode the `\u{...}` escapes in the identifier `s`.
+pub fn decode_escapes(s: &str) -> Result<String, LexError> {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(pos) = rest.find("\\u{") {
+        out.push_str(&rest[..pos]);
+        let (hex, after) = rest[pos + 3..]
+            .split_once('}')
+            .ok_or_else(|| LexError::custom("Expected `}` after escape!"))?;
+        let c = u32::from_str_radix(hex, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| {
+                LexError::custom(format!("Invalid escape `\\u{{{hex}}}`!"))
+            })?;
+        out.push(c);
+        rest = after;
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// How identifiers lexed with [Lexer::add_ident_rule] are decoded, normalized
+/// and checked. The default does none of it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IdentPolicy {
+    escapes: bool,
+    normalizer: Option<Normalizer>,
+    confusable: Option<ConfusableCheck>,
+    mixed_script: bool,
+}
+
+impl IdentPolicy {
+    /// Set whether `\u{...}` escapes are decoded, as in JavaScript.
+    pub fn with_escapes(mut self, yes: bool) -> Self {
+        self.escapes = yes;
+        self
+    }
+
+    /// Normalize identifiers with `normalizer` after decoding escapes, so
+    /// identifiers spelled with different code points compare equal.
+    pub fn with_normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = Some(normalizer);
+        self
+    }
+
+    /// Warn about identifiers `check` finds confusable, after normalizing.
+    pub fn with_confusable_check(mut self, check: ConfusableCheck) -> Self {
+        self.confusable = Some(check);
+        self
+    }
+
+    /// Set whether to warn about identifiers that mix scripts (see
+    /// [Script::mixed]), like a Latin `p` and `y` around a Cyrillic `а`.
+    pub fn with_mixed_script_check(mut self, yes: bool) -> Self {
+        self.mixed_script = yes;
+        self
+    }
+
+    /// Decode and normalize the identifier `s`, returning it with the
+    /// warnings found about it.
+    pub fn apply(&self, s: &str) -> Result<(String, Vec<String>), LexError> {
+        let decoded = match self.escapes {
+            true => decode_escapes(s)?,
+            false => s.to_owned(),
+        };
+        let ident = match self.normalizer {
+            Some(normalizer) => normalizer(&decoded),
+            None => decoded,
+        };
+        let mut warnings = Vec::new();
+        if self.mixed_script
+            && let Some(scripts) = Script::mixed(&ident)
+        {
+            let names: Vec<String> =
+                scripts.iter().map(Script::to_string).collect();
+            warnings.push(format!(
+                "Identifier `{ident}` mixes {} scripts!",
+                names.join(" and ")
+            ));
+        }
+        if let Some(warning) = self.confusable.and_then(|check| check(&ident)) {
+            warnings.push(warning);
+        }
+        Ok((ident, warnings))
+    }
+}
+
+impl<T, E: HandlerError> Lexer<T, E> {
+    /// Add a rule named "identifier" matching `pat`, passing the identifier
+    /// decoded and normalized by `policy` to `handler`. Warnings from
+    /// `policy` are reported by [Lexer::lex_with_warnings], and invalid
+    /// escapes are lex errors.
+    pub fn add_ident_rule(
+        &mut self,
+        pat: &str,
+        policy: IdentPolicy,
+        handler: impl Fn(String) -> T + Send + Sync + 'static,
+    ) {
+        self.add_rule_named("identifier", pat, move |re_match| {
+            match policy.apply(re_match.as_str()) {
+                Ok((ident, warnings)) if warnings.is_empty() => {
+                    LexResult::Token(handler(ident))
+                }
+                Ok((ident, warnings)) => {
+                    LexResult::Warn(handler(ident), warnings.join(" "))
+                }
+                Err(err) => LexResult::Error(err.into()),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ident::{IdentPolicy, Script, decode_escapes};
+    use crate::lex::Lexer;
+    use crate::span::Span;
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn test_ident_policy() -> anyhow::Result<()> {
+        assert!(decode_escapes(r"a\u{62}c")? == "abc");
+        assert!(decode_escapes(r"a\u{d800}").is_err());
+        assert!(decode_escapes(r"a\u{62").is_err());
+
+        assert!(Script::mixed("x_1").is_none());
+        assert!(Script::mixed("漢字かなカナ").is_none());
+        assert!(
+            Script::mixed("aβ")
+                == Some(BTreeSet::from([Script::Latin, Script::Greek]))
+        );
+
+        let policy = IdentPolicy::default()
+            .with_escapes(true)
+            .with_normalizer(|s| s.to_lowercase())
+            .with_confusable_check(|s| {
+                (s == "l1").then(|| "`l1` looks like `11`!".to_owned())
+            })
+            .with_mixed_script_check(true);
+        let mut lexer: Lexer<String> = Lexer::with_common_ignores();
+        lexer.add_ident_rule(r"(\w|\\u\{\w+\})+", policy, |ident| ident);
+
+        let (matches, warnings) = lexer.lex_with_warnings(r"X\u{3b1} L1 y")?;
+        let idents: Vec<String> =
+            matches.into_iter().map(|m| m.into_token()).collect();
+        assert!(idents == ["xα", "l1", "y"]);
+        assert!(warnings.len() == 2);
+        let message = "Identifier `xα` mixes Latin and Greek scripts!";
+        assert!(warnings[0].message == message);
+        assert!(warnings[1].span == Span::new(9, 11));
+        assert!(lexer.lex(r"\u{zz}").is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,259 @@
+//! Machine-readable output of [Diagnostic]s, for CI systems and editors that
+//! consume the output of `rlrl`-based compilers.
+//!
+//! [to_json] writes a JSON array with an object per diagnostic, in this
+//! schema:
+//!
+//! ```text
+//! {
+//!   "severity": "error" | "warning" | "note",
+//!   "code": string | null,
+//!   "message": string,
+//!   "file": string,
+//!   "span": { "start": number, "end": number },
+//!   "start": { "line": number, "column": number },
+//!   "end": { "line": number, "column": number },
+//!   "notes": [string],
+//!   "suggestions": [{
+//!     "message": string,
+//!     "span": { "start": number, "end": number },
+//!     "replacement": string,
+//!     "applicability":
+//!       "machine-applicable" | "maybe-incorrect" | "has-placeholders"
+//!   }]
+//! }
+//! ```
+//!
+//! Spans are byte offsets into the file. Lines and columns are 1-based, with
+//! columns counted in UTF-16 code units, as in SARIF and the Language Server
+//! Protocol. [to_sarif] writes the same diagnostics as a SARIF 2.1.0 log,
+//! which code scanning services accept.
+//!
+//! ```
+//! use rlrl::diagnostic::Diagnostic;
+//! use rlrl::json::to_json;
+//! use rlrl::source::SourceFile;
+//! use rlrl::span::Span;
+//!
+//! let source = SourceFile::new("a.cfg", "key value\n");
+//! let diagnostic = Diagnostic::error("expected `=`", Span::new(4, 9));
+//! let json = to_json(&[diagnostic], &source);
+//! assert!(json.starts_with(r#"[{"severity":"error","code":null,"#));
+//! ```
+
+use crate::diagnostic::{Applicability, Diagnostic, Severity, Suggestion};
+use crate::source::SourceFile;
+use crate::span::Span;
+
+/// The version of SARIF written by [to_sarif].
+pub const SARIF_VERSION: &str = "2.1.0";
+
+/// The schema of the SARIF logs written by [to_sarif].
+pub const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+/// Write `diagnostics`, about `source`, as a JSON array in the schema
+/// described in the [module docs](self).
+pub fn to_json(diagnostics: &[Diagnostic], source: &SourceFile) -> String {
+    let entries: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| diagnostic_json(diagnostic, source))
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+fn diagnostic_json(diagnostic: &Diagnostic, source: &SourceFile) -> String {
+    let code = diagnostic
+        .code
+        .as_deref()
+        .map_or("null".into(), json_string);
+    let notes: Vec<String> = diagnostic
+        .notes
+        .iter()
+        .map(|note| json_string(note))
+        .collect();
+    let suggestions: Vec<String> =
+        diagnostic.suggestions.iter().map(suggestion_json).collect();
+    let (start_line, start_col) = position(source, diagnostic.span.start);
+    let (end_line, end_col) = position(source, diagnostic.span.end);
+    format!(
+        "{{\"severity\":\"{}\",\"code\":{code},\"message\":{},\"file\":{},\
+         \"span\":{},\
+         \"start\":{{\"line\":{start_line},\"column\":{start_col}}},\
+         \"end\":{{\"line\":{end_line},\"column\":{end_col}}},\
+         \"notes\":[{}],\"suggestions\":[{}]}}",
+        diagnostic.severity,
+        json_string(&diagnostic.message),
+        json_string(source.name()),
+        span_json(diagnostic.span),
+        notes.join(","),
+        suggestions.join(",")
+    )
+}
+
+fn suggestion_json(suggestion: &Suggestion) -> String {
+    let applicability = match suggestion.applicability {
+        Applicability::MachineApplicable => "machine-applicable",
+        Applicability::MaybeIncorrect => "maybe-incorrect",
+        Applicability::HasPlaceholders => "has-placeholders",
+    };
+    format!(
+        "{{\"message\":{},\"span\":{},\"replacement\":{},\
+         \"applicability\":\"{applicability}\"}}",
+        json_string(&suggestion.message),
+        span_json(suggestion.span),
+        json_string(&suggestion.replacement)
+    )
+}
+
+fn span_json(span: Span) -> String {
+    format!("{{\"start\":{},\"end\":{}}}", span.start, span.end)
+}
+
+/// Write `diagnostics`, about `source`, as a SARIF log of a single run of
+/// the tool named `tool`. Each diagnostic's code, if any, is its rule ID,
+/// and its notes are appended to its message on lines of their own.
+pub fn to_sarif(
+    diagnostics: &[Diagnostic],
+    source: &SourceFile,
+    tool: &str,
+) -> String {
+    let results: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| sarif_result(diagnostic, source))
+        .collect();
+    format!(
+        "{{\"version\":\"{SARIF_VERSION}\",\"$schema\":\"{SARIF_SCHEMA}\",\
+         \"runs\":[{{\"tool\":{{\"driver\":{{\"name\":{}}}}},\
+         \"results\":[{}]}}]}}",
+        json_string(tool),
+        results.join(",")
+    )
+}
+
+fn sarif_result(diagnostic: &Diagnostic, source: &SourceFile) -> String {
+    let level = match diagnostic.severity {
+        Severity::Error => "error",
+        Severity::Warning => "warning",
+        Severity::Note => "note",
+    };
+    let rule = match &diagnostic.code {
+        Some(code) => format!("\"ruleId\":{},", json_string(code)),
+        None => String::new(),
+    };
+    let mut message = diagnostic.message.clone();
+    for note in &diagnostic.notes {
+        message.push_str(&format!("\n{}: {note}", Severity::Note));
+    }
+    let (start_line, start_col) = position(source, diagnostic.span.start);
+    let (end_line, end_col) = position(source, diagnostic.span.end);
+    format!(
+        "{{{rule}\"level\":\"{level}\",\"message\":{{\"text\":{}}},\
+         \"locations\":[{{\"physicalLocation\":{{\
+         \"artifactLocation\":{{\"uri\":{}}},\
+         \"region\":{{\"startLine\":{start_line},\"startColumn\":{start_col},\
+         \"endLine\":{end_line},\"endColumn\":{end_col}}}}}}}]}}",
+        json_string(&message),
+        json_string(source.name())
+    )
+}
+
+/// Get the 1-based (line, column) of the byte `offset` of `source`, counting
+/// columns in UTF-16 code units.
+fn position(source: &SourceFile, offset: usize) -> (usize, usize) {
+    let (line, col) = source.utf16_line_col(offset);
+    (line + 1, col + 1)
+}
+
+/// Quote and escape `s` as a JSON string, for tools writing JSON of their
+/// own alongside diagnostics.
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => {
+                out.push_str(&format!("\\u{:04x}", c as u32))
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{Applicability, Diagnostic, Suggestion};
+    use crate::json::{json_string, to_json, to_sarif};
+    use crate::source::SourceFile;
+    use crate::span::Span;
+
+    #[test]
+    fn test_json_string() {
+        assert!(json_string("a\"b\\\n\u{1}") == r#""a\"b\\\n\u0001""#);
+    }
+
+    #[test]
+    fn test_to_json() {
+        let source = SourceFile::new("a.cfg", "x = 1\né y\n");
+        let diagnostics = [
+            Diagnostic::error("expected `=`", Span::new(9, 10))
+                .with_code("E0001")
+                .with_note("entries are `key = value`")
+                .with_suggestion(Suggestion::new(
+                    "insert `=`",
+                    Span::new(9, 9),
+                    "= ",
+                    Applicability::MachineApplicable,
+                )),
+            Diagnostic::warning("unused", Span::new(0, 1)),
+        ];
+
+        assert!(
+            to_json(&diagnostics, &source)
+                == "[{\"severity\":\"error\",\"code\":\"E0001\",\
+                    \"message\":\"expected `=`\",\"file\":\"a.cfg\",\
+                    \"span\":{\"start\":9,\"end\":10},\
+                    \"start\":{\"line\":2,\"column\":3},\
+                    \"end\":{\"line\":2,\"column\":4},\
+                    \"notes\":[\"entries are `key = value`\"],\
+                    \"suggestions\":[{\"message\":\"insert `=`\",\
+                    \"span\":{\"start\":9,\"end\":9},\"replacement\":\"= \",\
+                    \"applicability\":\"machine-applicable\"}]},\
+                    {\"severity\":\"warning\",\"code\":null,\
+                    \"message\":\"unused\",\"file\":\"a.cfg\",\
+                    \"span\":{\"start\":0,\"end\":1},\
+                    \"start\":{\"line\":1,\"column\":1},\
+                    \"end\":{\"line\":1,\"column\":2},\
+                    \"notes\":[],\"suggestions\":[]}]"
+        );
+        assert!(to_json(&[], &source) == "[]");
+    }
+
+    #[test]
+    fn test_to_sarif() {
+        let source = SourceFile::new("a.cfg", "x = 1\né y\n");
+        let diagnostics = [Diagnostic::error("expected `=`", Span::new(9, 10))
+            .with_code("E0001")
+            .with_note("entries are `key = value`")];
+
+        assert!(
+            to_sarif(&diagnostics, &source, "cfgc")
+                == "{\"version\":\"2.1.0\",\
+                    \"$schema\":\
+                    \"https://json.schemastore.org/sarif-2.1.0.json\",\
+                    \"runs\":[{\"tool\":{\"driver\":{\"name\":\"cfgc\"}},\
+                    \"results\":[{\"ruleId\":\"E0001\",\"level\":\"error\",\
+                    \"message\":{\"text\":\"expected `=`\\n\
+                    note: entries are `key = value`\"},\
+                    \"locations\":[{\"physicalLocation\":{\
+                    \"artifactLocation\":{\"uri\":\"a.cfg\"},\
+                    \"region\":{\"startLine\":2,\"startColumn\":3,\
+                    \"endLine\":2,\"endColumn\":4}}}]}]}]}"
+        );
+    }
+}
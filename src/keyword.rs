@@ -0,0 +1,92 @@
+use crate::parse::{ParseError, Result, TokenQueue};
+use crate::span::Spanned;
+
+const NOT_SOFT_KEYWORD_MSG: &str = "Token isn't the expected soft keyword!";
+
+/// Implemented by token types with an identifier variant, so identifiers can
+/// be treated as keywords in specific parser contexts.
+///
+/// Lexing a word like `async` as a keyword everywhere stops programs from
+/// using it as an identifier; lexing it as an identifier and recognizing it
+/// with [TokenQueue::consume_soft_keyword] where the grammar expects it
+/// doesn't.
+pub trait Identifier {
+    /// Borrow the identifier's text, or `None` if the token isn't an
+    /// identifier.
+    fn ident(&self) -> Option<&str>;
+}
+
+impl<T: Identifier> Identifier for Spanned<T> {
+    fn ident(&self) -> Option<&str> {
+        self.value.ident()
+    }
+}
+
+impl<T: Identifier> TokenQueue<T> {
+    /// Return true if the front token is an identifier spelled `keyword`.
+    pub fn at_soft_keyword(&self, keyword: &str) -> bool {
+        self.peek()
+            .is_ok_and(|token| token.ident() == Some(keyword))
+    }
+
+    /// Consume the front token if it's an identifier spelled `keyword`,
+    /// otherwise return an error.
+    pub fn consume_soft_keyword(&mut self, keyword: &str) -> Result<()> {
+        if !self.at_soft_keyword(keyword) {
+            return Err(ParseError::at(self.get_idx(), NOT_SOFT_KEYWORD_MSG));
+        }
+        self.increment()
+    }
+
+    /// Consume the front token if it's an identifier, returning its text,
+    /// otherwise return an error.
+    pub fn consume_ident(&mut self) -> Result<&str> {
+        if self.peek()?.ident().is_none() {
+            return Err(ParseError::at(
+                self.get_idx(),
+                "Expected an identifier!",
+            ));
+        }
+        self.increment()?;
+        Ok(self.prev()?.ident().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::keyword::Identifier;
+    use crate::parse::TokenQueue;
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Ident(String),
+        LParen,
+    }
+
+    impl Identifier for Token {
+        fn ident(&self) -> Option<&str> {
+            match self {
+                Self::Ident(name) => Some(name),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_soft_keyword() -> anyhow::Result<()> {
+        // `async async(` declares an async function named `async`
+        let mut tq = TokenQueue::from(vec![
+            Token::Ident("async".to_owned()),
+            Token::Ident("async".to_owned()),
+            Token::LParen,
+        ]);
+
+        tq.consume_soft_keyword("async")?;
+        assert!(!tq.at_soft_keyword("fn"));
+        assert!(tq.consume_ident()? == "async");
+        assert!(tq.consume_soft_keyword("async").is_err());
+        assert!(tq.consume_ident().is_err());
+
+        Ok(())
+    }
+}
@@ -1,137 +1,1660 @@
-use anyhow;
+use crate::diagnostic::Diagnostic;
+use crate::sink::ErrorSink;
+use crate::span::{Span, Spanned};
+use crate::version::{Version, VersionRange};
 use regex::Match;
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
+use std::any::Any;
+use std::collections::{BTreeSet, HashSet};
+use std::error::Error;
+use std::fmt::Display;
+use std::num::{ParseFloatError, ParseIntError};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Represents an error that occurred while lexing.
+#[derive(Debug)]
+pub enum LexError {
+    /// No rule matched the input at byte `pos`.
+    Unmatched { pos: usize },
+    /// The handler of the rule named `rule` failed on the match starting at
+    /// byte `pos`.
+    InRule {
+        rule: String,
+        pos: usize,
+        source: Box<LexError>,
+    },
+    /// A limit set with [Lexer::with_limits] was exceeded at byte `pos`.
+    LimitExceeded {
+        limit: Limit,
+        max: usize,
+        pos: usize,
+    },
+    /// An error returned by a rule's handler.
+    Custom(Box<dyn Error + Send + Sync>),
+}
+
+impl LexError {
+    /// Create an error for a handler to return, from an error or message.
+    pub fn custom(err: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self::Custom(err.into())
+    }
+
+    /// Get the byte position of the input the error occurred at, if known.
+    pub fn pos(&self) -> Option<usize> {
+        match self {
+            Self::Unmatched { pos }
+            | Self::InRule { pos, .. }
+            | Self::LimitExceeded { pos, .. } => Some(*pos),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
+impl From<ParseIntError> for LexError {
+    fn from(value: ParseIntError) -> Self {
+        Self::custom(value)
+    }
+}
+
+impl From<ParseFloatError> for LexError {
+    fn from(value: ParseFloatError) -> Self {
+        Self::custom(value)
+    }
+}
+
+impl From<anyhow::Error> for LexError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::Custom(value.into())
+    }
+}
+
+impl Display for LexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unmatched { pos } => {
+                write!(f, "Unmatched input at position {pos}!")
+            }
+            Self::InRule { rule, pos, .. } => {
+                write!(f, "Lexer rule {rule} failed at position {pos}!")
+            }
+            Self::LimitExceeded { limit, max, pos } => {
+                write!(f, "Exceeded the {limit} of {max} at position {pos}!")
+            }
+            Self::Custom(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for LexError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::InRule { source, .. } => Some(source.as_ref()),
+            Self::Custom(err) => err.source(),
+            Self::Unmatched { .. } | Self::LimitExceeded { .. } => None,
+        }
+    }
+}
+
+/// A limit on the input a [Lexer] accepts, in a [LexError::LimitExceeded].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The length of the input in bytes.
+    InputLength,
+    /// The length in bytes of a match, whether lexed as a token or ignored.
+    MatchLength,
+    /// The number of tokens lexed.
+    Tokens,
+}
+
+impl Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InputLength => write!(f, "maximum input length"),
+            Self::MatchLength => write!(f, "maximum match length"),
+            Self::Tokens => write!(f, "maximum number of tokens"),
+        }
+    }
+}
+
+/// Limits on the input a [Lexer] accepts, so services lexing untrusted input
+/// can bound the memory used. Lexing fails with [LexError::LimitExceeded]
+/// when one is exceeded. The default sets no limits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LexLimits {
+    max_input_len: Option<usize>,
+    max_match_len: Option<usize>,
+    max_tokens: Option<usize>,
+}
+
+impl LexLimits {
+    /// Reject inputs longer than `max` bytes, before lexing them.
+    pub fn with_max_input_len(mut self, max: usize) -> Self {
+        self.max_input_len = Some(max);
+        self
+    }
+
+    /// Reject matches longer than `max` bytes, including ignored matches
+    /// like comments.
+    pub fn with_max_match_len(mut self, max: usize) -> Self {
+        self.max_match_len = Some(max);
+        self
+    }
+
+    /// Reject inputs lexing to more than `max` tokens.
+    pub fn with_max_tokens(mut self, max: usize) -> Self {
+        self.max_tokens = Some(max);
+        self
+    }
+
+    /// Return an error if `len` exceeds the limit `limit` at byte `pos`.
+    fn check(
+        &self,
+        limit: Limit,
+        len: usize,
+        pos: usize,
+    ) -> Result<(), LexError> {
+        let max = match limit {
+            Limit::InputLength => self.max_input_len,
+            Limit::MatchLength => self.max_match_len,
+            Limit::Tokens => self.max_tokens,
+        };
+        match max {
+            Some(max) if len > max => {
+                Err(LexError::LimitExceeded { limit, max, pos })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Implemented by the error types a [Lexer] can return: anything a
+/// [LexError] converts into.
+///
+/// Typed-error users implement `From<LexError>` for their error type, plus
+/// this trait with an empty body, and their handlers' errors are returned
+/// from [Lexer::lex] without boxing.
+pub trait HandlerError: From<LexError> {
+    /// Add context saying the error came from the handler of the rule named
+    /// `rule`, on the match starting at byte `pos`. Returns the error
+    /// unchanged by default.
+    fn in_rule(self, _rule: &str, _pos: usize) -> Self {
+        self
+    }
+
+    /// Get the byte position of the input the error occurred at, if known,
+    /// to report it at. Returns `None` by default.
+    fn pos(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl HandlerError for LexError {
+    fn in_rule(self, rule: &str, pos: usize) -> Self {
+        Self::InRule {
+            rule: rule.to_owned(),
+            pos,
+            source: Box::new(self),
+        }
+    }
+
+    fn pos(&self) -> Option<usize> {
+        LexError::pos(self)
+    }
+}
+
+impl HandlerError for anyhow::Error {
+    fn in_rule(self, rule: &str, pos: usize) -> Self {
+        self.context(format!("Lexer rule {rule} failed at position {pos}!"))
+    }
+
+    fn pos(&self) -> Option<usize> {
+        self.downcast_ref::<LexError>().and_then(LexError::pos)
+    }
+}
 
 /// Represents possible outcomes when trying to lex a token of type `T`.
-pub enum LexResult<T> {
+pub enum LexResult<T, E = LexError> {
     /// A token was successfully lexed from the input
     Token(T),
     /// The input was ignored
     Ignore,
     /// An error occurred lex the token
-    Error(anyhow::Error),
+    Error(E),
+    /// A token was lexed, with a warning about it, like a deprecated
+    /// spelling or a literal that had to be clamped. The warning is reported
+    /// by [Lexer::lex_with_warnings] at the span of the match, and lexing
+    /// continues.
+    Warn(T, String),
+}
+
+impl<T, E> From<Result<T, E>> for LexResult<T, E> {
+    fn from(value: Result<T, E>) -> Self {
+        match value {
+            Ok(token) => Self::Token(token),
+            Err(err) => Self::Error(err),
+        }
+    }
 }
 
 /// Function that accepts a [regex::Match] and tries to lex a token of type `T`
-/// from it.
-pub type MatchHandler<T> = fn(Match) -> LexResult<T>;
+/// from it. Rules can be added with any function or closure, including one
+/// that captures state, as long as it can be shared between threads.
+pub type MatchHandler<T, E = LexError> =
+    Arc<dyn Fn(Match) -> LexResult<T, E> + Send + Sync>;
+
+/// Function like a [MatchHandler] that returns a `Result`, so it can use `?`.
+/// Returning `Ok(None)` ignores the input.
+pub type TryHandler<T, E = LexError> =
+    Arc<dyn Fn(Match) -> Result<Option<T>, E> + Send + Sync>;
+
+/// Function like a [MatchHandler] that also receives the context passed to
+/// [Lexer::lex_with], type-erased. See [Lexer::add_ctx_rule].
+type CtxHandler<T, E> =
+    Arc<dyn Fn(Match, &mut dyn Any) -> LexResult<T, E> + Send + Sync>;
+
+enum Handler<T, E> {
+    Match(MatchHandler<T, E>),
+    Try(TryHandler<T, E>),
+    Ctx(CtxHandler<T, E>),
+}
+
+impl<T, E> Clone for Handler<T, E> {
+    fn clone(&self) -> Self {
+        match self {
+            Self::Match(handler) => Self::Match(handler.clone()),
+            Self::Try(handler) => Self::Try(handler.clone()),
+            Self::Ctx(handler) => Self::Ctx(handler.clone()),
+        }
+    }
+}
+
+/// Implemented by token types to list the kinds of token they have, usually
+/// the names of an enum's variants, so [Lexer::check_kinds] can check a
+/// lexer produces each of them.
+///
+/// ```
+/// use rlrl::lex::TokenKinds;
+///
+/// enum Token {
+///     Num(i64),
+///     Plus,
+/// }
+///
+/// impl TokenKinds for Token {
+///     const KINDS: &'static [&'static str] = &["Num", "Plus"];
+/// }
+/// ```
+pub trait TokenKinds {
+    /// The names of the kinds of token.
+    const KINDS: &'static [&'static str];
+}
+
+/// Options for a rule added with [Lexer::add_rule_with_options]: its name,
+/// and regex flags set without inline syntax like `(?i)`. The default gives
+/// no name and sets no flags.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RuleOptions {
+    name: Option<String>,
+    case_insensitive: bool,
+    multi_line: bool,
+    dot_matches_newline: bool,
+    versions: VersionRange,
+    kinds: Vec<String>,
+    group: Option<String>,
+}
+
+impl RuleOptions {
+    /// Name the rule `name`, as with [Lexer::add_rule_named].
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = Some(name.to_owned());
+        self
+    }
+
+    /// Set whether letters match regardless of case.
+    pub fn with_case_insensitive(mut self, yes: bool) -> Self {
+        self.case_insensitive = yes;
+        self
+    }
+
+    /// Set whether `^` and `$` match at the start and end of lines, rather
+    /// than only at the start and end of the input.
+    pub fn with_multi_line(mut self, yes: bool) -> Self {
+        self.multi_line = yes;
+        self
+    }
+
+    /// Set whether `.` matches `\n`.
+    pub fn with_dot_matches_newline(mut self, yes: bool) -> Self {
+        self.dot_matches_newline = yes;
+        self
+    }
+
+    /// Make the rule part of the language versions in `versions` only. See
+    /// [Lexer::for_version].
+    pub fn with_versions(mut self, versions: VersionRange) -> Self {
+        self.versions = versions;
+        self
+    }
+
+    /// Put the rule in the group named `group`, like "legacy", so it can be
+    /// left out when lexing with [Lexer::lex_matches_without_groups].
+    pub fn with_group(mut self, group: &str) -> Self {
+        self.group = Some(group.to_owned());
+        self
+    }
+
+    /// Declare that the rule produces tokens of the kind `kind`, for
+    /// [Lexer::check_kinds]. Call it once for each kind a rule can produce.
+    pub fn with_kind(mut self, kind: &str) -> Self {
+        self.kinds.push(kind.to_owned());
+        self
+    }
+
+    fn build(&self, pat: &str) -> crate::Result<Regex> {
+        RegexBuilder::new(pat)
+            .case_insensitive(self.case_insensitive)
+            .multi_line(self.multi_line)
+            .dot_matches_new_line(self.dot_matches_newline)
+            .build()
+            .map_err(|err| {
+                crate::Error::build(format!("Invalid regex /{pat}/: {err}"))
+            })
+    }
+}
 
 /// Represents a rule in a lexer that lexes tokens of type `T`.
-pub struct LexerRule<T> {
+pub struct LexerRule<T, E = LexError> {
+    name: Option<String>,
     pat: Regex,
-    handler: MatchHandler<T>,
+    handler: Handler<T, E>,
+    versions: VersionRange,
+    /// The token kinds declared with [RuleOptions::with_kind].
+    kinds: Vec<String>,
+    group: Option<String>,
+}
+
+impl<T, E> Clone for LexerRule<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            name: self.name.clone(),
+            pat: self.pat.clone(),
+            handler: self.handler.clone(),
+            versions: self.versions.clone(),
+            kinds: self.kinds.clone(),
+            group: self.group.clone(),
+        }
+    }
+}
+
+impl<T, E> LexerRule<T, E> {
+    fn info(&self, priority: usize) -> RuleInfo<'_> {
+        RuleInfo {
+            name: self.name.as_deref(),
+            pattern: self.pat.as_str(),
+            priority,
+        }
+    }
+
+    /// Get the rule's name, or its regex if it's unnamed, for messages.
+    #[cfg(feature = "tracing")]
+    fn label(&self) -> String {
+        self.info(0).label()
+    }
+
+    fn handle(&self, re_match: Match, ctx: &mut dyn Any) -> LexResult<T, E> {
+        match &self.handler {
+            Handler::Match(handler) => handler(re_match),
+            Handler::Ctx(handler) => handler(re_match, ctx),
+            Handler::Try(handler) => match handler(re_match) {
+                Ok(Some(token)) => LexResult::Token(token),
+                Ok(None) => LexResult::Ignore,
+                Err(err) => LexResult::Error(err),
+            },
+        }
+    }
+}
+
+/// Describes a rule in a [Lexer], as returned by [Lexer::rules].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleInfo<'a> {
+    /// The name given to the rule with [Lexer::add_rule_named], if any.
+    pub name: Option<&'a str>,
+    /// The source of the rule's regex.
+    pub pattern: &'a str,
+    /// The index the rule was added at. When two rules match the same
+    /// length of input at the same position, the rule with the lower
+    /// priority index wins.
+    pub priority: usize,
+}
+
+impl Display for RuleInfo<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:>3}  ", self.priority)?;
+        if let Some(name) = self.name {
+            write!(f, "{name}  ")?;
+        }
+        write!(f, "/{}/", self.pattern)
+    }
+}
+
+impl RuleInfo<'_> {
+    fn label(&self) -> String {
+        match self.name {
+            Some(name) => name.to_owned(),
+            None => format!("/{}/", self.pattern),
+        }
+    }
+}
+
+/// A match of a single rule against the input, as returned by
+/// [Lexer::all_matches].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleMatch<'a> {
+    /// The index of the rule that matched, as in [RuleInfo::priority].
+    pub rule: usize,
+    pub span: Span,
+    pub text: &'a str,
+    /// True if the match would produce a token when lexing, rather than
+    /// losing to an overlapping match.
+    pub won: bool,
 }
 
-impl<T> LexerRule<T> {
-    fn handle(&self, re_match: Match) -> LexResult<T> {
-        (self.handler)(re_match)
-    }
-}
+/// Why a rule's match lost a region of the input, in an [Explanation].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Defeat {
+    /// The winning match started at the same position and was longer.
+    Shorter,
+    /// The winning match started at the same position and was as long, but
+    /// belongs to an earlier rule.
+    Priority,
+    /// The match started inside the winning match, so was never considered.
+    Overlapped,
+}
+
+impl Display for Defeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shorter => write!(f, "a longer match won"),
+            Self::Priority => write!(f, "a higher priority match won"),
+            Self::Overlapped => write!(f, "it started inside the winner"),
+        }
+    }
+}
+
+/// A match that lost a region of the input, in an [Explanation].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rival<'a> {
+    pub rule: RuleInfo<'a>,
+    pub span: Span,
+    pub defeat: Defeat,
+}
+
+/// Describes how a region of the input would be tokenized, as returned by
+/// [Lexer::explain].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation<'a> {
+    pub span: Span,
+    pub text: &'a str,
+    /// The rule whose match won the region, or `None` if no rule matched it.
+    pub winner: Option<RuleInfo<'a>>,
+    /// Matches of other rules overlapping the region, which lost.
+    pub rivals: Vec<Rival<'a>>,
+}
+
+impl Display for Explanation<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:?}: ", self.span, self.text)?;
+        match &self.winner {
+            Some(rule) => write!(f, "matched by {}", rule.label())?,
+            None => write!(f, "unmatched")?,
+        }
+        for rival in &self.rivals {
+            write!(
+                f,
+                "\n  {} matched {} but lost: {}",
+                rival.rule.label(),
+                rival.span,
+                rival.defeat
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// A likely mistake in a rule of a [Lexer], found by [Lexer::lint].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind<'a> {
+    /// The rule can match the empty string. Empty matches are never lexed,
+    /// so the rule is likely missing a character or has a `*` meant as `+`.
+    EmptyMatch,
+    /// The rule starts with a repeated `.`, so wherever it matches it runs to
+    /// the end of the line and wins by maximal munch.
+    LeadingWildcard,
+    /// The rule never wins a match: everything it matches is matched at
+    /// least as long by the earlier rule given, like a keyword added after
+    /// the identifier rule.
+    Shadowed(RuleInfo<'a>),
+    /// The rule repeats a group that itself contains an unbounded
+    /// repetition, like `(a+)*`. The `regex` crate never backtracks, so it's
+    /// not catastrophic here, but it's redundant and would be in a
+    /// backtracking engine sharing the pattern.
+    NestedRepetition,
+}
+
+impl Display for LintKind<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyMatch => write!(f, "can match the empty string"),
+            Self::LeadingWildcard => {
+                write!(f, "starts with a repeated `.`, matching to line end")
+            }
+            Self::Shadowed(by) => write!(f, "is shadowed by {}", by.label()),
+            Self::NestedRepetition => write!(f, "has a nested repetition"),
+        }
+    }
+}
+
+/// A likely mistake in a rule of a [Lexer], as returned by [Lexer::lint].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lint<'a> {
+    pub rule: RuleInfo<'a>,
+    pub kind: LintKind<'a>,
+}
+
+impl Display for Lint<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.rule.label(), self.kind)
+    }
+}
+
+/// Strip a leading flag group like `(?i)` from the pattern `pat`.
+fn strip_flags(pat: &str) -> &str {
+    pat.strip_prefix("(?")
+        .and_then(|rest| rest.split_once(')'))
+        .filter(|(flags, _)| flags.chars().all(|c| c.is_alphabetic()))
+        .map_or(pat, |(_, rest)| rest)
+}
+
+/// Get the string the pattern `pat` matches if it's a literal, ignoring
+/// word boundaries at either end like those of keyword rules.
+fn literal(pat: &str) -> Option<String> {
+    let pat = pat.strip_prefix(r"\b").unwrap_or(pat);
+    let pat = pat.strip_suffix(r"\b").unwrap_or(pat);
+    let mut literal = String::new();
+    let mut chars = pat.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                c if c.is_alphanumeric() => return None,
+                c => literal.push(c),
+            },
+            '.' | '+' | '*' | '?' | '(' | ')' | '|' | '[' | ']' | '{' | '}'
+            | '^' | '$' => return None,
+            c => literal.push(c),
+        }
+    }
+    Some(literal)
+}
+
+/// Return true if the pattern `pat` repeats a group containing an unbounded
+/// repetition without bound, like `(a+)*`.
+fn has_nested_repetition(pat: &str) -> bool {
+    // for each open group, whether it contains an unbounded repetition
+    let mut groups: Vec<bool> = Vec::new();
+    // whether the previous item was a group containing one
+    let mut repeating_group = false;
+    let mut class_depth = 0;
+    let mut chars = pat.chars();
+    while let Some(c) = chars.next() {
+        let after_repeating_group = std::mem::take(&mut repeating_group);
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '[' => class_depth += 1,
+            ']' if class_depth > 0 => class_depth -= 1,
+            _ if class_depth > 0 => {}
+            '(' => groups.push(false),
+            ')' => {
+                let repeats = groups.pop().unwrap_or(false);
+                if let Some(outer) = groups.last_mut() {
+                    *outer |= repeats;
+                }
+                repeating_group = repeats;
+            }
+            '*' | '+' | '{' => {
+                let unbounded = c != '{'
+                    || chars.by_ref().take_while(|c| *c != '}').last()
+                        == Some(',');
+                if unbounded && after_repeating_group {
+                    return true;
+                }
+                if let Some(group) = groups.last_mut() {
+                    *group |= unbounded;
+                }
+            }
+            _ => {}
+        }
+    }
+    false
+}
+
+/// Statistics about one rule of a [Lexer], from [Lexer::lex_with_stats].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RuleStats<'a> {
+    pub rule: RuleInfo<'a>,
+    /// The number of matches the rule won, including ignored ones.
+    pub matches: usize,
+    /// The total length in bytes of the matches the rule won.
+    pub bytes: usize,
+    /// The time spent searching the input with the rule's regex and running
+    /// its handler.
+    pub time: Duration,
+}
+
+/// Statistics about lexing an input, from [Lexer::lex_with_stats].
+///
+/// Displays as a table of the rules, slowest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexStats<'a> {
+    /// The statistics of each rule, in priority order.
+    pub rules: Vec<RuleStats<'a>>,
+    /// The time spent lexing the whole input.
+    pub total: Duration,
+}
+
+impl Display for LexStats<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut rules: Vec<&RuleStats> = self.rules.iter().collect();
+        rules.sort_by_key(|stats| std::cmp::Reverse(stats.time));
+        writeln!(f, "{:>12}  {:>8}  {:>10}  rule", "time", "matches", "bytes")?;
+        for stats in rules {
+            writeln!(
+                f,
+                "{:>12}  {:>8}  {:>10}  {}",
+                format!("{:.2?}", stats.time),
+                stats.matches,
+                stats.bytes,
+                stats.rule.label()
+            )?;
+        }
+        write!(f, "{:>12}  total", format!("{:.2?}", self.total))
+    }
+}
+
+/// Represents a match discovered during lexing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LexerMatch<T> {
+    token: T,
+    span: Span,
+}
+
+impl<T> LexerMatch<T> {
+    /// Borrow the token that was lexed.
+    pub fn token(&self) -> &T {
+        &self.token
+    }
+
+    /// Get the span of the input the token was lexed from.
+    pub fn span(&self) -> Span {
+        self.span
+    }
+
+    /// Take the token that was lexed, discarding its span.
+    pub fn into_token(self) -> T {
+        self.token
+    }
+}
+
+/// Tokens lexed by [Lexer::lex_owned], together with the source they were
+/// lexed from, so the text of each token can be borrowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexed<T> {
+    source: String,
+    matches: Vec<LexerMatch<T>>,
+}
+
+impl<T> Lexed<T> {
+    /// Borrow the source the tokens were lexed from.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Borrow the tokens and their spans.
+    pub fn matches(&self) -> &[LexerMatch<T>] {
+        &self.matches
+    }
+
+    /// Borrow the text of the token at index `idx`.
+    pub fn text(&self, idx: usize) -> Option<&str> {
+        let span = self.matches.get(idx)?.span;
+        Some(&self.source[span.start..span.end])
+    }
+
+    /// Iterate over the tokens with their text.
+    pub fn iter(&self) -> impl Iterator<Item = (&T, &str)> {
+        self.matches.iter().map(|lexer_match| {
+            let span = lexer_match.span;
+            (&lexer_match.token, &self.source[span.start..span.end])
+        })
+    }
+
+    /// Take the source and the tokens.
+    pub fn into_parts(self) -> (String, Vec<LexerMatch<T>>) {
+        (self.source, self.matches)
+    }
+}
+
+impl<T> From<LexerMatch<T>> for Spanned<T> {
+    fn from(value: LexerMatch<T>) -> Self {
+        Spanned::new(value.token, value.span)
+    }
+}
+
+/// Represents a lexer that lexes tokens of type `T`, with handlers that
+/// return errors of type `E`.
+///
+/// # Maximal munch
+///
+/// The input is tokenized from left to right. At each position, every rule's
+/// regex is matched starting exactly there, and the longest non-empty match
+/// wins; if several are equally long, the rule added first wins. Lexing then
+/// continues from the end of the winning match, so a match that starts inside
+/// it is never considered. If no rule matches at a position, lexing fails
+/// with [LexError::Unmatched].
+///
+/// Tokenization is a function of the input and the rules in priority order
+/// only: it doesn't depend on the order rules are tried in internally, on
+/// earlier calls, or on the thread lexing. [Lexer::verify_determinism] checks
+/// this for a lexer against sample inputs.
+///
+/// # Empty matches
+///
+/// Rules may have patterns that can match the empty string, like `a*`, but
+/// their empty matches are skipped: they never win a position, never run a
+/// handler, and never produce a token, so lexing always makes progress. A
+/// rule like `a*` only lexes non-empty runs of `a`, and input where the
+/// only match is empty fails with [LexError::Unmatched]. [Lexer::lint]
+/// flags such rules, as the `*` is usually meant to be a `+`.
+///
+/// A lexer is `Clone`, `Send` and `Sync` for any `T` and `E`, so one built
+/// once can be stored in a `static` [std::sync::OnceLock] and shared across
+/// threads.
+pub struct Lexer<T, E = LexError> {
+    rules: Vec<LexerRule<T, E>>,
+    limits: LexLimits,
+    skip_bom: bool,
+    skip_shebang: bool,
+}
+
+impl<T, E> Clone for Lexer<T, E> {
+    fn clone(&self) -> Self {
+        Self {
+            rules: self.rules.clone(),
+            limits: self.limits,
+            skip_bom: self.skip_bom,
+            skip_shebang: self.skip_shebang,
+        }
+    }
+}
+
+impl<T, E> Default for Lexer<T, E> {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            limits: LexLimits::default(),
+            skip_bom: false,
+            skip_shebang: false,
+        }
+    }
+}
+
+impl<T> Lexer<T> {
+    /// Create a lexer with no rules, returning [LexError]s. Use
+    /// [Lexer::default] to create a lexer with another error type.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<T, E> Lexer<T, E> {
+    /// Create a lexer that ignores whitespace and `#` line comments, for
+    /// config files and shell-like languages. Rules added after these have
+    /// lower priority.
+    pub fn with_common_ignores() -> Self {
+        let mut lexer = Self::default();
+        lexer.add_rule_named("whitespace", r"\s+", |_| LexResult::Ignore);
+        lexer.add_rule_named("comment", r"#[^\n]*", |_| LexResult::Ignore);
+        lexer
+    }
+
+    /// Create a lexer that ignores whitespace, `//` line comments and `/* */`
+    /// block comments, for languages with C-like syntax. Rules added after
+    /// these have lower priority.
+    pub fn c_style() -> Self {
+        let mut lexer = Self::default();
+        lexer.add_rule_named("whitespace", r"\s+", |_| LexResult::Ignore);
+        lexer.add_rule_named("comment", r"//[^\n]*", |_| LexResult::Ignore);
+        lexer.add_rule_named("block comment", r"/\*(?s:.)*?\*/", |_| {
+            LexResult::Ignore
+        });
+        lexer
+    }
+
+    pub fn add_rule(
+        &mut self,
+        pat: &str,
+        handler: impl Fn(Match) -> LexResult<T, E> + Send + Sync + 'static,
+    ) {
+        self.rules.push(LexerRule {
+            name: None,
+            pat: Regex::new(pat)
+                .expect("Invalid regexp passed to Lexer::add_rule"),
+            handler: Handler::Match(Arc::new(handler)),
+            versions: VersionRange::default(),
+            kinds: Vec::new(),
+            group: None,
+        });
+    }
+
+    /// Add a rule like [Lexer::add_rule], with a handler that returns a
+    /// `Result`. Handlers can use `?` on any error that converts into `E`;
+    /// [LexError] converts from number parsing errors and [anyhow::Error].
+    pub fn add_try_rule(
+        &mut self,
+        pat: &str,
+        handler: impl Fn(Match) -> Result<Option<T>, E> + Send + Sync + 'static,
+    ) {
+        self.rules.push(LexerRule {
+            name: None,
+            pat: Regex::new(pat)
+                .expect("Invalid regexp passed to Lexer::add_try_rule"),
+            handler: Handler::Try(Arc::new(handler)),
+            versions: VersionRange::default(),
+            kinds: Vec::new(),
+            group: None,
+        });
+    }
+
+    /// Add a rule like [Lexer::add_rule], naming it `name`. The name is used
+    /// instead of the rule's regex in the rule table and tracing output, and
+    /// errors returned by the rule's handler are given it as context.
+    pub fn add_rule_named(
+        &mut self,
+        name: &str,
+        pat: &str,
+        handler: impl Fn(Match) -> LexResult<T, E> + Send + Sync + 'static,
+    ) {
+        self.rules.push(LexerRule {
+            name: Some(name.to_owned()),
+            pat: Regex::new(pat)
+                .expect("Invalid regexp passed to Lexer::add_rule_named"),
+            handler: Handler::Match(Arc::new(handler)),
+            versions: VersionRange::default(),
+            kinds: Vec::new(),
+            group: None,
+        });
+    }
+
+    /// Add a rule matching the word `keyword` exactly, anchored with word
+    /// boundaries so it doesn't match inside a longer word: a `type` keyword
+    /// rule doesn't match the start of `typename`. `keyword` is matched
+    /// literally, not as a regex.
+    pub fn add_keyword_rule(
+        &mut self,
+        keyword: &str,
+        handler: impl Fn(Match) -> LexResult<T, E> + Send + Sync + 'static,
+    ) {
+        self.rules.push(LexerRule {
+            name: None,
+            pat: Regex::new(&format!(r"\b{}\b", regex::escape(keyword)))
+                .expect("Invalid keyword passed to Lexer::add_keyword_rule"),
+            handler: Handler::Match(Arc::new(handler)),
+            versions: VersionRange::default(),
+            kinds: Vec::new(),
+            group: None,
+        });
+    }
+
+    /// Add a rule like [Lexer::add_rule], with the name and regex flags in
+    /// `options`. The regex is compiled when the rule is added, so an invalid
+    /// pattern is reported here instead of panicking.
+    pub fn add_rule_with_options(
+        &mut self,
+        pat: &str,
+        options: RuleOptions,
+        handler: impl Fn(Match) -> LexResult<T, E> + Send + Sync + 'static,
+    ) -> crate::Result<()> {
+        self.rules.push(LexerRule {
+            pat: options.build(pat)?,
+            name: options.name,
+            handler: Handler::Match(Arc::new(handler)),
+            versions: options.versions,
+            kinds: options.kinds,
+            group: options.group,
+        });
+        Ok(())
+    }
+
+    /// Create a lexer with only the rules that are part of the language
+    /// version `version`, as set with [RuleOptions::with_versions].
+    pub fn for_version(&self, version: &Version) -> Self {
+        Self {
+            rules: self
+                .rules
+                .iter()
+                .filter(|rule| rule.versions.contains(version))
+                .cloned()
+                .collect(),
+            limits: self.limits,
+            skip_bom: self.skip_bom,
+            skip_shebang: self.skip_shebang,
+        }
+    }
+
+    /// Set limits on the input the lexer accepts.
+    pub fn with_limits(mut self, limits: LexLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Get the limits on the input the lexer accepts.
+    pub fn limits(&self) -> LexLimits {
+        self.limits
+    }
+
+    /// Set whether a UTF-8 byte order mark at the start of the input is
+    /// skipped, as if an ignore rule matched it. Spans still count its bytes.
+    pub fn with_skip_bom(mut self, yes: bool) -> Self {
+        self.skip_bom = yes;
+        self
+    }
+
+    /// Set whether a shebang line like `#!/usr/bin/env lang` at the start of
+    /// the input (after any skipped byte order mark) is skipped, as if an
+    /// ignore rule matched it up to the newline.
+    pub fn with_skip_shebang(mut self, yes: bool) -> Self {
+        self.skip_shebang = yes;
+        self
+    }
+
+    /// Get the length of the byte order mark and shebang line skipped from
+    /// the start of `s`, adding their spans to `ignored` if it's given.
+    pub(crate) fn skip_preamble(
+        &self,
+        s: &str,
+        ignored: Option<&mut Vec<Span>>,
+    ) -> usize {
+        let mut spans = Vec::new();
+        let mut pos = 0;
+        if self.skip_bom && s.starts_with('\u{feff}') {
+            pos = '\u{feff}'.len_utf8();
+            spans.push(Span::new(0, pos));
+        }
+        if self.skip_shebang && s[pos..].starts_with("#!") {
+            let end = s[pos..].find('\n').map_or(s.len(), |len| pos + len);
+            spans.push(Span::new(pos, end));
+            pos = end;
+        }
+        if let Some(ignored) = ignored {
+            ignored.extend(spans);
+        }
+        pos
+    }
+
+    /// Check that every kind of token listed by `T`'s [TokenKinds] is
+    /// produced by a rule, and that every rule produces kinds `T` lists,
+    /// returning an error naming the kinds that aren't. Call it once the
+    /// lexer is built, so a variant added to the token enum without a rule
+    /// fails there instead of never being lexed.
+    ///
+    /// A rule's kinds are those declared with [RuleOptions::with_kind];
+    /// rules without any, like those ignoring whitespace, aren't checked.
+    pub fn check_kinds(&self) -> crate::Result<()>
+    where
+        T: TokenKinds,
+    {
+        let produced: BTreeSet<&str> = self
+            .rules
+            .iter()
+            .flat_map(|rule| &rule.kinds)
+            .map(String::as_str)
+            .collect();
+        let mut problems = Vec::new();
+        for kind in T::KINDS {
+            if !produced.contains(kind) {
+                problems.push(format!("no rule produces `{kind}`"));
+            }
+        }
+        for kind in produced {
+            if !T::KINDS.contains(&kind) {
+                problems.push(format!("`{kind}` isn't a kind of token"));
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(crate::Error::build(format!(
+                "Lexer doesn't match token kinds: {}!",
+                problems.join(", ")
+            ))),
+        }
+    }
+
+    /// Iterate over descriptions of the lexer's rules, in priority order.
+    pub fn rules(&self) -> impl Iterator<Item = RuleInfo<'_>> {
+        self.rules
+            .iter()
+            .enumerate()
+            .map(|(priority, rule)| rule.info(priority))
+    }
+}
+
+impl<T, E: HandlerError> Lexer<T, E> {
+    /// Lex the string `s` into tokens.
+    ///
+    /// `s` can be any string type, like a `&str`, `String` or `Cow<str>`.
+    /// The tokens never borrow from it, as handlers return owned tokens, so
+    /// `s` can be a temporary, like a line read from stdin in a loop. Use
+    /// [Lexer::lex_owned] to keep the text of each token too.
+    pub fn lex(&self, s: impl AsRef<str>) -> Result<Vec<T>, E> {
+        Ok(self
+            .lex_matches(s)?
+            .into_iter()
+            .map(LexerMatch::into_token)
+            .collect())
+    }
+
+    /// Lex the string `s` into tokens wrapped with the span each token was
+    /// lexed from, ready to be parsed from a `TokenQueue<Spanned<T>>`.
+    pub fn lex_spanned(
+        &self,
+        s: impl AsRef<str>,
+    ) -> Result<Vec<Spanned<T>>, E> {
+        Ok(self
+            .lex_matches(s)?
+            .into_iter()
+            .map(Spanned::from)
+            .collect())
+    }
+
+    /// Lex the string `s` into tokens, keeping the span each token was lexed
+    /// from.
+    pub fn lex_matches(
+        &self,
+        s: impl AsRef<str>,
+    ) -> Result<Vec<LexerMatch<T>>, E> {
+        self.lex_into(s.as_ref(), &mut (), &[], None, None, None)
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], passing
+    /// `ctx` to the handlers of rules added with [Lexer::add_ctx_rule], so
+    /// they can intern strings, count tokens, or record pragmas without
+    /// global state.
+    pub fn lex_with<C: 'static>(
+        &self,
+        s: impl AsRef<str>,
+        ctx: &mut C,
+    ) -> Result<Vec<LexerMatch<T>>, E> {
+        self.lex_into(s.as_ref(), ctx, &[], None, None, None)
+    }
+
+    /// Add a rule like [Lexer::add_rule], whose handler also receives the
+    /// context passed to [Lexer::lex_with]. Lexing with any other method, or
+    /// a context of another type, fails when the rule matches.
+    pub fn add_ctx_rule<C, F>(&mut self, pat: &str, handler: F)
+    where
+        C: 'static,
+        F: Fn(Match, &mut C) -> LexResult<T, E> + Send + Sync + 'static,
+    {
+        self.rules.push(LexerRule {
+            name: None,
+            pat: Regex::new(pat)
+                .expect("Invalid regexp passed to Lexer::add_ctx_rule"),
+            handler: Handler::Ctx(Arc::new(move |re_match, ctx| {
+                match ctx.downcast_mut::<C>() {
+                    Some(ctx) => handler(re_match, ctx),
+                    None => LexResult::Error(
+                        LexError::custom(format!(
+                            "Expected a lexing context of type `{}`!",
+                            std::any::type_name::<C>()
+                        ))
+                        .into(),
+                    ),
+                }
+            })),
+            versions: VersionRange::default(),
+            kinds: Vec::new(),
+            group: None,
+        });
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], as if the
+    /// rules in the groups named in `disabled` (see [RuleOptions::with_group])
+    /// weren't there. Nothing is rebuilt, so one lexer can lex several
+    /// dialects, each disabling the groups it doesn't support.
+    pub fn lex_matches_without_groups(
+        &self,
+        s: impl AsRef<str>,
+        disabled: &[&str],
+    ) -> Result<Vec<LexerMatch<T>>, E> {
+        self.lex_into(s.as_ref(), &mut (), disabled, None, None, None)
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], taking
+    /// ownership of it so the text of each token can be borrowed from the
+    /// result after the original string is gone. A `String` or owned
+    /// `Cow<str>` is moved in without copying.
+    pub fn lex_owned(&self, s: impl Into<String>) -> Result<Lexed<T>, E> {
+        let source = s.into();
+        let matches = self.lex_matches(&source)?;
+        Ok(Lexed { source, matches })
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], also
+    /// returning a warning diagnostic for each match whose handler returned
+    /// [LexResult::Warn], in order. Other methods keep the token and drop
+    /// the warning.
+    pub fn lex_with_warnings(
+        &self,
+        s: impl AsRef<str>,
+    ) -> Result<(Vec<LexerMatch<T>>, Vec<Diagnostic>), E> {
+        let mut warnings: Vec<Diagnostic> = Vec::new();
+        let matches = self.lex_into(
+            s.as_ref(),
+            &mut (),
+            &[],
+            None,
+            Some(&mut warnings),
+            None,
+        )?;
+        Ok((matches, warnings))
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], reporting
+    /// the warnings of handlers, then any error, to `sink`. An error is
+    /// reported at its [position](HandlerError::pos), or the start of the
+    /// input if it has none. Returns `None` if lexing failed or the sink
+    /// asked to stop.
+    pub fn lex_reporting(
+        &self,
+        s: impl AsRef<str>,
+        sink: &mut dyn ErrorSink,
+    ) -> Option<Vec<LexerMatch<T>>>
+    where
+        E: Display,
+    {
+        let s = s.as_ref();
+        let mut warnings: Vec<Diagnostic> = Vec::new();
+        let result =
+            self.lex_into(s, &mut (), &[], None, Some(&mut warnings), None);
+        for warning in warnings {
+            sink.report(warning).continue_value()?;
+        }
+        match result {
+            Ok(matches) => Some(matches),
+            Err(err) => {
+                let pos = err.pos().unwrap_or(0);
+                let span = Span::new(pos, pos);
+                let _ = sink.report(Diagnostic::error(err.to_string(), span));
+                None
+            }
+        }
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], also
+    /// returning the span of each match whose handler returned
+    /// [LexResult::Ignore], in order. Together the tokens and ignored spans
+    /// cover the whole input, so the exact bytes skipped can be recovered.
+    pub fn lex_with_gaps(
+        &self,
+        s: impl AsRef<str>,
+    ) -> Result<(Vec<LexerMatch<T>>, Vec<Span>), E> {
+        let mut ignored: Vec<Span> = Vec::new();
+        let matches = self.lex_into(
+            s.as_ref(),
+            &mut (),
+            &[],
+            Some(&mut ignored),
+            None,
+            None,
+        )?;
+        Ok((matches, ignored))
+    }
+
+    /// Lex the string `s` into tokens like [Lexer::lex_matches], also
+    /// returning how many matches each rule won, how many bytes they
+    /// covered, and how long was spent on the rule, to find the rules that
+    /// dominate lexing time.
+    pub fn lex_with_stats(
+        &self,
+        s: impl AsRef<str>,
+    ) -> Result<(Vec<LexerMatch<T>>, LexStats<'_>), E> {
+        let start = Instant::now();
+        let mut stats: Vec<RuleStats> = self
+            .rules()
+            .map(|rule| RuleStats {
+                rule,
+                matches: 0,
+                bytes: 0,
+                time: Duration::ZERO,
+            })
+            .collect();
+        let matches = self.lex_into(
+            s.as_ref(),
+            &mut (),
+            &[],
+            None,
+            None,
+            Some(&mut stats),
+        )?;
+        let stats = LexStats {
+            rules: stats,
+            total: start.elapsed(),
+        };
+        Ok((matches, stats))
+    }
+
+    /// Lex the string `s`, pushing the spans of ignored matches to `ignored`
+    /// and the warnings of handlers to `warnings`, and recording the
+    /// statistics of each rule in `stats`, if they're given.
+    fn lex_into(
+        &self,
+        s: &str,
+        ctx: &mut dyn Any,
+        disabled: &[&str],
+        mut ignored: Option<&mut Vec<Span>>,
+        mut warnings: Option<&mut Vec<Diagnostic>>,
+        mut stats: Option<&mut Vec<RuleStats>>,
+    ) -> Result<Vec<LexerMatch<T>>, E> {
+        let mut matches: Vec<LexerMatch<T>> = Vec::new();
+
+        self.limits.check(Limit::InputLength, s.len(), 0)?;
+        let mut pos = self.skip_preamble(s, ignored.as_deref_mut());
+        let order: Vec<usize> = (0..self.rules.len())
+            .filter(|rule_idx| {
+                self.rules[*rule_idx]
+                    .group
+                    .as_deref()
+                    .is_none_or(|group| !disabled.contains(&group))
+            })
+            .collect();
+        let winners = match stats.as_deref_mut() {
+            Some(stats) => {
+                let mut times = vec![Duration::ZERO; self.rules.len()];
+                let winners =
+                    self.resolve_timed(s, pos, &order, Some(&mut times));
+                for (rule_stats, time) in stats.iter_mut().zip(times) {
+                    rule_stats.time += time;
+                }
+                winners
+            }
+            None => self.resolve_timed(s, pos, &order, None),
+        };
+        for (rule_idx, re_match) in winners {
+            // ensure all input is matched
+            if re_match.start() > pos {
+                break;
+            }
+            pos = pos.max(re_match.end());
+            self.limits.check(
+                Limit::MatchLength,
+                re_match.len(),
+                re_match.start(),
+            )?;
+
+            // try handling the match and adding it to the list
+            let rule = &self.rules[rule_idx];
+            let start = Instant::now();
+            let result = rule.handle(re_match, ctx);
+            if let Some(stats) = stats.as_deref_mut() {
+                let rule_stats = &mut stats[rule_idx];
+                rule_stats.matches += 1;
+                rule_stats.bytes += re_match.len();
+                rule_stats.time += start.elapsed();
+            }
+            match result {
+                LexResult::Token(t) => matches.push(LexerMatch {
+                    token: t,
+                    span: re_match.range().into(),
+                }),
+                LexResult::Ignore => {
+                    if let Some(ignored) = ignored.as_deref_mut() {
+                        ignored.push(re_match.range().into());
+                    }
+                }
+                LexResult::Warn(t, message) => {
+                    let span: Span = re_match.range().into();
+                    if let Some(warnings) = warnings.as_deref_mut() {
+                        warnings.push(Diagnostic::warning(message, span));
+                    }
+                    matches.push(LexerMatch { token: t, span });
+                }
+                LexResult::Error(e) => {
+                    return Err(match &rule.name {
+                        Some(name) => e.in_rule(name, re_match.start()),
+                        None => e,
+                    });
+                }
+            }
+            self.limits.check(
+                Limit::Tokens,
+                matches.len(),
+                re_match.start(),
+            )?;
+        }
+
+        if pos < s.len() {
+            return Err(LexError::Unmatched { pos }.into());
+        }
+
+        Ok(matches)
+    }
+}
+
+impl<T, E> Lexer<T, E> {
+    /// Report how the string `s` would be tokenized, without running any
+    /// handlers: the rule that won each region of the input, the matches that
+    /// lost to it and why, and the regions no rule matched.
+    pub fn explain<'a>(&'a self, s: &'a str) -> Vec<Explanation<'a>> {
+        let mut explanations: Vec<Explanation> = self
+            .regions(s, self.skip_preamble(s, None))
+            .into_iter()
+            .map(|(rule_idx, span)| Explanation {
+                span,
+                text: &s[span.start..span.end],
+                winner: rule_idx
+                    .map(|rule_idx| self.rules[rule_idx].info(rule_idx)),
+                rivals: Vec::new(),
+            })
+            .collect();
+
+        // attribute each losing match to the first region it overlaps
+        for rule_match in self.all_matches(s) {
+            if rule_match.won {
+                continue;
+            }
+            let span = rule_match.span;
+            let Some(region) = explanations
+                .iter_mut()
+                .find(|region| region.span.intersect(span).is_some())
+            else {
+                continue;
+            };
+            let defeat = if span.start != region.span.start {
+                Defeat::Overlapped
+            } else if span.len() < region.span.len() {
+                Defeat::Shorter
+            } else {
+                Defeat::Priority
+            };
+            region.rivals.push(Rival {
+                rule: self.rules[rule_match.rule].info(rule_match.rule),
+                span,
+                defeat,
+            });
+        }
+
+        explanations
+    }
+
+    /// Check that tokenizing each of `samples` gives the same result when the
+    /// rules are tried in reverse order and when the sample is lexed again,
+    /// returning an error naming the first sample that doesn't. Handlers
+    /// aren't run.
+    pub fn verify_determinism<'s>(
+        &self,
+        samples: impl IntoIterator<Item = &'s str>,
+    ) -> crate::Result<()> {
+        let reversed: Vec<usize> = (0..self.rules.len()).rev().collect();
+        for (sample_idx, sample) in samples.into_iter().enumerate() {
+            let winners = self.resolve(sample);
+            if self.resolve_in_order(sample, &reversed) != winners
+                || self.resolve(sample) != winners
+            {
+                return Err(crate::Error::build(format!(
+                    "Lexing sample {sample_idx} ({sample:?}) isn't deterministic"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the rules for likely mistakes, returning each one found in
+    /// priority order: rules that can match the empty string, rules starting
+    /// with a repeated `.`, rules shadowed by an earlier rule, and nested
+    /// repetitions.
+    ///
+    /// The checks are heuristics. A rule is only found to be shadowed if it
+    /// matches a literal string (ignoring word boundaries at either end) that
+    /// an earlier rule matches entirely, or if an earlier rule has the same
+    /// pattern.
+    pub fn lint(&self) -> Vec<Lint<'_>> {
+        let mut lints: Vec<Lint> = Vec::new();
+        for (rule_idx, rule) in self.rules.iter().enumerate() {
+            let info = rule.info(rule_idx);
+            let pat = rule.pat.as_str();
+            let mut lint = |kind| lints.push(Lint { rule: info, kind });
+
+            if rule.pat.is_match("") {
+                lint(LintKind::EmptyMatch);
+            }
+            let unflagged = strip_flags(pat);
+            if unflagged.starts_with(".*") || unflagged.starts_with(".+") {
+                lint(LintKind::LeadingWildcard);
+            }
+            let literal = literal(pat);
+            let shadowed_by = self.rules[..rule_idx].iter().position(
+                |earlier| match &literal {
+                    Some(literal) => {
+                        earlier.pat.find(literal).is_some_and(|re_match| {
+                            re_match.range() == (0..literal.len())
+                        })
+                    }
+                    None => earlier.pat.as_str() == pat,
+                },
+            );
+            if let Some(earlier_idx) = shadowed_by {
+                let by = self.rules[earlier_idx].info(earlier_idx);
+                lint(LintKind::Shadowed(by));
+            }
+            if has_nested_repetition(pat) {
+                lint(LintKind::NestedRepetition);
+            }
+        }
+        lints
+    }
+
+    /// Find every match of every rule against the string `s`, including the
+    /// matches that lose to overlapping matches when lexing, sorted by start
+    /// and then by rule. Empty matches are left out, as they're never lexed,
+    /// as are matches in a skipped byte order mark or shebang line.
+    pub fn all_matches<'a>(&self, s: &'a str) -> Vec<RuleMatch<'a>> {
+        let winners: HashSet<(usize, Range<usize>)> = self
+            .resolve(s)
+            .into_iter()
+            .map(|(rule_idx, re_match)| (rule_idx, re_match.range()))
+            .collect();
+        let mut matches: Vec<RuleMatch> = Vec::new();
+        let start = self.skip_preamble(s, None);
+
+        for (rule_idx, rule) in self.rules.iter().enumerate() {
+            let mut pos = start;
+            while let Some(re_match) = rule.pat.find_at(s, pos) {
+                pos = re_match.end();
+                if re_match.is_empty() {
+                    // step past the empty match to find the next one
+                    match s[pos..].chars().next() {
+                        Some(c) => pos += c.len_utf8(),
+                        None => break,
+                    }
+                    continue;
+                }
+                matches.push(RuleMatch {
+                    rule: rule_idx,
+                    span: re_match.range().into(),
+                    text: re_match.as_str(),
+                    won: winners.contains(&(rule_idx, re_match.range())),
+                });
+            }
+        }
+        matches
+            .sort_by_key(|rule_match| (rule_match.span.start, rule_match.rule));
 
-/// Represents a match discovered during lexing.
-pub struct LexerMatch<T> {
-    token: T,
-    start: usize,
-    len: usize,
-}
+        matches
+    }
 
-/// Represents a lexer that lexes tokens of type `T`.
-pub struct Lexer<T> {
-    rules: Vec<LexerRule<T>>,
-}
+    /// Find the match that wins each region of `s`, as pairs of rule index
+    /// and match sorted by start. Input no rule matched is left out, as is a
+    /// byte order mark or shebang line the lexer skips.
+    fn resolve<'s>(&self, s: &'s str) -> Vec<(usize, Match<'s>)> {
+        let order: Vec<usize> = (0..self.rules.len()).collect();
+        self.resolve_in_order(s, &order)
+    }
 
-impl<T> Lexer<T> {
-    pub fn new() -> Self {
-        Self { rules: Vec::new() }
+    /// Split `s`, from byte `start`, into the regions lexing resolves it
+    /// into: each winning match, with its rule index, and each stretch of
+    /// input no rule matched, with `None`, in order.
+    pub(crate) fn regions(
+        &self,
+        s: &str,
+        start: usize,
+    ) -> Vec<(Option<usize>, Span)> {
+        let order: Vec<usize> = (0..self.rules.len()).collect();
+        let mut regions = Vec::new();
+        let mut pos = start;
+        for (rule_idx, re_match) in self.resolve_timed(s, start, &order, None) {
+            if re_match.start() > pos {
+                regions.push((None, Span::new(pos, re_match.start())));
+            }
+            regions.push((Some(rule_idx), re_match.range().into()));
+            pos = re_match.end();
+        }
+        if pos < s.len() {
+            regions.push((None, Span::new(pos, s.len())));
+        }
+        regions
     }
 
-    pub fn add_rule(&mut self, pat: &str, handler: MatchHandler<T>) {
-        self.rules.push(LexerRule {
-            pat: Regex::new(pat)
-                .expect("Invalid regexp passed to Lexer::add_rule"),
-            handler,
-        });
+    /// Resolve matches like [Lexer::resolve], trying the rules at each
+    /// position in the order of the indices in `order`. The result doesn't
+    /// depend on `order`.
+    fn resolve_in_order<'s>(
+        &self,
+        s: &'s str,
+        order: &[usize],
+    ) -> Vec<(usize, Match<'s>)> {
+        self.resolve_timed(s, self.skip_preamble(s, None), order, None)
     }
 
-    pub fn lex(&self, s: &str) -> anyhow::Result<Vec<T>> {
-        let mut match_info: Vec<(usize, usize)> = vec![(0, 0); s.len()];
-        let mut matches: Vec<LexerMatch<T>> = Vec::new();
+    /// Resolve matches like [Lexer::resolve_in_order] from byte `start` of
+    /// `s`, adding the time spent searching with each rule's regex to
+    /// `search_times` if it's given.
+    fn resolve_timed<'s>(
+        &self,
+        s: &'s str,
+        start: usize,
+        order: &[usize],
+        mut search_times: Option<&mut [Duration]>,
+    ) -> Vec<(usize, Match<'s>)> {
+        let mut find_at = |rule_idx: usize, pos: usize| {
+            let Some(search_times) = search_times.as_deref_mut() else {
+                return self.rules[rule_idx].pat.find_at(s, pos);
+            };
+            let start = Instant::now();
+            let re_match = self.rules[rule_idx].pat.find_at(s, pos);
+            search_times[rule_idx] += start.elapsed();
+            re_match
+        };
+        // the leftmost match of each rule at or after the last position it
+        // was searched from; a match that starts where the search started is
+        // the rule's match at that position
+        let mut next: Vec<Option<Match>> =
+            (0..self.rules.len()).map(|_| None).collect();
+        for &rule_idx in order {
+            next[rule_idx] = find_at(rule_idx, start);
+        }
+        let mut winners: Vec<(usize, Match)> = Vec::new();
+        let mut pos = start;
 
-        // for each rule
-        for rule in &self.rules {
-            // for each match of the rule's regex against the input
-            for re_match in rule.pat.find_iter(s) {
-                let mut takes_priority = true;
-                // for each position in the match
-                for i in re_match.start()..re_match.end() {
-                    // extract info about conflicting match
-                    let (confl_start, confl_len) = match_info[i];
-                    // note confl_len = 0 if no conflicting match exists
-                    if confl_len >= re_match.len() {
-                        // a match that was already found has a length gte this
-                        // one
-                        takes_priority = false;
-                        // stop looking for overlapping matches because we're
-                        // not keeping this match anyway
-                        break;
-                    } else if confl_len > 0 {
-                        // a match already exists and it's shorter than this
-                        // one => remove it from the arrays
-                        for i in confl_start..confl_start + confl_len {
-                            match_info[i] = (0, 0);
-                        }
-                        matches = matches
-                            .into_iter()
-                            .filter(|lexer_match| {
-                                !(lexer_match.start == confl_start
-                                    && lexer_match.len == confl_len)
-                            })
-                            .collect();
-                    }
+        while pos < s.len() {
+            let mut best: Option<(usize, Match)> = None;
+            for &rule_idx in order {
+                // the cached match is stale once the position passes it
+                if next[rule_idx].is_some_and(|re_match| re_match.start() < pos)
+                {
+                    next[rule_idx] = find_at(rule_idx, pos);
                 }
-                if takes_priority {
-                    // got through the loop without finding an overlapping
-                    // match - update the match_info array
-                    for i in re_match.start()..re_match.end() {
-                        match_info[i] = (re_match.start(), re_match.len());
-                    }
-                    // try handling the match and adding it to the list
-                    match rule.handle(re_match) {
-                        LexResult::Token(t) => matches.push(LexerMatch {
-                            token: t,
-                            start: re_match.start(),
-                            len: re_match.len(),
-                        }),
-                        LexResult::Ignore => {}
-                        LexResult::Error(e) => return Err(e),
+                let Some(re_match) = next[rule_idx] else {
+                    continue;
+                };
+                if re_match.start() != pos || re_match.is_empty() {
+                    continue;
+                }
+                // longest match wins, ties go to the earlier rule
+                match best {
+                    Some((best_idx, best_match))
+                        if (best_match.len(), rule_idx)
+                            > (re_match.len(), best_idx) =>
+                    {
+                        #[cfg(feature = "tracing")]
+                        tracing::trace!(
+                            rule = self.rules[rule_idx].label(),
+                            start = re_match.start(),
+                            end = re_match.end(),
+                            "match discarded in favour of a longer match"
+                        );
                     }
+                    _ => best = Some((rule_idx, re_match)),
                 }
             }
-        }
 
-        // ensure all input is matched
-        for (start, len) in match_info {
-            if start == 0 && len == 0 {
-                return Err(anyhow::anyhow!(
-                    "Unmatched input at position {start}!",
-                ));
+            match best {
+                Some((rule_idx, re_match)) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::trace!(
+                        rule = self.rules[rule_idx].label(),
+                        start = re_match.start(),
+                        end = re_match.end(),
+                        "rule matched"
+                    );
+                    pos = re_match.end();
+                    winners.push((rule_idx, re_match));
+                }
+                // no rule matches here: skip a character and try again
+                None => {
+                    pos += s[pos..].chars().next().map_or(1, char::len_utf8);
+                }
             }
         }
 
-        // sort matches by start location
-        matches.sort_by(|a, b| a.start.cmp(&b.start));
+        winners
+    }
+}
 
-        Ok(matches
-            .into_iter()
-            .map(|lexer_match| lexer_match.token)
-            .collect())
+/// Lists the lexer's rules, one per line, in priority order.
+impl<T, E> Display for Lexer<T, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for rule in self.rules() {
+            writeln!(f, "{rule}")?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::lex::{LexResult, Lexer};
+    use crate::diagnostic::Diagnostic;
+    use crate::lex::{
+        Defeat, HandlerError, LexError, LexLimits, LexResult, Lexer, Limit,
+        RuleInfo, RuleOptions, TokenKinds, has_nested_repetition, literal,
+    };
+    use crate::span::Span;
+    use crate::version::{Version, VersionRange};
     use std::error::Error;
+    use std::sync::OnceLock;
+    use std::thread;
 
     #[derive(PartialEq, Debug)]
     enum Token {
@@ -143,17 +1666,11 @@ mod tests {
         let mut lexer = Lexer::new();
 
         lexer.add_rule(r"[\s\t\n]", |_| LexResult::Ignore);
-        lexer.add_rule(r"\-?[0-9]+", |int_match| {
-            match int_match.as_str().parse::<i32>() {
-                Ok(val) => LexResult::Token(Token::IntLiteral(val)),
-                Err(err) => LexResult::Error(err.into()),
-            }
+        lexer.add_try_rule(r"\-?[0-9]+", |int_match| {
+            Ok(Some(Token::IntLiteral(int_match.as_str().parse()?)))
         });
-        lexer.add_rule(r"\-?[0-9]+(\.[0-9]+)", |dbl_match| {
-            match dbl_match.as_str().parse::<f64>() {
-                Ok(val) => LexResult::Token(Token::DblLiteral(val)),
-                Err(err) => LexResult::Error(err.into()),
-            }
+        lexer.add_try_rule(r"\-?[0-9]+(\.[0-9]+)", |dbl_match| {
+            Ok(Some(Token::DblLiteral(dbl_match.as_str().parse()?)))
         });
 
         lexer
@@ -174,4 +1691,680 @@ mod tests {
 
         Ok(())
     }
+
+    #[derive(Debug, PartialEq)]
+    enum TypedError {
+        Unmatched(usize),
+        Overflow,
+    }
+
+    impl From<LexError> for TypedError {
+        fn from(value: LexError) -> Self {
+            match value {
+                LexError::Unmatched { pos } => Self::Unmatched(pos),
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    impl HandlerError for TypedError {}
+
+    #[test]
+    fn test_typed_error() {
+        let mut lexer: Lexer<u8, TypedError> = Lexer::default();
+        lexer.add_rule(r" ", |_| LexResult::Ignore);
+        lexer.add_rule_named("byte", r"[0-9]+", |byte_match| {
+            let byte = byte_match.as_str().parse();
+            byte.map_err(|_| TypedError::Overflow).into()
+        });
+
+        assert!(lexer.lex("1 255") == Ok(vec![1, 255]));
+        assert!(lexer.lex("1 256") == Err(TypedError::Overflow));
+        assert!(lexer.lex("1 x") == Err(TypedError::Unmatched(2)));
+    }
+
+    #[test]
+    fn test_rules() {
+        let lexer = setup_lexer();
+
+        let rules: Vec<RuleInfo> = lexer.rules().collect();
+        assert!(rules.len() == 3);
+        assert!(rules[1].pattern == r"\-?[0-9]+" && rules[1].priority == 1);
+        assert!(
+            lexer.to_string().lines().nth(2)
+                == Some(r"  2  /\-?[0-9]+(\.[0-9]+)/")
+        );
+    }
+
+    #[test]
+    fn test_named_rule() {
+        let mut lexer = setup_lexer();
+        lexer.add_rule_named("unsupported", r"[a-z]+", |_| {
+            LexResult::Error(LexError::custom("Identifiers aren't supported!"))
+        });
+
+        assert!(lexer.rules().last().unwrap().name == Some("unsupported"));
+        assert!(lexer.to_string().ends_with("  3  unsupported  /[a-z]+/\n"));
+        let err = lexer.lex("1 abc").unwrap_err();
+        assert!(
+            err.to_string() == "Lexer rule unsupported failed at position 2!"
+        );
+    }
+
+    #[test]
+    fn test_explain() {
+        let mut lexer = setup_lexer();
+        lexer.add_rule_named("digit", r"[0-9]", |_| LexResult::Ignore);
+
+        let explanations = lexer.explain("0.5 x");
+        assert!(explanations.len() == 3);
+
+        let number = &explanations[0];
+        assert!(number.text == "0.5" && number.winner.unwrap().priority == 2);
+        let defeats: Vec<Defeat> =
+            number.rivals.iter().map(|rival| rival.defeat).collect();
+        assert!(
+            defeats
+                == vec![
+                    Defeat::Shorter,
+                    Defeat::Shorter,
+                    Defeat::Overlapped,
+                    Defeat::Overlapped
+                ]
+        );
+        assert!(explanations[2].winner.is_none());
+        assert!(
+            explanations[0].to_string().lines().last()
+                == Some(
+                    "  digit matched 2..3 but lost: it started inside the winner"
+                )
+        );
+        assert!(explanations[2].to_string() == "4..5 \"x\": unmatched");
+    }
+
+    #[test]
+    fn test_all_matches() {
+        let lexer = setup_lexer();
+
+        let matches: Vec<(usize, Span, bool)> = lexer
+            .all_matches("1.5 2")
+            .iter()
+            .map(|rule_match| {
+                (rule_match.rule, rule_match.span, rule_match.won)
+            })
+            .collect();
+        assert!(
+            matches
+                == vec![
+                    (1, Span::new(0, 1), false),
+                    (2, Span::new(0, 3), true),
+                    (1, Span::new(2, 3), false),
+                    (0, Span::new(3, 4), true),
+                    (1, Span::new(4, 5), true),
+                ]
+        );
+    }
+
+    #[test]
+    fn test_lex_matches() -> Result<(), Box<dyn Error>> {
+        let lexer = setup_lexer();
+
+        let spans: Vec<Span> = lexer
+            .lex_matches("12  0.5")?
+            .iter()
+            .map(|lexer_match| lexer_match.span())
+            .collect();
+        assert!(spans == vec![Span::new(0, 2), Span::new(4, 7)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shared() -> Result<(), Box<dyn Error>> {
+        fn assert_send_sync<T: Send + Sync>(_: &T) {}
+
+        static LEXER: OnceLock<Lexer<Token>> = OnceLock::new();
+        let lexer = LEXER.get_or_init(setup_lexer);
+        assert_send_sync(lexer);
+
+        let tokens = thread::scope(|scope| {
+            let handles: Vec<_> = ["1 2", "3.5"]
+                .into_iter()
+                .map(|input| scope.spawn(move || lexer.lex(input).unwrap()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect::<Vec<_>>()
+        });
+        assert!(
+            tokens
+                == vec![
+                    vec![Token::IntLiteral(1), Token::IntLiteral(2)],
+                    vec![Token::DblLiteral(3.5)],
+                ]
+        );
+
+        let mut clone = lexer.clone();
+        clone.add_rule(r"x", |_| LexResult::Ignore);
+        assert!(clone.rules().count() == 4 && lexer.rules().count() == 3);
+        assert!(clone.lex("x 1")? == vec![Token::IntLiteral(1)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_presets() -> Result<(), Box<dyn Error>> {
+        fn add_ident(lexer: &mut Lexer<String>) {
+            lexer.add_rule(r"[a-z/]+", |ident_match| {
+                LexResult::Token(ident_match.as_str().to_owned())
+            });
+        }
+
+        let mut lexer = Lexer::with_common_ignores();
+        add_ident(&mut lexer);
+        assert!(lexer.lex("a # b\n c")? == vec!["a", "c"]);
+
+        let mut lexer = Lexer::c_style();
+        add_ident(&mut lexer);
+        assert!(lexer.lex("a // b\n c /* d\n */ e")? == vec!["a", "c", "e"]);
+        assert!(lexer.lex("a/b")? == vec!["a/b"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_options() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<String> = Lexer::with_common_ignores();
+        lexer.add_rule_with_options(
+            "select|from",
+            RuleOptions::default().with_case_insensitive(true),
+            |kw_match| LexResult::Token(kw_match.as_str().to_lowercase()),
+        )?;
+        lexer.add_rule_with_options(
+            "'.*?'",
+            RuleOptions::default().with_dot_matches_newline(true),
+            |str_match| LexResult::Token(str_match.as_str().to_owned()),
+        )?;
+        assert!(
+            lexer.lex("SELECT 'a\nb' From")?
+                == vec!["select", "'a\nb'", "from"]
+        );
+
+        let err = lexer
+            .add_rule_with_options("(", RuleOptions::default(), |_| {
+                LexResult::Ignore
+            })
+            .unwrap_err();
+        assert!(matches!(err, crate::Error::Build(_)));
+        assert!(lexer.rules().count() == 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_for_version() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<&str> = Lexer::with_common_ignores();
+        let since_2 = VersionRange::default().with_since(Version::parse("2")?);
+        lexer.add_rule_with_options(
+            r"async\b",
+            RuleOptions::default().with_versions(since_2),
+            |_| LexResult::Token("async"),
+        )?;
+        lexer.add_rule(r"[a-z]+", |_| LexResult::Token("ident"));
+
+        let v1 = lexer.for_version(&Version::parse("1.4")?);
+        assert!(v1.lex("async fn")? == vec!["ident", "ident"]);
+        let v2 = lexer.for_version(&Version::parse("2.0")?);
+        assert!(v2.lex("async fn")? == vec!["async", "ident"]);
+        assert!(v1.rules().count() == 3 && v2.rules().count() == 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_keyword_rule() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<&str> = Lexer::with_common_ignores();
+        lexer.add_keyword_rule("type", |_| LexResult::Token("kw"));
+        lexer.add_rule(r"[a-z]+_", |_| LexResult::Token("prefix"));
+        lexer.add_rule(r"[a-z]+", |_| LexResult::Token("ident"));
+        assert!(lexer.lex("type typename")? == vec!["kw", "ident"]);
+        assert!(lexer.lex("type_ x")? == vec!["prefix", "ident"]);
+
+        let mut lexer: Lexer<&str> = Lexer::new();
+        lexer.add_keyword_rule("type", |_| LexResult::Token("kw"));
+        assert!(matches!(
+            lexer.lex("typename"),
+            Err(LexError::Unmatched { pos: 0 })
+        ));
+
+        Ok(())
+    }
+
+    /// Tokenize `s` by the documented maximal munch rules, as directly as
+    /// possible, for comparison with [Lexer::resolve].
+    fn munch<T, E>(lexer: &Lexer<T, E>, s: &str) -> Vec<(usize, Span)> {
+        let mut tokens = Vec::new();
+        let mut pos = 0;
+        while pos < s.len() {
+            let best = lexer
+                .rules
+                .iter()
+                .enumerate()
+                .filter_map(|(rule_idx, rule)| {
+                    let re_match = rule.pat.find_at(s, pos)?;
+                    (re_match.start() == pos && !re_match.is_empty())
+                        .then(|| (rule_idx, re_match.end()))
+                })
+                .min_by_key(|&(rule_idx, end)| (usize::MAX - end, rule_idx));
+            match best {
+                Some((rule_idx, end)) => {
+                    tokens.push((rule_idx, Span::new(pos, end)));
+                    pos = end;
+                }
+                None => pos += s[pos..].chars().next().unwrap().len_utf8(),
+            }
+        }
+        tokens
+    }
+
+    #[test]
+    fn test_maximal_munch() {
+        let mut lexer: Lexer<()> = Lexer::new();
+        lexer.add_rule("ab", |_| LexResult::Ignore);
+        lexer.add_rule("bcd", |_| LexResult::Ignore);
+        lexer.add_rule(".", |_| LexResult::Ignore);
+        let spans: Vec<(usize, Span)> = lexer
+            .resolve("abcd")
+            .iter()
+            .map(|(rule_idx, re_match)| (*rule_idx, re_match.range().into()))
+            .collect();
+        assert!(
+            spans
+                == vec![
+                    (0, Span::new(0, 2)),
+                    (2, Span::new(2, 3)),
+                    (2, Span::new(3, 4))
+                ]
+        );
+
+        // compare against the reference on pseudo-random rule sets and inputs
+        const PATTERNS: [&str; 10] = [
+            "a", "ab", "a+", "b*", "ba|b", r"\bab\b", "[ab]{2}", "c", "a?c+",
+            "é",
+        ];
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        let mut next = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize % n
+        };
+        for _ in 0..500 {
+            let mut lexer: Lexer<()> = Lexer::new();
+            for _ in 0..1 + next(5) {
+                lexer.add_rule(PATTERNS[next(PATTERNS.len())], |_| {
+                    LexResult::Ignore
+                });
+            }
+            let input: String = (0..next(12))
+                .map(|_| ['a', 'b', 'c', ' ', 'é'][next(5)])
+                .collect();
+            let spans: Vec<(usize, Span)> = lexer
+                .resolve(&input)
+                .iter()
+                .map(|(rule_idx, re_match)| {
+                    (*rule_idx, re_match.range().into())
+                })
+                .collect();
+            assert!(spans == munch(&lexer, &input), "{lexer}{input:?}");
+            assert!(lexer.verify_determinism([input.as_str()]).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_verify_determinism() {
+        let mut lexer = setup_lexer();
+        lexer.add_rule(r"[0-9]+", |_| LexResult::Ignore);
+        lexer.add_rule(r"[0-9.]+", |_| LexResult::Ignore);
+        assert!(
+            lexer
+                .verify_determinism(["1 2.5", "-3", "1.2.3", "", "1..2"])
+                .is_ok()
+        );
+
+        // ties between equally long matches always go to the earlier rule
+        let reversed: Vec<usize> = (0..lexer.rules.len()).rev().collect();
+        for input in ["12", "1.5", "0.5.5"] {
+            assert!(
+                lexer.resolve(input)
+                    == lexer.resolve_in_order(input, &reversed)
+            );
+        }
+        assert!(lexer.resolve("12")[0].0 == 1);
+    }
+
+    #[test]
+    fn test_lex_with_gaps() -> Result<(), Box<dyn Error>> {
+        let mut lexer = setup_lexer();
+        lexer.add_rule(r"#[^\n]*", |_| LexResult::Ignore);
+
+        let (matches, ignored) = lexer.lex_with_gaps("1 #x\n 2")?;
+        let spans: Vec<Span> = matches
+            .iter()
+            .map(|lexer_match| lexer_match.span())
+            .collect();
+        assert!(spans == vec![Span::new(0, 1), Span::new(6, 7)]);
+        assert!(
+            ignored
+                == vec![
+                    Span::new(1, 2),
+                    Span::new(2, 4),
+                    Span::new(4, 5),
+                    Span::new(5, 6)
+                ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lex_owned() -> Result<(), Box<dyn Error>> {
+        let lexer = setup_lexer();
+
+        // the source can be a temporary
+        let lexed = lexer.lex_owned(format!("{} {}", 12, 3.5))?;
+        let texts: Vec<&str> = lexed.iter().map(|(_, text)| text).collect();
+        assert!(texts == ["12", "3.5"]);
+        assert!(lexed.text(1) == Some("3.5") && lexed.text(2).is_none());
+        assert!(lexed.source() == "12 3.5" && lexed.matches().len() == 2);
+
+        let line = std::borrow::Cow::Borrowed("1 2");
+        assert!(lexer.lex(&line)? == lexer.lex(String::from("1 2"))?);
+
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Op {
+        Plus,
+        Minus,
+        Times,
+    }
+
+    impl TokenKinds for Op {
+        const KINDS: &'static [&'static str] = &["Plus", "Minus", "Times"];
+    }
+
+    #[test]
+    fn test_check_kinds() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<Op> = Lexer::with_common_ignores();
+        let kind = |kind| RuleOptions::default().with_kind(kind);
+        lexer.add_rule_with_options(r"\+", kind("Plus"), |_| {
+            LexResult::Token(Op::Plus)
+        })?;
+        lexer.add_rule_with_options(r"-", kind("Minus"), |_| {
+            LexResult::Token(Op::Minus)
+        })?;
+        assert!(
+            lexer.check_kinds().unwrap_err().to_string()
+                == "Lexer doesn't match token kinds: no rule produces \
+                    `Times`!"
+        );
+
+        lexer.add_rule_with_options(r"\*", kind("Times"), |_| {
+            LexResult::Token(Op::Times)
+        })?;
+        lexer.check_kinds()?;
+
+        lexer.add_rule_with_options(r"/", kind("Divide"), |_| {
+            LexResult::Token(Op::Times)
+        })?;
+        assert!(lexer.check_kinds().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lex_with() -> Result<(), Box<dyn Error>> {
+        // interns identifiers, lexing each as its index in the context
+        let mut lexer: Lexer<usize> = Lexer::with_common_ignores();
+        lexer.add_ctx_rule(r"[a-z]+", |m, names: &mut Vec<String>| {
+            let idx = names.iter().position(|name| name == m.as_str());
+            LexResult::Token(idx.unwrap_or_else(|| {
+                names.push(m.as_str().to_owned());
+                names.len() - 1
+            }))
+        });
+
+        let mut names: Vec<String> = Vec::new();
+        let matches = lexer.lex_with("a b a c", &mut names)?;
+        let tokens: Vec<usize> =
+            matches.into_iter().map(|m| m.into_token()).collect();
+        assert!(tokens == [0, 1, 0, 2]);
+        assert!(names == ["a", "b", "c"]);
+
+        assert!(lexer.lex("a").is_err());
+        assert!(lexer.lex_with("a", &mut 0).is_err());
+        assert!(lexer.lex(" ")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_rule_groups() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<String> = Lexer::with_common_ignores();
+        let token = |m: regex::Match| LexResult::Token(m.as_str().to_owned());
+        lexer.add_rule(r"[a-z]+|=", token);
+        lexer.add_rule_with_options(
+            r"=>",
+            RuleOptions::default().with_group("arrows"),
+            token,
+        )?;
+        lexer.add_rule_with_options(
+            r"<>",
+            RuleOptions::default().with_group("legacy"),
+            token,
+        )?;
+
+        let texts = |disabled: &[&str]| -> Result<Vec<String>, LexError> {
+            Ok(lexer
+                .lex_matches_without_groups("a => b <> c", disabled)?
+                .into_iter()
+                .map(|m| m.into_token())
+                .collect())
+        };
+        assert!(texts(&[])? == ["a", "=>", "b", "<>", "c"]);
+        assert!(texts(&["arrows"]).is_err());
+        assert!(
+            lexer
+                .lex_matches_without_groups("a => b", &["legacy"])?
+                .len()
+                == 3
+        );
+        assert!(lexer.lex("x <> y")?.len() == 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_preamble() -> Result<(), Box<dyn Error>> {
+        let lexer: Lexer<String> = Lexer::with_common_ignores();
+        let mut lexer = lexer.with_skip_bom(true).with_skip_shebang(true);
+        lexer.add_rule(r"[a-z!#/]+", |m| LexResult::Token(m.as_str().into()));
+
+        let (matches, ignored) =
+            lexer.lex_with_gaps("\u{feff}#!/bin/lang\nab")?;
+        assert!(matches.len() == 1 && matches[0].span() == Span::new(15, 17));
+        assert!(
+            ignored
+                == vec![Span::new(0, 3), Span::new(3, 14), Span::new(14, 15)]
+        );
+
+        // and isn't explained as lexed or unmatched
+        let s = "\u{feff}#!/bin/lang\nab";
+        assert!(lexer.explain(s)[0].span == Span::new(14, 15));
+        let rule_matches = lexer.all_matches(s);
+        assert!(rule_matches.len() == 2);
+        assert!(rule_matches.iter().all(|m| m.span.start >= 14 && m.won));
+        lexer.verify_determinism([s])?;
+
+        // only skipped at the start of the input
+        assert!(lexer.lex("a #!b")? == ["a"]);
+        assert!(lexer.lex("#!b")?.is_empty());
+        let lexer = lexer.with_skip_shebang(false);
+        assert!(lexer.lex("#!b")?.is_empty());
+        assert!(lexer.lex("\u{feff}a")? == ["a"]);
+        let lexer = lexer.with_skip_bom(false);
+        assert!(lexer.lex("\u{feff}a").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_limits() -> Result<(), Box<dyn Error>> {
+        let limits = LexLimits::default()
+            .with_max_input_len(16)
+            .with_max_match_len(4)
+            .with_max_tokens(3);
+        let lexer = setup_lexer().with_limits(limits);
+        assert!(lexer.limits() == limits);
+
+        assert!(lexer.lex("1 2 3")?.len() == 3);
+        let err = lexer.lex("1 2 3 4").unwrap_err();
+        assert!(matches!(
+            err,
+            LexError::LimitExceeded {
+                limit: Limit::Tokens,
+                max: 3,
+                pos: 6
+            }
+        ));
+        assert!(
+            err.to_string()
+                == "Exceeded the maximum number of tokens of 3 at position 6!"
+        );
+        assert!(matches!(
+            lexer.lex("1 22222"),
+            Err(LexError::LimitExceeded {
+                limit: Limit::MatchLength,
+                pos: 2,
+                ..
+            })
+        ));
+        assert!(matches!(
+            lexer.lex(" ".repeat(17)),
+            Err(LexError::LimitExceeded {
+                limit: Limit::InputLength,
+                pos: 0,
+                ..
+            })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_empty_matches() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<String> = Lexer::new();
+        lexer.add_rule(r"a*", |re_match| {
+            LexResult::Token(re_match.as_str().to_owned())
+        });
+        lexer.add_rule(r"b", |_| LexResult::Token("b".into()));
+
+        assert!(lexer.lex("aabab")? == ["aa", "b", "a", "b"]);
+        assert!(lexer.lex("")?.is_empty());
+        assert!(matches!(
+            lexer.lex("bc"),
+            Err(LexError::Unmatched { pos: 1 })
+        ));
+
+        let rules: Vec<usize> = lexer
+            .all_matches("ba")
+            .iter()
+            .map(|rule_match| rule_match.rule)
+            .collect();
+        assert!(rules == [1, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lint() {
+        let mut lexer: Lexer<()> = Lexer::new();
+        lexer.add_rule(r"[a-z]+", |_| LexResult::Ignore);
+        lexer.add_keyword_rule("if", |_| LexResult::Ignore);
+        lexer.add_rule(r"[0-9]*", |_| LexResult::Ignore);
+        lexer.add_rule(r"(?s).+", |_| LexResult::Ignore);
+        lexer.add_rule(r"(\s*,)+", |_| LexResult::Ignore);
+        lexer.add_rule(r"([a-z]{1,}\.)*[a-z]", |_| LexResult::Ignore);
+        lexer.add_rule(r"\(|\)", |_| LexResult::Ignore);
+
+        let lints: Vec<String> =
+            lexer.lint().iter().map(|lint| lint.to_string()).collect();
+        assert!(
+            lints
+                == [
+                    r"/\bif\b/ is shadowed by /[a-z]+/",
+                    "/[0-9]*/ can match the empty string",
+                    "/(?s).+/ starts with a repeated `.`, matching to line end",
+                    r"/(\s*,)+/ has a nested repetition",
+                    r"/([a-z]{1,}\.)*[a-z]/ has a nested repetition",
+                ]
+        );
+
+        // bounded repetitions and escaped parentheses are fine
+        assert!(!has_nested_repetition(r"(a+){2}"));
+        assert!(!has_nested_repetition(r"\(a+\)+"));
+        assert!(has_nested_repetition(r"([(]a+)+"));
+        assert!(literal(r"a\.b") == Some("a.b".into()));
+        assert!(literal(r"a\db").is_none());
+    }
+
+    #[test]
+    fn test_lex_with_stats() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<u32> = Lexer::with_common_ignores();
+        lexer.add_try_rule("[0-9]+", |int_match| {
+            Ok(Some(int_match.as_str().parse()?))
+        });
+
+        let (matches, stats) = lexer.lex_with_stats("1 22 # 333\n4444")?;
+        assert!(matches.len() == 3);
+        let counts: Vec<(usize, usize)> = stats
+            .rules
+            .iter()
+            .map(|stats| (stats.matches, stats.bytes))
+            .collect();
+        assert!(counts == [(3, 3), (1, 5), (3, 7)]);
+        assert!(stats.rules[0].rule.name == Some("whitespace"));
+
+        let report = stats.to_string();
+        assert!(report.lines().count() == 5);
+        assert!(report.contains("       3           7  /[0-9]+/"));
+
+        assert!(lexer.lex_with_stats("1 x").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lex_with_warnings() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<u8> = Lexer::with_common_ignores();
+        lexer.add_rule("[0-9]+", |int_match| {
+            match int_match.as_str().parse() {
+                Ok(int) => LexResult::Token(int),
+                Err(_) => LexResult::Warn(u8::MAX, "Clamped to 255".into()),
+            }
+        });
+
+        let (matches, warnings) = lexer.lex_with_warnings("1 300")?;
+        assert!(matches.len() == 2 && *matches[1].token() == 255);
+        assert!(
+            warnings
+                == [Diagnostic::warning("Clamped to 255", Span::new(2, 5))]
+        );
+        assert!(lexer.lex("300")? == [255]);
+
+        Ok(())
+    }
 }
@@ -1,5 +1,47 @@
+// Lets code generated by `rlrl-derive` refer to `::rlrl` inside this crate.
+extern crate self as rlrl;
+
+pub mod brackets;
+pub mod chars;
+pub mod compact;
+pub mod corpus;
+pub mod coverage;
+pub mod deferred;
+pub mod detokenize;
+pub mod diagnostic;
+pub mod docs;
+pub mod dynamic;
+pub mod earley;
+pub mod ebnf;
+pub mod error;
+pub mod eval;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod grammar;
+pub mod ident;
+pub mod json;
+pub mod keyword;
 pub mod lex;
+pub mod lr;
+#[cfg(feature = "lsp")]
+pub mod lsp;
 pub mod parse;
+#[cfg(feature = "playground")]
+pub mod playground;
 pub mod prelude;
+pub mod pretty;
+pub mod rewrite;
+pub mod scope;
+pub mod semantic;
+pub mod session;
+pub mod sink;
+pub mod source;
+pub mod span;
+pub mod suggest;
+pub mod terminator;
+pub mod token;
+pub mod trace;
+pub mod value;
+pub mod version;
 
-mod calc;
+pub use error::{Error, Result};
@@ -0,0 +1,648 @@
+use crate::coverage::Coverage;
+use crate::grammar::{Grammar, Symbol};
+use crate::parse::{ParseError, ParseResult, Result, TokenQueue};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Display;
+
+/// Function that maps a token of type `L` to the name of its terminal in the
+/// [Grammar].
+pub type ClassifyFn<L> = fn(&L) -> &str;
+
+/// Function that converts a shifted token of type `L` into a value of type `V`.
+pub type ShiftFn<L, V> = fn(&L) -> V;
+
+/// Function that combines the values of a production's right hand side into
+/// the value of its left hand side.
+pub type ReduceFn<V> = fn(Vec<V>) -> Result<V>;
+
+/// The kind of table to construct from a [Grammar].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LrKind {
+    /// Canonical LR(1), which may have many more states than LALR(1).
+    Lr1,
+    /// LALR(1), built by merging LR(1) states with identical cores.
+    Lalr,
+}
+
+/// An entry in the action table of an [LrTable].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Consume the lookahead token and go to the given state.
+    Shift(usize),
+    /// Reduce by the production with the given index.
+    Reduce(usize),
+    /// Accept the input.
+    Accept,
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Shift(state) => write!(f, "shift to state {state}"),
+            Self::Reduce(production) => {
+                write!(f, "reduce by production {production}")
+            }
+            Self::Accept => write!(f, "accept"),
+        }
+    }
+}
+
+/// The kind of a [Conflict].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictKind {
+    ShiftReduce,
+    ReduceReduce,
+    /// Accepting the input at the end conflicts with a reduction, as in a
+    /// grammar with a production like `s -> s`.
+    AcceptReduce,
+}
+
+/// Represents a conflict found while building an [LrTable].
+///
+/// Conflicts are resolved the way yacc resolves them: shift-reduce conflicts
+/// prefer the shift, and reduce-reduce conflicts prefer the production
+/// declared first. Accepting is always preferred to reducing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub kind: ConflictKind,
+    pub state: usize,
+    /// The lookahead terminal, or `None` for the end of input.
+    pub terminal: Option<String>,
+    pub chosen: Action,
+    pub discarded: Action,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            ConflictKind::ShiftReduce => "shift-reduce",
+            ConflictKind::ReduceReduce => "reduce-reduce",
+            ConflictKind::AcceptReduce => "accept-reduce",
+        };
+        let terminal = match &self.terminal {
+            Some(name) => format!("`{name}`"),
+            None => "end of input".to_owned(),
+        };
+        write!(
+            f,
+            "{kind} conflict in state {} on {terminal}: chose {} over {}",
+            self.state, self.chosen, self.discarded
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Sym {
+    T(usize),
+    N(usize),
+}
+
+/// An LR(1) item: (production, dot position, lookahead terminal).
+type Item = (usize, usize, usize);
+
+/// Represents the action and goto tables of an LR parser built from a
+/// [Grammar].
+#[derive(Debug, Clone)]
+pub struct LrTable {
+    terminals: Vec<String>,
+    /// (lhs, rhs length) for each production, including the augmented one.
+    productions: Vec<(usize, usize)>,
+    actions: Vec<BTreeMap<usize, Action>>,
+    gotos: Vec<BTreeMap<usize, usize>>,
+    conflicts: Vec<Conflict>,
+}
+
+impl LrTable {
+    /// Build a table of kind `kind` from `grammar`, returning an error if the
+    /// grammar is invalid. Conflicts don't cause an error; they are resolved
+    /// and reported through [LrTable::conflicts].
+    pub fn build(grammar: &Grammar, kind: LrKind) -> crate::Result<Self> {
+        grammar.validate()?;
+
+        let terminals: Vec<String> =
+            grammar.terminals().into_iter().map(str::to_owned).collect();
+        let mut nonterminals: Vec<&str> =
+            grammar.nonterminals().into_iter().collect();
+        let end = terminals.len();
+        // the augmented start symbol S' shares the start symbol's name, but
+        // name lookups always find the real start symbol first
+        let augmented_nt = nonterminals.len();
+        nonterminals.push(grammar.start());
+
+        let term_id = |name: &str| terminals.iter().position(|t| t == name);
+        let nt_id = |name: &str| nonterminals.iter().position(|n| *n == name);
+
+        // the augmented production S' -> S goes last so that production
+        // indices match the grammar's
+        let mut prods: Vec<(usize, Vec<Sym>)> = grammar
+            .productions()
+            .iter()
+            .map(|production| {
+                let rhs = production
+                    .rhs
+                    .iter()
+                    .map(|symbol| match symbol {
+                        Symbol::Terminal(name) => {
+                            Sym::T(term_id(name).unwrap())
+                        }
+                        Symbol::NonTerminal(name) => {
+                            Sym::N(nt_id(name).unwrap())
+                        }
+                    })
+                    .collect();
+                (nt_id(&production.lhs).unwrap(), rhs)
+            })
+            .collect();
+        let augmented = prods.len();
+        prods.push((
+            augmented_nt,
+            vec![Sym::N(nt_id(grammar.start()).unwrap())],
+        ));
+
+        let nullable = grammar.nullable();
+        let nullable: Vec<bool> = nonterminals
+            .iter()
+            .map(|name| nullable.contains(name))
+            .collect();
+        let first_sets = grammar.first_sets();
+        let first: Vec<BTreeSet<usize>> = nonterminals
+            .iter()
+            .map(|name| {
+                first_sets
+                    .get(name)
+                    .map(|set| {
+                        set.iter().map(|t| term_id(t).unwrap()).collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let closure = |kernel: BTreeSet<Item>| -> BTreeSet<Item> {
+            let mut items = kernel;
+            let mut work: Vec<Item> = items.iter().copied().collect();
+            while let Some((prod, dot, la)) = work.pop() {
+                let rhs = &prods[prod].1;
+                let Some(&Sym::N(next)) = rhs.get(dot) else {
+                    continue;
+                };
+                // FIRST(β la) where β is the rest of the rhs after `next`
+                let mut lookaheads = BTreeSet::new();
+                let mut rest_nullable = true;
+                for sym in &rhs[dot + 1..] {
+                    match *sym {
+                        Sym::T(t) => {
+                            lookaheads.insert(t);
+                            rest_nullable = false;
+                        }
+                        Sym::N(n) => {
+                            lookaheads.extend(&first[n]);
+                            rest_nullable = nullable[n];
+                        }
+                    }
+                    if !rest_nullable {
+                        break;
+                    }
+                }
+                if rest_nullable {
+                    lookaheads.insert(la);
+                }
+                for (idx, (lhs, _)) in prods.iter().enumerate() {
+                    if *lhs != next {
+                        continue;
+                    }
+                    for &b in &lookaheads {
+                        if items.insert((idx, 0, b)) {
+                            work.push((idx, 0, b));
+                        }
+                    }
+                }
+            }
+            items
+        };
+
+        // canonical LR(1) collection
+        let mut states: Vec<BTreeSet<Item>> =
+            vec![closure(BTreeSet::from([(augmented, 0, end)]))];
+        let mut state_ids: BTreeMap<BTreeSet<Item>, usize> =
+            BTreeMap::from([(states[0].clone(), 0)]);
+        let mut transitions: Vec<BTreeMap<Sym, usize>> = Vec::new();
+        let mut i = 0;
+        while i < states.len() {
+            let mut kernels: BTreeMap<Sym, BTreeSet<Item>> = BTreeMap::new();
+            for &(prod, dot, la) in &states[i] {
+                if let Some(&sym) = prods[prod].1.get(dot) {
+                    kernels.entry(sym).or_default().insert((prod, dot + 1, la));
+                }
+            }
+            let mut edges = BTreeMap::new();
+            for (sym, kernel) in kernels {
+                let state = closure(kernel);
+                let id = match state_ids.get(&state) {
+                    Some(&id) => id,
+                    None => {
+                        states.push(state.clone());
+                        state_ids.insert(state, states.len() - 1);
+                        states.len() - 1
+                    }
+                };
+                edges.insert(sym, id);
+            }
+            transitions.push(edges);
+            i += 1;
+        }
+
+        if kind == LrKind::Lalr {
+            (states, transitions) = merge_cores(states, transitions);
+        }
+
+        let mut table = Self {
+            terminals,
+            productions: prods
+                .iter()
+                .map(|(lhs, rhs)| (*lhs, rhs.len()))
+                .collect(),
+            actions: vec![BTreeMap::new(); states.len()],
+            gotos: vec![BTreeMap::new(); states.len()],
+            conflicts: Vec::new(),
+        };
+        for (state, items) in states.iter().enumerate() {
+            for (&sym, &target) in &transitions[state] {
+                match sym {
+                    Sym::T(t) => {
+                        table.set_action(state, t, Action::Shift(target))
+                    }
+                    Sym::N(n) => {
+                        table.gotos[state].insert(n, target);
+                    }
+                }
+            }
+            for &(prod, dot, la) in items {
+                if dot < prods[prod].1.len() {
+                    continue;
+                }
+                if prod == augmented {
+                    table.set_action(state, la, Action::Accept);
+                } else {
+                    table.set_action(state, la, Action::Reduce(prod));
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    fn set_action(&mut self, state: usize, terminal: usize, action: Action) {
+        let Some(&existing) = self.actions[state].get(&terminal) else {
+            self.actions[state].insert(terminal, action);
+            return;
+        };
+        if existing == action {
+            return;
+        }
+        let (kind, chosen, discarded) = match (existing, action) {
+            (Action::Accept, _) => {
+                (ConflictKind::AcceptReduce, existing, action)
+            }
+            (_, Action::Accept) => {
+                (ConflictKind::AcceptReduce, action, existing)
+            }
+            (Action::Shift(_), _) => {
+                (ConflictKind::ShiftReduce, existing, action)
+            }
+            (_, Action::Shift(_)) => {
+                (ConflictKind::ShiftReduce, action, existing)
+            }
+            (Action::Reduce(a), Action::Reduce(b)) if b < a => {
+                (ConflictKind::ReduceReduce, action, existing)
+            }
+            _ => (ConflictKind::ReduceReduce, existing, action),
+        };
+        self.actions[state].insert(terminal, chosen);
+        self.conflicts.push(Conflict {
+            kind,
+            state,
+            terminal: self.terminals.get(terminal).cloned(),
+            chosen,
+            discarded,
+        });
+    }
+
+    /// Borrow the conflicts that were resolved while building the table.
+    pub fn conflicts(&self) -> &[Conflict] {
+        &self.conflicts
+    }
+
+    /// Get the number of states in the table.
+    pub fn state_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    fn terminal_id(&self, name: &str) -> Option<usize> {
+        self.terminals.iter().position(|t| t == name)
+    }
+
+    fn expected(&self, state: usize) -> String {
+        self.actions[state]
+            .keys()
+            .map(|&t| match self.terminals.get(t) {
+                Some(name) => format!("`{name}`"),
+                None => "end of input".to_owned(),
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Merge LR(1) states that share a core (their items without lookaheads) to
+/// produce the LALR(1) collection.
+fn merge_cores(
+    states: Vec<BTreeSet<Item>>,
+    transitions: Vec<BTreeMap<Sym, usize>>,
+) -> (Vec<BTreeSet<Item>>, Vec<BTreeMap<Sym, usize>>) {
+    let mut core_ids: BTreeMap<BTreeSet<(usize, usize)>, usize> =
+        BTreeMap::new();
+    let mut remap = Vec::with_capacity(states.len());
+    let mut merged: Vec<BTreeSet<Item>> = Vec::new();
+    for state in states {
+        let core = state.iter().map(|&(prod, dot, _)| (prod, dot)).collect();
+        let id = *core_ids.entry(core).or_insert_with(|| {
+            merged.push(BTreeSet::new());
+            merged.len() - 1
+        });
+        merged[id].extend(state);
+        remap.push(id);
+    }
+    let mut merged_transitions = vec![BTreeMap::new(); merged.len()];
+    for (state, edges) in transitions.into_iter().enumerate() {
+        for (sym, target) in edges {
+            merged_transitions[remap[state]].insert(sym, remap[target]);
+        }
+    }
+    (merged, merged_transitions)
+}
+
+/// Represents a table-driven shift-reduce parser that parses values of type
+/// `V` from a queue of tokens with type `L`.
+pub struct LrParser<L, V> {
+    table: LrTable,
+    classify: ClassifyFn<L>,
+    shift: ShiftFn<L, V>,
+    reductions: Vec<Option<ReduceFn<V>>>,
+}
+
+impl<L, V> LrParser<L, V> {
+    /// Create a parser driven by `table`. `classify` maps each token to its
+    /// terminal and `shift` converts shifted tokens into values.
+    pub fn new(
+        table: LrTable,
+        classify: ClassifyFn<L>,
+        shift: ShiftFn<L, V>,
+    ) -> Self {
+        let reductions = vec![None; table.productions.len()];
+        Self {
+            table,
+            classify,
+            shift,
+            reductions,
+        }
+    }
+
+    /// Set the callback invoked when reducing by the production with index
+    /// `production`. Productions without a callback pass through the value
+    /// of a single-symbol right hand side, and fail otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `production` isn't the index of a production of the
+    /// grammar.
+    pub fn on_reduce(&mut self, production: usize, reduce_fn: ReduceFn<V>) {
+        self.reductions[production] = Some(reduce_fn);
+    }
+
+    /// Borrow the table driving the parser.
+    pub fn table(&self) -> &LrTable {
+        &self.table
+    }
+
+    /// Parse a value of type `V` from the token queue, consuming tokens until
+    /// the input is accepted at the end of the queue.
+    pub fn parse(&self, tq: &TokenQueue<L>) -> ParseResult<V> {
+        self.run(tq, None)
+    }
+
+    /// Parse a value like [LrParser::parse], recording each production
+    /// reduced by in `coverage`, which must be of the grammar the table was
+    /// built from.
+    pub fn parse_with_coverage(
+        &self,
+        tq: &TokenQueue<L>,
+        coverage: &mut Coverage,
+    ) -> ParseResult<V> {
+        self.run(tq, Some(coverage))
+    }
+
+    fn run(
+        &self,
+        tq: &TokenQueue<L>,
+        mut coverage: Option<&mut Coverage>,
+    ) -> ParseResult<V> {
+        let mut tq = tq.clone();
+        let mut states = vec![0];
+        let mut values: Vec<V> = Vec::new();
+        loop {
+            let state = *states.last().unwrap();
+            let lookahead = match tq.is_consumed() {
+                true => self.table.terminals.len(),
+                false => {
+                    let name = (self.classify)(tq.peek()?);
+                    self.table.terminal_id(name).ok_or_else(|| {
+                        ParseError::at(
+                            tq.get_idx(),
+                            format!(
+                                "Token classified as unknown terminal \
+                                 `{name}`!"
+                            ),
+                        )
+                    })?
+                }
+            };
+            let action = self.table.actions[state].get(&lookahead).copied();
+            match action {
+                Some(Action::Shift(target)) => {
+                    values.push((self.shift)(tq.consume()?));
+                    states.push(target);
+                }
+                Some(Action::Reduce(production)) => {
+                    if let Some(coverage) = coverage.as_deref_mut() {
+                        coverage.record(production);
+                    }
+                    let (lhs, len) = self.table.productions[production];
+                    states.truncate(states.len() - len);
+                    let children = values.split_off(values.len() - len);
+                    let value = match self.reductions[production] {
+                        Some(reduce_fn) => reduce_fn(children)?,
+                        None if len == 1 => {
+                            children.into_iter().next().unwrap()
+                        }
+                        None => {
+                            return Err(ParseError::at(
+                                tq.get_idx(),
+                                format!(
+                                    "No reduce callback for production \
+                                     {production}!"
+                                ),
+                            ));
+                        }
+                    };
+                    let state = *states.last().unwrap();
+                    states.push(self.table.gotos[state][&lhs]);
+                    values.push(value);
+                }
+                Some(Action::Accept) => {
+                    return Ok((values.pop().unwrap(), tq.get_idx()));
+                }
+                None => {
+                    let found = match self.table.terminals.get(lookahead) {
+                        Some(name) => format!("`{name}`"),
+                        None => "end of input".to_owned(),
+                    };
+                    return Err(ParseError::at(
+                        tq.get_idx(),
+                        format!(
+                            "Unexpected {found}, expected one of: {}",
+                            self.table.expected(state)
+                        ),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grammar::{Grammar, Symbol};
+    use crate::lr::{Action, ConflictKind, LrKind, LrParser, LrTable};
+    use crate::parse::TokenQueue;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token {
+        Num(i64),
+        Add,
+        Mul,
+        LParen,
+        RParen,
+    }
+
+    fn classify(token: &Token) -> &str {
+        match token {
+            Token::Num(_) => "num",
+            Token::Add => "+",
+            Token::Mul => "*",
+            Token::LParen => "(",
+            Token::RParen => ")",
+        }
+    }
+
+    fn shift(token: &Token) -> i64 {
+        match token {
+            Token::Num(val) => *val,
+            _ => 0,
+        }
+    }
+
+    fn setup_parser(kind: LrKind) -> anyhow::Result<LrParser<Token, i64>> {
+        let mut grammar = Grammar::new("expr");
+        let add = grammar.add_production(
+            "expr",
+            vec![Symbol::nt("expr"), Symbol::t("+"), Symbol::nt("term")],
+        );
+        grammar.add_production("expr", vec![Symbol::nt("term")]);
+        let mul = grammar.add_production(
+            "term",
+            vec![Symbol::nt("term"), Symbol::t("*"), Symbol::nt("factor")],
+        );
+        grammar.add_production("term", vec![Symbol::nt("factor")]);
+        let paren = grammar.add_production(
+            "factor",
+            vec![Symbol::t("("), Symbol::nt("expr"), Symbol::t(")")],
+        );
+        grammar.add_production("factor", vec![Symbol::t("num")]);
+
+        let table = LrTable::build(&grammar, kind)?;
+        assert!(table.conflicts().is_empty());
+
+        let mut parser = LrParser::new(table, classify, shift);
+        parser.on_reduce(add, |vals| Ok(vals[0] + vals[2]));
+        parser.on_reduce(mul, |vals| Ok(vals[0] * vals[2]));
+        parser.on_reduce(paren, |vals| Ok(vals[1]));
+        Ok(parser)
+    }
+
+    #[test]
+    fn test_lr_parse() -> anyhow::Result<()> {
+        for kind in [LrKind::Lr1, LrKind::Lalr] {
+            let parser = setup_parser(kind)?;
+
+            // 2 + 3 * (4 + 1)
+            let tq = TokenQueue::from(vec![
+                Token::Num(2),
+                Token::Add,
+                Token::Num(3),
+                Token::Mul,
+                Token::LParen,
+                Token::Num(4),
+                Token::Add,
+                Token::Num(1),
+                Token::RParen,
+            ]);
+            assert!(parser.parse(&tq)?.0 == 17);
+
+            let tq = TokenQueue::from(vec![Token::Num(2), Token::Add]);
+            assert!(parser.parse(&tq).is_err());
+        }
+
+        assert!(
+            setup_parser(LrKind::Lalr)?.table().state_count()
+                < setup_parser(LrKind::Lr1)?.table().state_count()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conflicts() -> anyhow::Result<()> {
+        let mut grammar = Grammar::new("expr");
+        grammar.add_production(
+            "expr",
+            vec![Symbol::nt("expr"), Symbol::t("+"), Symbol::nt("expr")],
+        );
+        grammar.add_production("expr", vec![Symbol::t("num")]);
+
+        let table = LrTable::build(&grammar, LrKind::Lalr)?;
+        assert!(!table.conflicts().is_empty());
+        assert!(
+            table
+                .conflicts()
+                .iter()
+                .all(|conflict| conflict.kind == ConflictKind::ShiftReduce)
+        );
+
+        // `s -> s` can always be reduced where the input could be accepted
+        let mut grammar = Grammar::new("s");
+        grammar.add_production("s", vec![Symbol::nt("s")]);
+        grammar.add_production("s", vec![Symbol::t("x")]);
+        for kind in [LrKind::Lalr, LrKind::Lr1] {
+            let table = LrTable::build(&grammar, kind)?;
+            let conflict = table
+                .conflicts()
+                .iter()
+                .find(|conflict| conflict.kind == ConflictKind::AcceptReduce)
+                .unwrap();
+            assert!(conflict.chosen == Action::Accept);
+            assert!(conflict.discarded == Action::Reduce(0));
+            assert!(conflict.to_string().starts_with("accept-reduce conflict"));
+        }
+
+        Ok(())
+    }
+}
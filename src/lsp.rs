@@ -0,0 +1,220 @@
+//! Conversion of [Diagnostic]s to Language Server Protocol diagnostics.
+//!
+//! The types here mirror the protocol's JSON shapes and the field names used
+//! by the `lsp-types` crate, without depending on it. Rather than mapping
+//! them field by field, a language server can send the JSON written by
+//! [Diagnostic::to_json] or [publish_diagnostics_params] as is, or
+//! deserialize it into `lsp_types::Diagnostic` with `serde_json`.
+
+use crate::diagnostic::{Diagnostic as RlrlDiagnostic, Severity};
+use crate::json::json_string;
+use crate::source::SourceFile;
+use crate::span::Span;
+
+/// A 0-based line and UTF-16 column in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Position {
+    pub line: u32,
+    pub character: u32,
+}
+
+/// A range of a document from `start` up to `end`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+/// The severity of a [Diagnostic], numbered as in the protocol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiagnosticSeverity {
+    Error = 1,
+    Warning = 2,
+    Information = 3,
+    Hint = 4,
+}
+
+impl From<Severity> for DiagnosticSeverity {
+    fn from(value: Severity) -> Self {
+        match value {
+            Severity::Error => Self::Error,
+            Severity::Warning => Self::Warning,
+            Severity::Note => Self::Information,
+        }
+    }
+}
+
+/// A diagnostic as published to an editor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub range: Range,
+    pub severity: Option<DiagnosticSeverity>,
+    pub code: Option<String>,
+    /// The tool that produced the diagnostic, shown by some editors.
+    pub source: Option<String>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    /// Set the name of the tool that produced the diagnostic.
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Write the diagnostic as a protocol `Diagnostic` JSON object, leaving
+    /// out the fields that are `None`.
+    pub fn to_json(&self) -> String {
+        let mut out = format!("{{\"range\":{}", self.range.to_json());
+        if let Some(severity) = self.severity {
+            out.push_str(&format!(",\"severity\":{}", severity as u8));
+        }
+        if let Some(code) = &self.code {
+            out.push_str(&format!(",\"code\":{}", json_string(code)));
+        }
+        if let Some(source) = &self.source {
+            out.push_str(&format!(",\"source\":{}", json_string(source)));
+        }
+        out.push_str(&format!(",\"message\":{}}}", json_string(&self.message)));
+        out
+    }
+}
+
+impl Position {
+    /// Write the position as a protocol `Position` JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"line\":{},\"character\":{}}}",
+            self.line, self.character
+        )
+    }
+}
+
+impl Range {
+    /// Write the range as a protocol `Range` JSON object.
+    pub fn to_json(&self) -> String {
+        format!(
+            "{{\"start\":{},\"end\":{}}}",
+            self.start.to_json(),
+            self.end.to_json()
+        )
+    }
+}
+
+/// Write the params of a `textDocument/publishDiagnostics` notification
+/// replacing the diagnostics of the document at `uri` with `diagnostics`.
+pub fn publish_diagnostics_params(
+    uri: &str,
+    diagnostics: &[Diagnostic],
+) -> String {
+    let diagnostics: Vec<String> =
+        diagnostics.iter().map(Diagnostic::to_json).collect();
+    format!(
+        "{{\"uri\":{},\"diagnostics\":[{}]}}",
+        json_string(uri),
+        diagnostics.join(",")
+    )
+}
+
+impl SourceFile {
+    /// Get the protocol position of the byte `offset`.
+    pub fn lsp_position(&self, offset: usize) -> Position {
+        let (line, character) = self.utf16_line_col(offset);
+        Position {
+            line: line as u32,
+            character: character as u32,
+        }
+    }
+
+    /// Get the protocol range of the byte span `span`.
+    pub fn lsp_range(&self, span: Span) -> Range {
+        Range {
+            start: self.lsp_position(span.start),
+            end: self.lsp_position(span.end),
+        }
+    }
+}
+
+impl RlrlDiagnostic {
+    /// Convert the diagnostic to a protocol diagnostic, with its span
+    /// converted to a range in `source`. Notes and the messages of
+    /// suggestions are appended to the message on lines of their own.
+    pub fn to_lsp(&self, source: &SourceFile) -> Diagnostic {
+        let mut message = self.message.clone();
+        for note in &self.notes {
+            message.push_str(&format!("\n{}: {note}", Severity::Note));
+        }
+        for suggestion in &self.suggestions {
+            message.push_str(&format!("\nhelp: {}", suggestion.message));
+        }
+        Diagnostic {
+            range: source.lsp_range(self.span),
+            severity: Some(self.severity.into()),
+            code: self.code.clone(),
+            source: None,
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::lsp::{
+        DiagnosticSeverity, Position, Range, publish_diagnostics_params,
+    };
+    use crate::source::SourceFile;
+    use crate::span::Span;
+
+    #[test]
+    fn test_to_lsp() {
+        let source = SourceFile::new("test.cfg", "a = 1\né = x\n");
+        let diagnostic =
+            Diagnostic::warning("unknown value", Span::new(11, 12))
+                .with_note("values are numbers");
+        let lsp = diagnostic.to_lsp(&source).with_source("cfg");
+
+        assert!(
+            lsp.range
+                == Range {
+                    start: Position {
+                        line: 1,
+                        character: 4
+                    },
+                    end: Position {
+                        line: 1,
+                        character: 5
+                    },
+                }
+        );
+        assert!(lsp.severity == Some(DiagnosticSeverity::Warning));
+        assert!(lsp.message == "unknown value\nnote: values are numbers");
+        assert!(lsp.source.as_deref() == Some("cfg"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let source = SourceFile::new("test.cfg", "a = 1\né = x\n");
+        let warning = Diagnostic::warning("unknown value", Span::new(11, 12))
+            .with_code("W0001")
+            .to_lsp(&source)
+            .with_source("cfg");
+        let error = Diagnostic::error("expected \"=\"", Span::new(0, 1))
+            .to_lsp(&source);
+
+        assert!(
+            warning.to_json()
+                == "{\"range\":{\"start\":{\"line\":1,\"character\":4},\
+                    \"end\":{\"line\":1,\"character\":5}},\"severity\":2,\
+                    \"code\":\"W0001\",\"source\":\"cfg\",\
+                    \"message\":\"unknown value\"}"
+        );
+        assert!(
+            publish_diagnostics_params("file:///test.cfg", &[error])
+                == "{\"uri\":\"file:///test.cfg\",\"diagnostics\":[\
+                    {\"range\":{\"start\":{\"line\":0,\"character\":0},\
+                    \"end\":{\"line\":0,\"character\":1}},\"severity\":1,\
+                    \"message\":\"expected \\\"=\\\"\"}]}"
+        );
+    }
+}
@@ -1,10 +1,27 @@
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lex::LexError;
+use crate::sink::ErrorSink;
+use crate::span::{HasSpan, Span, Spanned};
+use crate::trace::{EventKind, ParseEvent, ParseTrace};
+use std::cell::{Cell, RefCell};
 use std::cmp::min;
-use std::fmt::Debug;
+use std::error::Error;
+use std::fmt::{Debug, Display};
+use std::ops::{Index, Range};
 use std::rc::Rc;
+use std::sync::Arc;
 
 const TOKEN_QUEUE_EMPTY_MSG: &str = "Couldn't get token from empty TokenQueue!";
 const TOKEN_DID_NOT_MATCH_MSG: &str = "Token didn't match required format!";
 const COULD_NOT_READ_PREV_MSG: &str = "Couldn't read prev token in TokenQueue.";
+const NESTING_TOO_DEEP_MSG: &str = "Nesting too deep!";
+const FUEL_EXHAUSTED_MSG: &str = "Ran out of fuel while parsing!";
+const TOO_MANY_ERRORS_MSG: &str = "Too many errors, giving up!";
+const NO_PROGRESS_MSG: &str = "Parse loop made no progress!";
+const MISSING_SEPARATOR_MSG: &str = "Expected a separator after list item!";
+
+/// The default maximum number of nested parse driver calls.
+pub const DEFAULT_MAX_DEPTH: usize = 128;
 
 /// A function that parses an item of type `T` from a queue of tokens with type
 /// `L`
@@ -14,75 +31,489 @@ pub type ParseWithFn<L, C, T> = fn(&TokenQueue<L>, &C) -> ParseResult<T>;
 
 pub type ParseWithMutFn<L, C, T> = fn(&TokenQueue<L>, &mut C) -> ParseResult<T>;
 
+/// Result type returned by the parsing API, with a [ParseError] by default.
+pub type Result<T, E = ParseError> = std::result::Result<T, E>;
+
 /// Convenience type to return from parse functions
-pub type ParseResult<T> = anyhow::Result<(T, usize)>;
+pub type ParseResult<T> = Result<(T, usize)>;
+
+/// Implemented by types that can be parsed from a queue of tokens with type
+/// `L`, so they can be parsed with `tq.parse(T::parse)` and used as fields
+/// of types that derive `Parse`.
+///
+/// With the `derive` feature, `#[derive(Parse)]` implements this for structs
+/// by parsing their fields in order, and for enums by trying their variants
+/// in order. See the `rlrl-derive` crate for its
+/// attributes.
+pub trait Parse<L>: Sized {
+    /// Parse a value from the front of the queue, like a [ParseFn].
+    fn parse(tq: &TokenQueue<L>) -> ParseResult<Self>;
+}
+
+#[cfg(feature = "derive")]
+pub use rlrl_derive::Parse;
+
+/// Whether a separated list may (or must) end with a separator, for
+/// [TokenQueue::parse_separated].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Trailing {
+    /// `a, b` but not `a, b,` (JSON).
+    #[default]
+    Forbid,
+    /// `a, b` or `a, b,` (Rust).
+    Allow,
+    /// `a; b;` but not `a; b` (statements terminated by `;`).
+    Require,
+}
+
+impl Trailing {
+    fn allowed(&self) -> bool {
+        *self != Self::Forbid
+    }
+}
+
+/// Options for [TokenQueue::parse_separated]. The default forbids trailing
+/// separators and allows empty lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Separated {
+    trailing: Trailing,
+    min: usize,
+}
+
+impl Separated {
+    /// Set whether the list may end with a separator.
+    pub fn with_trailing(mut self, trailing: Trailing) -> Self {
+        self.trailing = trailing;
+        self
+    }
+
+    /// Set the minimum number of values in the list.
+    pub fn with_min(mut self, min: usize) -> Self {
+        self.min = min;
+        self
+    }
+}
+
+/// The result of [TokenQueue::parse_input], telling apart input that's wrong
+/// from input that's unfinished, so a REPL knows whether to report an error
+/// or prompt for more.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseStatus<T> {
+    /// All of the input was parsed.
+    Complete(T),
+    /// Parsing failed at the end of the input, so more tokens might have
+    /// made it valid, like a missing `)`.
+    Incomplete(ParseError),
+    /// Parsing failed before the end of the input, or left tokens over.
+    Error(ParseError),
+}
+
+/// Represents an error encountered while parsing, optionally at the index of
+/// the token that caused it.
+///
+/// Errors from elsewhere (a lexer, a semantic check in a parse function) can
+/// be wrapped with [ParseError::wrap], or converted with `?` from [LexError]
+/// and [anyhow::Error].
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    message: String,
+    idx: Option<usize>,
+    /// Labels of the constructs being parsed when the error occurred,
+    /// innermost first.
+    contexts: Vec<String>,
+    /// The wrapped error this error was created from, if any.
+    cause: Option<Arc<dyn Error + Send + Sync>>,
+    /// Whether the error is from a construct parsing committed to, so
+    /// alternatives around it mustn't be tried.
+    cut: bool,
+}
+
+impl ParseError {
+    /// Create an error with the message `message`.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            idx: None,
+            contexts: Vec::new(),
+            cause: None,
+            cut: false,
+        }
+    }
+
+    /// Create an error with the message `message` at token index `idx`.
+    pub fn at(idx: usize, message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+            idx: Some(idx),
+            contexts: Vec::new(),
+            cause: None,
+            cut: false,
+        }
+    }
+
+    /// Create an error wrapping the error `err`, taking its message.
+    pub fn wrap(err: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        let cause: Box<dyn Error + Send + Sync> = err.into();
+        Self {
+            message: cause.to_string(),
+            idx: None,
+            contexts: Vec::new(),
+            cause: Some(cause.into()),
+            cut: false,
+        }
+    }
+
+    /// Create an error at the front of `tq` saying `what` was expected,
+    /// quoting the tokens found there instead, and the tokens consumed just
+    /// before if `tq` was created
+    /// [with error context](TokenQueue::with_error_context).
+    pub fn expected<T: Debug>(what: impl Display, tq: &TokenQueue<T>) -> Self {
+        let mut message = match tq.is_consumed() {
+            true => format!("Expected {what} but reached the end of input"),
+            false => format!("Expected {what} but found `{tq:?}`"),
+        };
+        let recent = tq.recent(tq.error_context);
+        if !recent.is_empty() {
+            let recent: Vec<String> =
+                recent.iter().map(|token| format!("{token:?}")).collect();
+            message.push_str(&format!(" after `{}`", recent.join(" ")));
+        }
+        message.push('!');
+        Self::at(tq.get_idx(), message)
+    }
+
+    /// Borrow the error message.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Get the index of the token that caused the error, if known.
+    pub fn idx(&self) -> Option<usize> {
+        self.idx
+    }
+
+    /// Record that the error occurred while parsing the construct labelled
+    /// `label`, outside any constructs already recorded.
+    pub fn in_context(mut self, label: impl Into<String>) -> Self {
+        self.contexts.push(label.into());
+        self
+    }
+
+    /// Borrow the labels of the constructs being parsed when the error
+    /// occurred, innermost first.
+    pub fn contexts(&self) -> &[String] {
+        &self.contexts
+    }
+
+    /// Mark the error as cut, as if it was returned after
+    /// [TokenQueue::cut], so alternation stops at it.
+    pub fn cut(mut self) -> Self {
+        self.cut = true;
+        self
+    }
+
+    /// Return true if the error is cut: it's from a construct parsing
+    /// committed to, so it's returned as is by alternation
+    /// ([TokenQueue::one_of], derived enums, ...) instead of trying other
+    /// alternatives, and by parse functions that stop at errors, like
+    /// [TokenQueue::parse_separated], instead of stopping.
+    pub fn is_cut(&self) -> bool {
+        self.cut
+    }
+
+    /// Borrow the error this error wraps, if it was created with
+    /// [ParseError::wrap], so it can be downcast.
+    pub fn cause(&self) -> Option<&(dyn Error + Send + Sync + 'static)> {
+        self.cause.as_deref()
+    }
+}
+
+/// Errors compare equal by message, index, and contexts, whether cut or not.
+impl PartialEq for ParseError {
+    fn eq(&self, other: &Self) -> bool {
+        self.message == other.message
+            && self.idx == other.idx
+            && self.contexts == other.contexts
+    }
+}
+
+impl Eq for ParseError {}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.idx {
+            Some(idx) => write!(f, "{} (at token {idx})", self.message)?,
+            None => write!(f, "{}", self.message)?,
+        }
+        for (i, context) in self.contexts.iter().rev().enumerate() {
+            match i {
+                0 => write!(f, " while parsing {context}")?,
+                _ => write!(f, " → {context}")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        // the cause's own message is this error's message, so skip to its
+        // source rather than reporting the same message twice
+        self.cause.as_ref().and_then(|cause| cause.source())
+    }
+}
+
+impl From<LexError> for ParseError {
+    fn from(value: LexError) -> Self {
+        Self::wrap(value)
+    }
+}
+
+impl From<anyhow::Error> for ParseError {
+    fn from(value: anyhow::Error) -> Self {
+        Self::wrap(value)
+    }
+}
+
+/// The errors of each alternative tried at the same point, combined into a
+/// single error by [TokenQueue::one_of]. It displays as "Expected one of X,
+/// Y, Z!", listing every alternative's label, and converts into a
+/// [ParseError] whose message is chosen by how far each alternative got
+/// (see [AltError::primary]), and whose [cause](ParseError::cause) is the
+/// `AltError` itself, so it can be downcast to list the alternatives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AltError {
+    start: usize,
+    alternatives: Vec<(String, ParseError)>,
+}
+
+impl AltError {
+    /// Create an error for alternatives tried from token index `start`.
+    pub fn new(start: usize) -> Self {
+        Self {
+            start,
+            alternatives: Vec::new(),
+        }
+    }
+
+    /// Record that the alternative labelled `label` failed with `err`.
+    /// Labels are shown as given, so quote tokens like "`let`".
+    pub fn push(&mut self, label: impl Into<String>, err: ParseError) {
+        self.alternatives.push((label.into(), err));
+    }
+
+    /// Borrow the label and error of each alternative, in the order tried.
+    pub fn alternatives(&self) -> &[(String, ParseError)] {
+        &self.alternatives
+    }
+
+    /// Choose the error to report: an alternative that got further than the
+    /// others has most likely failed on the construct the input was meant to
+    /// be, so if one alternative failed furthest past the start, its error
+    /// is reported. If several tied there, the error lists just their
+    /// labels, and if none got past the start it lists all of them.
+    pub fn primary(&self) -> ParseError {
+        let farthest = self
+            .alternatives
+            .iter()
+            .filter_map(|(_, err)| err.idx)
+            .max()
+            .filter(|idx| *idx > self.start);
+        let Some(farthest) = farthest else {
+            return ParseError::at(self.start, self.to_string());
+        };
+        let at_farthest: Vec<&(String, ParseError)> = self
+            .alternatives
+            .iter()
+            .filter(|(_, err)| err.idx == Some(farthest))
+            .collect();
+        match at_farthest[..] {
+            [(_, err)] => err.clone(),
+            _ => ParseError::at(
+                farthest,
+                expected_one_of(at_farthest.iter().map(|(label, _)| label)),
+            ),
+        }
+    }
+}
+
+/// Format "Expected one of X, Y, Z!" listing `labels`.
+fn expected_one_of<'a>(labels: impl Iterator<Item = &'a String>) -> String {
+    let labels: Vec<&str> = labels.map(String::as_str).collect();
+    format!("Expected one of {}!", labels.join(", "))
+}
+
+impl Display for AltError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let labels = self.alternatives.iter().map(|(label, _)| label);
+        write!(f, "{}", expected_one_of(labels))
+    }
+}
+
+impl Error for AltError {}
+
+impl From<AltError> for ParseError {
+    fn from(value: AltError) -> Self {
+        let mut err = value.primary();
+        err.cause = Some(Arc::new(value));
+        err
+    }
+}
 
 /// Wrapper around `Vec<T>` exposing the functionality needed for
 /// parsing.
-#[derive(Clone)]
 pub struct TokenQueue<T> {
     tokens: Rc<Vec<T>>,
     idx: usize,
+    depth: usize,
+    max_depth: usize,
+    /// How many consumed tokens errors quote before the error.
+    error_context: usize,
+    /// Remaining parse steps, shared between clones of the queue.
+    fuel: Option<Rc<Cell<usize>>>,
+    /// Returns true for the tokens designated as newlines.
+    newline: Option<fn(&T) -> bool>,
+    /// Whether newline tokens are seen by parse functions or skipped.
+    newlines_significant: bool,
+    /// Warnings emitted by parse functions, shared between clones of the
+    /// queue.
+    warnings: Rc<RefCell<Vec<Diagnostic>>>,
+    /// How many warnings can be emitted before parsing gives up.
+    max_diagnostics: Option<usize>,
+    /// Whether parsing gave up after too many warnings, shared between
+    /// clones of the queue.
+    given_up: Rc<Cell<bool>>,
+    /// The error at the greatest index from any parse driver call in the
+    /// current top-level parse, shared between clones of the queue.
+    farthest: Rc<RefCell<Option<ParseError>>>,
+    /// The depths of the parse driver calls in progress that [TokenQueue::cut]
+    /// was called in, shared between clones of the queue.
+    cuts: Rc<RefCell<Vec<usize>>>,
+    /// The events recorded if tracing, shared between clones of the queue.
+    trace: Option<Rc<RefCell<Vec<ParseEvent>>>>,
+}
+
+impl<T> Clone for TokenQueue<T> {
+    fn clone(&self) -> Self {
+        Self {
+            tokens: Rc::clone(&self.tokens),
+            idx: self.idx,
+            depth: self.depth,
+            max_depth: self.max_depth,
+            error_context: self.error_context,
+            fuel: self.fuel.clone(),
+            newline: self.newline,
+            newlines_significant: self.newlines_significant,
+            warnings: Rc::clone(&self.warnings),
+            max_diagnostics: self.max_diagnostics,
+            given_up: Rc::clone(&self.given_up),
+            farthest: Rc::clone(&self.farthest),
+            cuts: Rc::clone(&self.cuts),
+            trace: self.trace.clone(),
+        }
+    }
 }
 
 impl<T> TokenQueue<T> {
     /// Borrow the front token from the queue.
-    pub fn peek(&self) -> anyhow::Result<&T> {
+    pub fn peek(&self) -> Result<&T> {
+        self.record(|| EventKind::Peek { idx: self.front() });
         self.tokens
-            .get(self.idx)
-            .ok_or(anyhow::anyhow!(TOKEN_QUEUE_EMPTY_MSG))
+            .get(self.front())
+            .ok_or_else(|| ParseError::at(self.idx, TOKEN_QUEUE_EMPTY_MSG))
     }
 
     /// Consume the front token in the queue.
-    pub fn consume(&mut self) -> anyhow::Result<&T> {
+    pub fn consume(&mut self) -> Result<&T> {
         self.increment()?;
         self.prev()
     }
 
     /// Borrow the front token if it returns `true` when passed to `f`,
-    /// otherwise return an error.
-    pub fn peek_matching(&self, f: fn(&T) -> bool) -> anyhow::Result<&T> {
+    /// otherwise return an error naming the token.
+    pub fn peek_matching(&self, f: fn(&T) -> bool) -> Result<&T>
+    where
+        T: Debug,
+    {
         let token = self.peek()?;
         if !f(token) {
-            return Err(anyhow::anyhow!(TOKEN_DID_NOT_MATCH_MSG));
+            return Err(self.unexpected());
         }
         Ok(token)
     }
 
     /// Borrow the last token consumed.
-    pub fn prev(&self) -> anyhow::Result<&T> {
+    pub fn prev(&self) -> Result<&T> {
         self.tokens
             .get(self.idx - 1)
-            .ok_or(anyhow::anyhow!(COULD_NOT_READ_PREV_MSG))
+            .ok_or_else(|| ParseError::at(self.idx, COULD_NOT_READ_PREV_MSG))
     }
 
     /// Consume the front token if it returns `true` when passed to `f`,
-    /// otherwise return an error.
-    pub fn consume_matching(
-        &mut self,
-        f: fn(&T) -> bool,
-    ) -> anyhow::Result<&T> {
-        if !self.peek().map_or(false, f) {
-            return Err(anyhow::anyhow!(TOKEN_DID_NOT_MATCH_MSG));
+    /// otherwise return an error naming the token.
+    pub fn consume_matching(&mut self, f: fn(&T) -> bool) -> Result<&T>
+    where
+        T: Debug,
+    {
+        if !self.peek().is_ok_and(f) {
+            return Err(self.unexpected());
         }
         self.increment()?;
-        Ok(self.prev()?)
+        self.prev()
+    }
+
+    /// Create an error at the front of the queue naming the front token,
+    /// with its `Debug` output, as unexpected.
+    fn unexpected(&self) -> ParseError
+    where
+        T: Debug,
+    {
+        let message = match self.peek() {
+            Ok(token) => format!("Unexpected `{token:?}`!"),
+            Err(_) => "Unexpected end of input!".to_owned(),
+        };
+        ParseError::at(self.idx, message)
+    }
+
+    /// Consume the front token if `f` projects a value out of it, returning
+    /// the value. If `f` returns `None` (or the queue is empty), return a
+    /// [ParseError] saying `expected` was expected, without consuming.
+    pub fn consume_map<U>(
+        &mut self,
+        expected: &str,
+        f: fn(&T) -> Option<U>,
+    ) -> Result<U> {
+        match self.peek().ok().and_then(f) {
+            Some(val) => {
+                self.increment()?;
+                Ok(val)
+            }
+            None => {
+                Err(ParseError::at(self.idx, format!("Expected {expected}!")))
+            }
+        }
     }
 
     /// Return `Ok(())` if the index is valid in this token queue, else return
     /// an error.
-    pub fn validate_idx(&self, idx: usize) -> anyhow::Result<()> {
+    pub fn validate_idx(&self, idx: usize) -> Result<()> {
         if idx > self.tokens.len() {
-            return Err(anyhow::anyhow!("Prematurely reached end of input!"));
+            return Err(ParseError::at(
+                idx,
+                "Prematurely reached end of input!",
+            ));
         }
         Ok(())
     }
 
     /// Go to the next token by incrementing the index.
-    pub fn increment(&mut self) -> anyhow::Result<()> {
-        match self.validate_idx(self.idx + 1) {
+    pub fn increment(&mut self) -> Result<()> {
+        self.burn_fuel()?;
+        let next = self.front() + 1;
+        match self.validate_idx(next) {
             Ok(_) => {
-                self.idx += 1;
+                self.record(|| EventKind::Consume { idx: next - 1 });
+                self.idx = next;
                 Ok(())
             }
             Err(err) => Err(err),
@@ -90,9 +521,17 @@ impl<T> TokenQueue<T> {
     }
 
     /// Go to the token at position `i`.
-    pub fn go_to(&mut self, idx: usize) -> anyhow::Result<()> {
+    pub fn go_to(&mut self, idx: usize) -> Result<()> {
         match self.validate_idx(idx) {
             Ok(_) => {
+                #[cfg(feature = "tracing")]
+                if idx < self.idx {
+                    tracing::trace!(from = self.idx, to = idx, "backtrack");
+                }
+                if idx < self.idx {
+                    let from = self.idx;
+                    self.record(|| EventKind::Backtrack { from, to: idx });
+                }
                 self.idx = idx;
                 Ok(())
             }
@@ -107,7 +546,281 @@ impl<T> TokenQueue<T> {
 
     /// Return true when the token queue has no tokens left.
     pub fn is_consumed(&self) -> bool {
-        self.idx == self.tokens.len()
+        self.front() == self.tokens.len()
+    }
+
+    /// Borrow the tokens left in the queue. Slices are the input type of
+    /// combinator libraries like `nom` and `chumsky`, so their parsers can
+    /// run on the tokens in a queue; see [TokenQueue::parse_slice].
+    pub fn remaining(&self) -> &[T] {
+        &self.tokens[self.front()..]
+    }
+
+    /// Borrow the token at index `idx` of the whole stream, wherever the
+    /// queue is, for passes after parsing that relate tree nodes back to
+    /// tokens by index.
+    pub fn get(&self, idx: usize) -> Option<&T> {
+        self.tokens.get(idx)
+    }
+
+    /// Borrow the tokens at the indices in `range` of the whole stream, like
+    /// the tokens a node spans, or `None` if the range is out of bounds.
+    /// Newlines are included whether they're significant or not.
+    pub fn tokens_in(&self, range: Range<usize>) -> Option<&[T]> {
+        self.tokens.get(range)
+    }
+
+    /// Iterate over the tokens left in the queue without consuming them,
+    /// skipping newlines if they're insignificant, for quick lookahead with
+    /// iterator adapters like `position`.
+    pub fn tokens(&self) -> impl Iterator<Item = &T> {
+        let skipped = self.newline.filter(|_| !self.newlines_significant);
+        self.remaining().iter().filter(move |token| {
+            !skipped.is_some_and(|is_newline| is_newline(token))
+        })
+    }
+
+    /// Run a parser over the tokens left in the queue, then move past the
+    /// tokens it consumed. Like a `nom` parser, `f` returns the tokens it
+    /// didn't consume, which must end the slice it was given, along with its
+    /// output. Its error is converted to a [ParseError] at the front of the
+    /// queue, taking the error's message.
+    pub fn parse_slice<O, E: Display>(
+        &mut self,
+        f: impl FnOnce(&[T]) -> std::result::Result<(&[T], O), E>,
+    ) -> Result<O> {
+        let front = self.front();
+        let len = self.tokens.len() - front;
+        match f(&self.tokens[front..]) {
+            Ok((rest, output)) => {
+                self.go_to(front + len - min(rest.len(), len))?;
+                Ok(output)
+            }
+            Err(err) => Err(ParseError::at(front, err.to_string())),
+        }
+    }
+
+    /// Designate the tokens for which `is_newline` returns true as newlines.
+    /// Newlines are significant by default, so parse functions see them like
+    /// any other token, for example to separate statements. When they're
+    /// insignificant, they're skipped over as if they weren't in the queue.
+    /// See [TokenQueue::parse_with_newlines] to switch inside a region.
+    pub fn with_newline(mut self, is_newline: fn(&T) -> bool) -> Self {
+        self.newline = Some(is_newline);
+        self
+    }
+
+    /// Set whether newline tokens are significant. Has no effect unless
+    /// newlines were designated with [TokenQueue::with_newline].
+    pub fn with_newlines_significant(mut self, significant: bool) -> Self {
+        self.newlines_significant = significant;
+        self
+    }
+
+    /// Return true if newline tokens are seen by parse functions rather than
+    /// skipped.
+    pub fn newlines_significant(&self) -> bool {
+        self.newlines_significant
+    }
+
+    /// Consume any newline tokens at the front of the queue, like blank
+    /// lines between statements where newlines are significant.
+    pub fn skip_newlines(&mut self) -> Result<()> {
+        while let Some(is_newline) = self.newline {
+            match self.tokens.get(self.idx) {
+                Some(token) if is_newline(token) => self.increment()?,
+                _ => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Emit a non-fatal diagnostic, like a warning about deprecated syntax,
+    /// to be returned with the result by [TokenQueue::take_warnings]. Parse
+    /// functions can emit from their clone of the queue: the warnings are
+    /// shared, and those emitted inside a parse driver call that fails are
+    /// discarded, so alternatives that were backtracked out of leave none.
+    ///
+    /// If the [maximum](TokenQueue::with_max_diagnostics) has already been
+    /// emitted, a "too many errors" note at the diagnostic's span is emitted
+    /// instead, and parsing gives up. Diagnostics emitted after that are
+    /// dropped.
+    pub fn warn(&self, diagnostic: Diagnostic) {
+        if self.given_up.get() {
+            return;
+        }
+        let mut warnings = self.warnings.borrow_mut();
+        if self
+            .max_diagnostics
+            .is_some_and(|max| warnings.len() >= max)
+        {
+            warnings.push(Diagnostic::new(
+                Severity::Note,
+                TOO_MANY_ERRORS_MSG,
+                diagnostic.span,
+            ));
+            self.given_up.set(true);
+            return;
+        }
+        warnings.push(diagnostic);
+    }
+
+    /// Give up parsing once `max` diagnostics have been emitted with
+    /// [TokenQueue::warn] and another is emitted, so a parser recovering
+    /// from errors doesn't flood the output on badly corrupted input. After
+    /// giving up, every parse driver call and token consumed fails with a
+    /// [cut](ParseError::is_cut) [ParseError], and the diagnostics emitted
+    /// are kept, ending with a "too many errors" note, rather than discarded
+    /// with the failed parse.
+    pub fn with_max_diagnostics(mut self, max: usize) -> Self {
+        self.max_diagnostics = Some(max);
+        self
+    }
+
+    /// Check that a step of a loop that started at index `idx` consumed
+    /// something, returning a [cut](ParseError::is_cut) error if the queue is
+    /// still at `idx`, as the loop would repeat the step forever. Loops
+    /// written by hand, like ones skipping input to recover from errors, can
+    /// call this at the end of each step.
+    pub fn ensure_progress(&self, idx: usize) -> Result<()> {
+        if self.idx == idx {
+            return Err(ParseError::at(idx, NO_PROGRESS_MSG).cut());
+        }
+        Ok(())
+    }
+
+    /// Return true if parsing gave up after too many diagnostics were
+    /// emitted (see [TokenQueue::with_max_diagnostics]).
+    pub fn has_given_up(&self) -> bool {
+        self.given_up.get()
+    }
+
+    /// Take the warnings emitted so far, in order.
+    pub fn take_warnings(&mut self) -> Vec<Diagnostic> {
+        self.warnings.take()
+    }
+
+    /// Record every parse driver call, token peeked at and consumed, and
+    /// backtrack from now on, in this queue and its clones, to be taken with
+    /// [TokenQueue::take_trace].
+    pub fn with_trace(mut self) -> Self {
+        self.trace = Some(Rc::default());
+        self
+    }
+
+    /// Record the event made by `kind` at the current depth, if tracing.
+    fn record(&self, kind: impl FnOnce() -> EventKind) {
+        if let Some(trace) = &self.trace {
+            trace.borrow_mut().push(ParseEvent {
+                depth: self.depth,
+                kind: kind(),
+            });
+        }
+    }
+
+    /// Take the events recorded so far, if the queue was created
+    /// [with tracing](TokenQueue::with_trace), leaving none.
+    pub fn take_trace(&mut self) -> ParseTrace {
+        let events = self.trace.as_ref().map(|trace| trace.take());
+        ParseTrace::new(events.unwrap_or_default())
+    }
+
+    /// Commit to the construct being parsed, once a token that tells it
+    /// apart from the alternatives, like a `fn` keyword, has been consumed.
+    /// If the parse function this is called from then fails, its error is
+    /// [cut](ParseError::is_cut), so instead of backtracking to try other
+    /// alternatives, alternation returns it, with its position inside the
+    /// construct. Does nothing outside a parse function.
+    pub fn cut(&self) {
+        let mut cuts = self.cuts.borrow_mut();
+        if self.depth > 0 && cuts.last() != Some(&self.depth) {
+            cuts.push(self.depth);
+        }
+    }
+
+    /// Get the error that got furthest into the input during the last parse
+    /// from the top level, including errors backtracked out of.
+    ///
+    /// A parse driver call (`parse`, `parse_with`, ...) made from the top
+    /// level, outside any other, reports this error instead of its own if
+    /// it's at a greater index: when every alternative of a construct fails,
+    /// the one that got furthest most likely failed where the input went
+    /// wrong, while the top-level error is wherever the last alternative
+    /// gave up. Errors without an index are never preferred.
+    pub fn farthest_failure(&self) -> Option<ParseError> {
+        self.farthest.borrow().clone()
+    }
+
+    /// Get the index of the front token, past any newlines being skipped.
+    fn front(&self) -> usize {
+        let mut idx = self.idx;
+        if let Some(is_newline) = self.newline
+            && !self.newlines_significant
+        {
+            while self.tokens.get(idx).is_some_and(is_newline) {
+                idx += 1;
+            }
+        }
+        idx
+    }
+
+    /// Set the maximum number of nested parse driver calls (`parse`,
+    /// `parse_with`, ...) before parsing fails with a "nesting too deep"
+    /// [ParseError] instead of overflowing the stack. Defaults to
+    /// [DEFAULT_MAX_DEPTH].
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Quote up to `tokens` of the tokens consumed before an error in errors
+    /// made with [ParseError::expected], like "Expected `;` but found `x`
+    /// after `let y = 1`!", so the error can be placed without looking up
+    /// its position. Defaults to 0.
+    pub fn with_error_context(mut self, tokens: usize) -> Self {
+        self.error_context = tokens;
+        self
+    }
+
+    /// Borrow up to `n` of the tokens just before the queue's index, most
+    /// recent last, including any newlines skipped.
+    pub fn recent(&self, n: usize) -> &[T] {
+        &self.tokens
+            [self.idx.saturating_sub(n)..self.idx.min(self.tokens.len())]
+    }
+
+    /// Get the number of parse driver calls currently in progress.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Limit parsing to `fuel` steps, after which parsing fails with a
+    /// [ParseError]. Each parse driver call and each token consumed is a
+    /// step, and the budget is shared by every clone of the queue, so
+    /// backtracking can't escape it. This bounds the work done on untrusted
+    /// input even when a grammar backtracks exponentially.
+    pub fn with_fuel(mut self, fuel: usize) -> Self {
+        self.fuel = Some(Rc::new(Cell::new(fuel)));
+        self
+    }
+
+    /// Get the number of parse steps remaining, or `None` if parsing isn't
+    /// limited.
+    pub fn remaining_fuel(&self) -> Option<usize> {
+        self.fuel.as_ref().map(|fuel| fuel.get())
+    }
+
+    fn burn_fuel(&self) -> Result<()> {
+        if self.given_up.get() {
+            return Err(ParseError::at(self.idx, TOO_MANY_ERRORS_MSG).cut());
+        }
+        if let Some(fuel) = &self.fuel {
+            if fuel.get() == 0 {
+                return Err(ParseError::at(self.idx, FUEL_EXHAUSTED_MSG));
+            }
+            fuel.set(fuel.get() - 1);
+        }
+        Ok(())
     }
 }
 
@@ -115,10 +828,8 @@ impl<L> TokenQueue<L> {
     /// Parse a value of type `T` from the token queue with tokens of type `L`.
     /// Update the token queue's index with the index returned by the
     /// `parse_fn`.
-    pub fn parse<T>(&mut self, parse_fn: ParseFn<L, T>) -> anyhow::Result<T> {
-        let (val, index) = parse_fn(self)?;
-        self.go_to(index)?;
-        Ok(val)
+    pub fn parse<T>(&mut self, parse_fn: ParseFn<L, T>) -> Result<T> {
+        self.drive(parse_fn)
     }
 
     /// Parse a value of type `T` from the token queue with tokens of type `L`,
@@ -130,10 +841,8 @@ impl<L> TokenQueue<L> {
         &mut self,
         parse_with_fn: ParseWithFn<L, C, T>,
         context: &C,
-    ) -> anyhow::Result<T> {
-        let (val, index) = parse_with_fn(self, context)?;
-        self.go_to(index)?;
-        Ok(val)
+    ) -> Result<T> {
+        self.drive(|tq| parse_with_fn(tq, context))
     }
 
     /// Parse a value of type `T` from the token queue with tokens of type `L`,
@@ -145,37 +854,603 @@ impl<L> TokenQueue<L> {
         &mut self,
         parse_with_mut_fn: ParseWithMutFn<L, C, T>,
         context: &mut C,
-    ) -> anyhow::Result<T> {
-        let (val, index) = parse_with_mut_fn(self, context)?;
+    ) -> Result<T> {
+        self.drive(|tq| parse_with_mut_fn(tq, context))
+    }
+
+    /// Parse a value like [TokenQueue::parse], with newline tokens
+    /// significant inside the `parse_fn` if `significant` is true and
+    /// skipped otherwise, like newlines inside parentheses in languages that
+    /// end statements at newlines. The setting is restored afterwards.
+    pub fn parse_with_newlines<T>(
+        &mut self,
+        significant: bool,
+        parse_fn: ParseFn<L, T>,
+    ) -> Result<T> {
+        let outer = self.newlines_significant;
+        self.newlines_significant = significant;
+        let result = self.parse(parse_fn);
+        self.newlines_significant = outer;
+        result
+    }
+
+    /// Parse a value in two passes over the same tokens, for languages where
+    /// names can be used before they're declared. First `scan_fn` collects
+    /// declarations into `declarations`, then the queue is reset to where it
+    /// started and `parse_fn` parses the input with them in hand.
+    ///
+    /// Warnings emitted by `scan_fn` are discarded, as the full parse is
+    /// expected to emit them again. The queue is left unchanged if either
+    /// pass fails.
+    pub fn parse_two_phase<T, D>(
+        &mut self,
+        scan_fn: ParseWithMutFn<L, D, ()>,
+        parse_fn: ParseWithMutFn<L, D, T>,
+        declarations: &mut D,
+    ) -> Result<T> {
+        let start = self.idx;
+        let warnings = self.warnings.borrow().len();
+        self.parse_with_mut(scan_fn, declarations)?;
+        self.warnings.borrow_mut().truncate(warnings);
+        self.go_to(start)?;
+        self.parse_with_mut(parse_fn, declarations)
+    }
+
+    /// Parse a value from all of the remaining input, like a statement typed
+    /// into a REPL. An error at the end of the input is
+    /// [ParseStatus::Incomplete], as more input could continue it, and any
+    /// other error, or input left over, is [ParseStatus::Error].
+    ///
+    /// Errors without an index count as errors, so parse functions should
+    /// report where they failed for input to be found incomplete.
+    pub fn parse_input<T>(
+        &mut self,
+        parse_fn: ParseFn<L, T>,
+    ) -> ParseStatus<T> {
+        match self.parse(parse_fn) {
+            Ok(val) if self.is_consumed() => ParseStatus::Complete(val),
+            Ok(_) => ParseStatus::Error(ParseError::at(
+                self.idx,
+                "Expected the end of input!",
+            )),
+            Err(err) => {
+                let mut at_err = self.clone();
+                let len = self.tokens.len();
+                match err.idx().is_some_and(|idx| {
+                    at_err.go_to(idx.min(len)).is_ok() && at_err.is_consumed()
+                }) {
+                    true => ParseStatus::Incomplete(err),
+                    false => ParseStatus::Error(err),
+                }
+            }
+        }
+    }
+
+    /// Parse a value like [TokenQueue::parse], labelling the construct being
+    /// parsed `label`. Errors returned from inside `parse_fn` record the
+    /// label, so nested calls build a trace like "while parsing function
+    /// signature → parameter list".
+    pub fn with_context<T>(
+        &mut self,
+        label: &str,
+        parse_fn: ParseFn<L, T>,
+    ) -> Result<T> {
+        self.parse(parse_fn).map_err(|err| err.in_context(label))
+    }
+
+    /// Parse a value with the first of `alternatives` that succeeds, each a
+    /// label and a [ParseFn]. If all of them fail, the error combines their
+    /// errors as an [AltError], unless one is [cut](ParseError::is_cut), in
+    /// which case its error is returned without trying the rest.
+    pub fn one_of<T>(
+        &mut self,
+        alternatives: &[(&str, ParseFn<L, T>)],
+    ) -> Result<T> {
+        let mut errors = AltError::new(self.idx);
+        for (label, parse_fn) in alternatives {
+            match self.parse(*parse_fn) {
+                Ok(val) => return Ok(val),
+                Err(err) if err.is_cut() => return Err(err),
+                Err(err) => errors.push(*label, err),
+            }
+        }
+        Err(errors.into())
+    }
+
+    /// Parse a list of values of type `T` separated by tokens equal to
+    /// `separator`, following `options` on trailing separators and the
+    /// minimum number of values.
+    ///
+    /// The list ends at the first value `parse_fn` fails to parse, unless
+    /// that value follows a separator and a trailing separator isn't allowed,
+    /// or its error is [cut](ParseError::is_cut), in which case its error is
+    /// returned.
+    pub fn parse_separated<T, U>(
+        &mut self,
+        parse_fn: ParseFn<L, T>,
+        separator: U,
+        options: Separated,
+    ) -> Result<Vec<T>>
+    where
+        L: PartialEq<U>,
+    {
+        let start = self.idx;
+        let mut vals = Vec::new();
+
+        loop {
+            match self.parse(parse_fn) {
+                Ok(val) => vals.push(val),
+                Err(err)
+                    if err.is_cut()
+                        || !vals.is_empty() && !options.trailing.allowed() =>
+                {
+                    self.go_to(start)?;
+                    return Err(err);
+                }
+                Err(_) => break,
+            }
+            if !self.at(&separator) {
+                if options.trailing == Trailing::Require {
+                    let err = ParseError::at(self.idx, MISSING_SEPARATOR_MSG);
+                    self.go_to(start)?;
+                    return Err(err);
+                }
+                break;
+            }
+            self.increment()?;
+        }
+
+        if vals.len() < options.min {
+            let err = ParseError::at(
+                self.idx,
+                format!("Expected at least {} list items!", options.min),
+            );
+            self.go_to(start)?;
+            return Err(err);
+        }
+        Ok(vals)
+    }
+
+    /// Parse values of type `T` until `parse_fn` fails, returning them in
+    /// order, possibly none.
+    ///
+    /// If `parse_fn` fails with a [cut](ParseError::is_cut) error, or
+    /// succeeds without consuming anything, which would repeat forever, its
+    /// error (or one from [TokenQueue::ensure_progress]) is returned and the
+    /// queue is left unchanged.
+    pub fn parse_many<T>(&mut self, parse_fn: ParseFn<L, T>) -> Result<Vec<T>> {
+        let start = self.idx;
+        let mut vals = Vec::new();
+        loop {
+            let idx = self.idx;
+            let result = match self.parse(parse_fn) {
+                Ok(val) => self.ensure_progress(idx).map(|_| val),
+                Err(err) if err.is_cut() => Err(err),
+                Err(_) => return Ok(vals),
+            };
+            match result {
+                Ok(val) => vals.push(val),
+                Err(err) => {
+                    self.go_to(start)?;
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Parse one or more operands separated by operators, combining them
+    /// left-associatively: `a - b - c` is combined as
+    /// `combine(combine(a, -, b), -, c)`.
+    ///
+    /// The chain ends at the first operator `operator_fn` fails to parse,
+    /// unless its error is [cut](ParseError::is_cut). An operand that fails
+    /// to parse after an operator is an error, as is an operator and operand
+    /// that together consume nothing, which would repeat forever.
+    pub fn fold_left<T, O>(
+        &mut self,
+        operand_fn: ParseFn<L, T>,
+        operator_fn: ParseFn<L, O>,
+        combine: fn(T, O, T) -> T,
+    ) -> Result<T> {
+        let mut lhs = self.parse(operand_fn)?;
+        loop {
+            let idx = self.idx;
+            let op = match self.parse(operator_fn) {
+                Ok(op) => op,
+                Err(err) if err.is_cut() => return Err(err),
+                Err(_) => return Ok(lhs),
+            };
+            let rhs = self.parse(operand_fn)?;
+            self.ensure_progress(idx)?;
+            lhs = combine(lhs, op, rhs);
+        }
+    }
+
+    /// Record `err` as the farthest failure if it's at least as far as the
+    /// one recorded, so at a tie the error from the outer call, with more
+    /// context, is kept, or if it's cut. At the top level, replace `err`
+    /// with the farthest failure if that's further.
+    fn record_failure(&self, err: &mut ParseError, top_level: bool) {
+        let mut farthest = self.farthest.borrow_mut();
+        match farthest.as_ref() {
+            _ if err.cut => *farthest = Some(err.clone()),
+            Some(far) if far.idx > err.idx && top_level => *err = far.clone(),
+            Some(far) if far.idx > err.idx => {}
+            _ if err.idx.is_some() => *farthest = Some(err.clone()),
+            _ => {}
+        }
+    }
+
+    /// Run a parse function one level deeper, then move to the index it
+    /// returns.
+    fn drive<T>(
+        &mut self,
+        f: impl FnOnce(&Self) -> ParseResult<T>,
+    ) -> Result<T> {
+        if self.depth >= self.max_depth {
+            return Err(ParseError::at(self.idx, NESTING_TOO_DEEP_MSG));
+        }
+        self.burn_fuel()?;
+        #[cfg(feature = "tracing")]
+        let _span = tracing::trace_span!(
+            "parse",
+            node = std::any::type_name::<T>(),
+            idx = self.idx,
+            depth = self.depth,
+        )
+        .entered();
+        self.record(|| EventKind::Enter {
+            node: std::any::type_name::<T>(),
+            idx: self.idx,
+        });
+        let warnings = self.warnings.borrow().len();
+        let top_level = self.depth == 0;
+        if top_level {
+            self.farthest.take();
+        }
+        self.depth += 1;
+        let mut result = f(self);
+        let cut = self.cuts.borrow().last() == Some(&self.depth);
+        self.cuts.borrow_mut().retain(|depth| *depth < self.depth);
+        self.depth -= 1;
+        if let Err(err) = &mut result {
+            err.cut |= cut;
+            if !self.given_up.get() {
+                self.warnings.borrow_mut().truncate(warnings);
+            }
+            self.record_failure(err, top_level);
+        }
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok((_, end)) => tracing::trace!(end, "parse succeeded"),
+            Err(err) => tracing::trace!(%err, "parse failed"),
+        }
+        self.record(|| match &result {
+            Ok((_, end)) => EventKind::Succeed { end: *end },
+            Err(err) => EventKind::Fail {
+                idx: err.idx,
+                message: err.message.clone(),
+            },
+        });
+        let (val, index) = result?;
         self.go_to(index)?;
         Ok(val)
     }
 }
 
-impl<T: PartialEq> TokenQueue<T> {
+impl<T> TokenQueue<T> {
     /// Consume a token that is equal to token `token`, returning an error if the
-    /// front token in the queue doesn't equal `token`.
-    pub fn consume_eq(&mut self, token: T) -> anyhow::Result<()> {
-        if self.peek()? == &token {
-            self.increment()?;
-            return Ok(());
+    /// front token in the queue doesn't equal `token`, naming both with their
+    /// `Debug` output, like "Expected `','` but found `'x'`!" (see
+    /// [ParseError::expected]).
+    ///
+    /// `token` may be any type the tokens can be compared with, so a queue of
+    /// [Spanned] tokens can be matched against bare tokens.
+    pub fn consume_eq<U>(&mut self, token: U) -> Result<()>
+    where
+        T: PartialEq<U> + Debug,
+        U: Debug,
+    {
+        if !self.at(&token) {
+            return Err(ParseError::expected(format!("`{token:?}`"), self));
         }
-        Err(anyhow::anyhow!(TOKEN_DID_NOT_MATCH_MSG))
+        self.increment()
     }
-}
 
-impl<T> From<Vec<T>> for TokenQueue<T> {
-    fn from(value: Vec<T>) -> Self {
-        Self {
-            tokens: Rc::new(value),
-            idx: 0,
+    /// Return true if the front token equals `token`. Never fails and never
+    /// advances the queue; an empty queue isn't at any token.
+    pub fn at<U>(&self, token: &U) -> bool
+    where
+        T: PartialEq<U>,
+    {
+        self.peek().is_ok_and(|front| front == token)
+    }
+
+    /// Return true if the front token equals any of `tokens`.
+    pub fn at_any<U>(&self, tokens: &[U]) -> bool
+    where
+        T: PartialEq<U>,
+    {
+        tokens.iter().any(|token| self.at(token))
+    }
+
+    /// Consume a balanced region starting with the front token, which must
+    /// equal `open`, up to and including the `close` token that matches it,
+    /// counting nested `open` and `close` tokens. Returns the range of token
+    /// indices consumed, so a function body can be skipped and parsed later
+    /// from a clone of the queue, or a broken region skipped to recover.
+    ///
+    /// If the region isn't closed before the end of input, returns an error
+    /// at the end without consuming anything.
+    pub fn skip_balanced<U>(
+        &mut self,
+        open: U,
+        close: U,
+    ) -> Result<Range<usize>>
+    where
+        T: PartialEq<U>,
+    {
+        let start = self.front();
+        if !self.at(&open) {
+            return Err(ParseError::at(start, TOKEN_DID_NOT_MATCH_MSG));
+        }
+        let mut end = self.clone();
+        let mut depth = 0;
+        loop {
+            let token = end.consume().map_err(|_| {
+                ParseError::at(
+                    self.tokens.len(),
+                    "Expected a closing delimiter!",
+                )
+            })?;
+            if *token == open {
+                depth += 1;
+            } else if *token == close {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+            }
         }
+        self.idx = end.idx;
+        Ok(start..end.idx)
     }
 }
 
-impl<T> Debug for TokenQueue<T>
-where
-    T: Debug,
+impl<T: Clone> TokenQueue<T> {
+    /// Borrow the queue mutably. Used by [expect!] to evaluate its queue
+    /// expression once, by method call, so it can be a queue or a mutable
+    /// reference to one.
+    #[doc(hidden)]
+    pub fn as_mut_queue(&mut self) -> &mut Self {
+        self
+    }
+
+    /// Consume the tokens one by one as an iterator, so iterator adapters
+    /// can be used on the queue, which continues after the tokens they
+    /// consumed.
+    ///
+    /// Every token the iterator yields is consumed, including ones an adapter
+    /// drops: `take_while` consumes the first token that fails its predicate
+    /// and discards it. Use [TokenQueue::consume_while] to stop before that
+    /// token instead.
+    pub fn consuming(&mut self) -> Consume<'_, T> {
+        Consume { tq: self }
+    }
+
+    /// Consume tokens while the front token returns `true` when passed to
+    /// `f`, returning clones of them, possibly none. The first token that
+    /// doesn't match is left at the front of the queue.
+    pub fn consume_while(&mut self, mut f: impl FnMut(&T) -> bool) -> Vec<T> {
+        let mut tokens = Vec::new();
+        while self.peek().is_ok_and(&mut f) {
+            match self.consume_cloned() {
+                Ok(token) => tokens.push(token),
+                Err(_) => break,
+            }
+        }
+        tokens
+    }
+
+    /// Consume the front token in the queue, returning a clone of it.
+    ///
+    /// Unlike [TokenQueue::consume], the returned token doesn't borrow the
+    /// queue, so the queue can keep being used while the token is matched on.
+    pub fn consume_cloned(&mut self) -> Result<T> {
+        self.consume().cloned()
+    }
+}
+
+impl<L> TokenQueue<Spanned<L>> {
+    /// Create a queue from results paired with the byte ranges of the input
+    /// they were lexed from, returning an error at the start of the first
+    /// range whose result is an error.
+    ///
+    /// This is the item type of `logos::Lexer::spanned`, so tokens from a
+    /// `logos`-derived lexer can be parsed with
+    /// `TokenQueue::from_spanned(lexer.spanned())`.
+    pub fn from_spanned<E, I>(tokens: I) -> std::result::Result<Self, LexError>
+    where
+        I: IntoIterator<Item = (std::result::Result<L, E>, Range<usize>)>,
+    {
+        let tokens = tokens
+            .into_iter()
+            .map(|(token, range)| match token {
+                Ok(token) => Ok(Spanned::new(token, range.into())),
+                Err(_) => Err(LexError::Unmatched { pos: range.start }),
+            })
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self::from(tokens))
+    }
+}
+
+impl<T: HasSpan> TokenQueue<T> {
+    /// Get the span of the front token in the queue.
+    pub fn peek_span(&self) -> Result<Span> {
+        Ok(self.peek()?.span())
+    }
+
+    /// Get the span of the last token consumed.
+    pub fn prev_span(&self) -> Result<Span> {
+        Ok(self.prev()?.span())
+    }
+
+    /// Get the span of the input covered by the tokens at indices
+    /// `start..end`. An empty range of tokens gives an empty span at the
+    /// position where the token at `start` would begin. Indices past the last
+    /// token are treated as the end of the input.
+    pub fn span_between(&self, start: usize, end: usize) -> Span {
+        let end = end.min(self.tokens.len());
+        if start < end {
+            return self.tokens[start]
+                .span()
+                .merge(self.tokens[end - 1].span());
+        }
+        match self.tokens.get(start) {
+            Some(token) => Span::new(token.span().start, token.span().start),
+            None => match self.tokens.last() {
+                Some(token) => Span::new(token.span().end, token.span().end),
+                None => Span::default(),
+            },
+        }
+    }
+
+    /// Get the span of the input to report `err` at: the span of the token
+    /// at its index (or the front token, if it has none), or an empty span
+    /// at the end of the input if the index is past the last token.
+    pub fn error_span(&self, err: &ParseError) -> Span {
+        let idx = err.idx().unwrap_or(self.idx);
+        match self.tokens.get(idx) {
+            Some(token) => token.span(),
+            None => self.span_between(idx, idx),
+        }
+    }
+
+    /// Parse a value of type `U` like [TokenQueue::parse], reporting the
+    /// warnings emitted, then any error, to `sink`, at the span from
+    /// [TokenQueue::error_span]. Returns `None` if parsing failed or the
+    /// sink asked to stop. If parsing gave up after too many diagnostics,
+    /// the error isn't reported, as the last warning already says so.
+    pub fn parse_reporting<U>(
+        &mut self,
+        parse_fn: ParseFn<T, U>,
+        sink: &mut dyn ErrorSink,
+    ) -> Option<U> {
+        let result = self.parse(parse_fn);
+        for warning in self.take_warnings() {
+            sink.report(warning).continue_value()?;
+        }
+        match result {
+            Ok(val) => Some(val),
+            Err(_) if self.has_given_up() => None,
+            Err(err) => {
+                let span = self.error_span(&err);
+                let _ = sink.report(Diagnostic::error(err.to_string(), span));
+                None
+            }
+        }
+    }
+
+    /// Parse a value of type `U` like [TokenQueue::parse], wrapping it with
+    /// the span of the input covered by the tokens the `parse_fn` consumed.
+    pub fn parse_spanned<U>(
+        &mut self,
+        parse_fn: ParseFn<T, U>,
+    ) -> Result<Spanned<U>> {
+        let start = self.get_idx();
+        let val = self.parse(parse_fn)?;
+        Ok(Spanned::new(val, self.span_between(start, self.get_idx())))
+    }
+}
+
+impl<T> From<Vec<T>> for TokenQueue<T> {
+    fn from(value: Vec<T>) -> Self {
+        Self {
+            tokens: Rc::new(value),
+            idx: 0,
+            depth: 0,
+            max_depth: DEFAULT_MAX_DEPTH,
+            error_context: 0,
+            fuel: None,
+            newline: None,
+            newlines_significant: true,
+            warnings: Rc::default(),
+            max_diagnostics: None,
+            given_up: Rc::default(),
+            farthest: Rc::default(),
+            cuts: Rc::default(),
+            trace: None,
+        }
+    }
+}
+
+/// Indexes the whole stream, like [TokenQueue::get], panicking if `idx` is
+/// out of bounds.
+impl<T> Index<usize> for TokenQueue<T> {
+    type Output = T;
+
+    fn index(&self, idx: usize) -> &Self::Output {
+        &self.tokens[idx]
+    }
+}
+
+/// An iterator consuming the tokens of a [TokenQueue] one by one, cloning
+/// each, returned by [TokenQueue::consuming]. Iteration ends at the end of
+/// input, or when the queue runs out of fuel.
+pub struct Consume<'a, T> {
+    tq: &'a mut TokenQueue<T>,
+}
+
+impl<T: Clone> Iterator for Consume<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.tq.consume_cloned().ok()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a TokenQueue<T> {
+    type Item = &'a T;
+    type IntoIter = Box<dyn Iterator<Item = &'a T> + 'a>;
+
+    /// Iterate over the tokens left in the queue, like [TokenQueue::tokens].
+    fn into_iter(self) -> Self::IntoIter {
+        Box::new(self.tokens())
+    }
+}
+
+impl<'a, T: Clone> IntoIterator for &'a mut TokenQueue<T> {
+    type Item = T;
+    type IntoIter = Consume<'a, T>;
+
+    /// Consume the tokens left in the queue, like [TokenQueue::consuming].
+    fn into_iter(self) -> Self::IntoIter {
+        self.consuming()
+    }
+}
+
+impl<T: Clone> IntoIterator for TokenQueue<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    /// Take the tokens left in the queue, without cloning them if no other
+    /// clone of the queue shares them.
+    fn into_iter(self) -> Self::IntoIter {
+        let front = self.front();
+        let skipped = self.newline.filter(|_| !self.newlines_significant);
+        let mut tokens =
+            Rc::try_unwrap(self.tokens).unwrap_or_else(|rc| (*rc).clone());
+        tokens.drain(..front);
+        tokens.retain(|token| {
+            !skipped.is_some_and(|is_newline| is_newline(token))
+        });
+        tokens.into_iter()
+    }
+}
+
+impl<T> Debug for TokenQueue<T>
+where
+    T: Debug,
 {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for token in
@@ -186,3 +1461,819 @@ where
         Ok(())
     }
 }
+
+/// Consume the front token of a [TokenQueue] if it matches a pattern,
+/// evaluating to the expression after `=>` (or `()` if there isn't one) with
+/// the pattern's bindings taken by value.
+///
+/// Evaluates to a `Result` whose error is a [ParseError] naming the pattern
+/// that was expected and the tokens found (see [ParseError::expected]). The
+/// queue is left unchanged on failure. Tokens must be `Clone`, as the matched
+/// token is cloned out of the queue, and `Debug`.
+///
+/// ```
+/// use rlrl::expect;
+/// use rlrl::parse::TokenQueue;
+///
+/// #[derive(Debug, Clone)]
+/// enum Token {
+///     Int(i64),
+///     Comma,
+/// }
+///
+/// let mut tq = TokenQueue::from(vec![Token::Int(1), Token::Comma]);
+/// assert!(expect!(tq, Token::Int(val) => val) == Ok(1));
+/// let err = expect!(tq, Token::Int(val) => val).unwrap_err();
+/// assert!(err.to_string().starts_with("Expected `Token::Int(val)` but"));
+/// assert!(expect!(tq, Token::Comma).is_ok());
+/// ```
+#[macro_export]
+macro_rules! expect {
+    ($tq:expr, $pat:pat => $val:expr) => {{
+        // evaluate the queue expression once, whether it's a queue or a
+        // mutable reference to one
+        let tq = $tq.as_mut_queue();
+        // the bindings are only used once the token is cloned out
+        #[allow(unused_variables, clippy::redundant_pattern_matching)]
+        let found = matches!(tq.peek(), Ok($pat));
+        match found {
+            // consuming can still fail, if out of fuel or given up
+            #[allow(unreachable_patterns)]
+            true => match tq.consume_cloned() {
+                Ok($pat) => $crate::parse::Result::Ok($val),
+                Ok(_) => unreachable!(),
+                Err(err) => Err(err),
+            },
+            false => Err($crate::parse::ParseError::expected(
+                concat!("`", stringify!($pat), "`"),
+                tq,
+            )),
+        }
+    }};
+    ($tq:expr, $pat:pat) => {
+        $crate::expect!($tq, $pat => ())
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::{Diagnostic, Severity};
+    use crate::lex::LexError;
+    use crate::parse::{
+        AltError, ParseError, ParseResult, ParseStatus, Separated, TokenQueue,
+        Trailing,
+    };
+    use crate::span::{Span, Spanned};
+    use std::cell::Cell;
+    use std::collections::HashSet;
+
+    /// Parses `(` ... `)` nested to any depth.
+    fn parse_nested(tq: &TokenQueue<char>) -> ParseResult<usize> {
+        let mut tq = tq.clone();
+
+        if tq.consume_eq('(').is_err() {
+            return Ok((0, tq.get_idx()));
+        }
+        let depth = tq.parse(parse_nested)?;
+        tq.consume_eq(')')?;
+
+        Ok((depth + 1, tq.get_idx()))
+    }
+
+    fn nested(depth: usize) -> TokenQueue<char> {
+        let mut tokens = vec!['('; depth];
+        tokens.extend(vec![')'; depth]);
+        TokenQueue::from(tokens)
+    }
+
+    #[test]
+    fn test_max_depth() -> anyhow::Result<()> {
+        assert!(nested(100).parse(parse_nested)? == 100);
+
+        let err = nested(100_000).parse(parse_nested).unwrap_err();
+        assert!(err.message() == "Nesting too deep!");
+
+        let mut tq = nested(10).with_max_depth(5);
+        assert!(tq.parse(parse_nested).is_err());
+        assert!(tq.depth() == 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_consume_cloned() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a', 'b']);
+
+        // the token is owned, so the queue can be used inside the match
+        match tq.consume_cloned()? {
+            'a' => tq.consume_eq('b')?,
+            token => panic!("unexpected {token}"),
+        }
+        assert!(tq.consume_cloned().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_consume_map() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['7', 'x']);
+
+        let digit = |c: &char| c.to_digit(10);
+        assert!(tq.consume_map("a digit", digit)? == 7);
+        let err = tq.consume_map("a digit", digit).unwrap_err();
+        assert!(err.to_string() == "Expected a digit! (at token 1)");
+        assert!(tq.get_idx() == 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_at() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a', ',']);
+
+        assert!(tq.at(&'a') && !tq.at(&','));
+        assert!(tq.at_any(&[',', 'a']) && !tq.at_any(&[',', ';']));
+        tq.go_to(2)?;
+        assert!(!tq.at(&',') && !tq.at_any(&[',']));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_skip_balanced() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("{a{b}c}d{".chars().collect::<Vec<_>>());
+
+        assert!(tq.skip_balanced('{', '}')? == (0..7));
+        assert!(tq.at(&'d'));
+        assert!(tq.skip_balanced('{', '}').is_err());
+        tq.increment()?;
+        let err = tq.skip_balanced('{', '}').unwrap_err();
+        assert!(err.idx() == Some(9) && tq.get_idx() == 8);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_index() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a', 'b', 'c']);
+        tq.go_to(2)?;
+
+        assert!(tq.get(0) == Some(&'a') && tq.get(3).is_none());
+        assert!(tq[1] == 'b');
+        assert!(tq.tokens_in(0..2) == Some(&['a', 'b'][..]));
+        assert!(tq.tokens_in(2..4).is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_iterator() -> anyhow::Result<()> {
+        let tq = TokenQueue::from("ab\n1c".chars().collect::<Vec<_>>())
+            .with_newline(|c| *c == '\n')
+            .with_newlines_significant(false);
+        let mut tq = tq.clone();
+
+        assert!(tq.tokens().position(|c| *c == '1') == Some(2));
+        let letters = tq.consume_while(char::is_ascii_alphabetic);
+        assert!(letters == ['a', 'b'] && tq.peek()? == &'1');
+        // the token that ended the run isn't lost
+        assert!(tq.consume_while(char::is_ascii_alphabetic).is_empty());
+        assert!(tq.consume_cloned()? == '1');
+        assert!((&tq).into_iter().eq(['c'].iter()));
+
+        let mut rest = String::new();
+        for c in &mut tq.clone() {
+            rest.push(c);
+        }
+        assert!(rest == "c" && tq.into_iter().collect::<String>() == "c");
+
+        Ok(())
+    }
+
+    /// Parses a single letter.
+    fn parse_letter(tq: &TokenQueue<char>) -> ParseResult<char> {
+        let mut tq = tq.clone();
+        let letter = *tq.consume_matching(char::is_ascii_alphabetic)?;
+        Ok((letter, tq.get_idx()))
+    }
+
+    fn separated(
+        s: &str,
+        options: Separated,
+    ) -> anyhow::Result<(Vec<char>, usize)> {
+        let mut tq = TokenQueue::from(s.chars().collect::<Vec<char>>());
+        let letters = tq.parse_separated(parse_letter, ',', options)?;
+        Ok((letters, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_parse_separated() -> anyhow::Result<()> {
+        let forbid = Separated::default();
+        let allow = forbid.with_trailing(Trailing::Allow);
+        let require = forbid.with_trailing(Trailing::Require);
+
+        assert!(separated("a,b)", forbid)? == (vec!['a', 'b'], 3));
+        assert!(separated(")", forbid)? == (vec![], 0));
+        assert!(separated("a,b,)", forbid).is_err());
+        assert!(separated("a,b,)", allow)? == (vec!['a', 'b'], 4));
+        assert!(separated("a,b,)", require)? == (vec!['a', 'b'], 4));
+        assert!(separated("a,b)", require).is_err());
+        assert!(separated(")", forbid.with_min(1)).is_err());
+
+        Ok(())
+    }
+
+    fn parse_digit(tq: &TokenQueue<char>) -> ParseResult<i64> {
+        let mut tq = tq.clone();
+        let digit = tq.consume_map("a digit", |c| c.to_digit(10))?;
+        Ok((digit.into(), tq.get_idx()))
+    }
+
+    fn parse_sign(tq: &TokenQueue<char>) -> ParseResult<i64> {
+        let mut tq = tq.clone();
+        let sign = tq.consume_map("`+` or `-`", |c| match c {
+            '+' => Some(1),
+            '-' => Some(-1),
+            _ => None,
+        })?;
+        Ok((sign, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_fold_left() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("9-3-2+1)".chars().collect::<Vec<_>>());
+
+        // (((9 - 3) - 2) + 1), not 9 - (3 - (2 + 1))
+        let val =
+            tq.fold_left(parse_digit, parse_sign, |a, sign, b| a + sign * b)?;
+        assert!(val == 5);
+        assert!(tq.at(&')'));
+
+        let mut tq = TokenQueue::from("9-)".chars().collect::<Vec<_>>());
+        assert!(tq.fold_left(parse_digit, parse_sign, |a, _, _| a).is_err());
+
+        // an operator and operand that consume nothing would repeat forever
+        let mut tq = TokenQueue::from(vec!['9']);
+        let err = tq
+            .fold_left(parse_nothing, parse_nothing, |a, _, _| a)
+            .unwrap_err();
+        assert!(err == ParseError::at(0, "Parse loop made no progress!"));
+
+        Ok(())
+    }
+
+    fn parse_nothing(tq: &TokenQueue<char>) -> ParseResult<i64> {
+        Ok((0, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_parse_many() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("12+".chars().collect::<Vec<_>>());
+        assert!(tq.parse_many(parse_digit)? == [1, 2]);
+        assert!(tq.parse_many(parse_digit)?.is_empty());
+        assert!(tq.at(&'+'));
+
+        let mut tq = TokenQueue::from(vec!['1']);
+        let err = tq.parse_many(parse_nothing).unwrap_err();
+        assert!(
+            err.is_cut() && err.message() == "Parse loop made no progress!"
+        );
+        assert!(tq.get_idx() == 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expected() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a', 'b']);
+
+        tq.increment()?;
+        let err = ParseError::expected("a digit", &tq);
+        assert!(
+            err.to_string() == "Expected a digit but found `'b'`! (at token 1)"
+        );
+        tq.increment()?;
+        let err = ParseError::expected(format_args!("`{}`", ')'), &tq);
+        assert!(err.message() == "Expected `)` but reached the end of input!");
+
+        let mut tq =
+            TokenQueue::from(vec!['a', 'b', 'c']).with_error_context(2);
+        assert!(tq.recent(2).is_empty());
+        tq.go_to(1)?;
+        let err = ParseError::expected("a digit", &tq);
+        assert!(
+            err.message() == "Expected a digit but found `'b''c'` after `'a'`!"
+        );
+        tq.go_to(3)?;
+        assert!(tq.recent(2) == ['b', 'c']);
+        let err = ParseError::expected("a digit", &tq);
+        assert!(
+            err.message()
+                == "Expected a digit but reached the end of input after \
+                    `'b' 'c'`!"
+        );
+
+        let mut tq = TokenQueue::from(vec!['a', '?']);
+        let err = tq.consume_eq(',').unwrap_err();
+        assert!(err.message() == "Expected `','` but found `'a''?'`!");
+        tq.consume_matching(char::is_ascii_alphabetic)?;
+        let err = tq.consume_matching(char::is_ascii_alphabetic).unwrap_err();
+        assert!(err == ParseError::at(1, "Unexpected `'?'`!"));
+        tq.increment()?;
+        let err = tq.consume_eq(',').unwrap_err();
+        assert!(
+            err.message() == "Expected `','` but reached the end of input!"
+        );
+
+        Ok(())
+    }
+
+    fn parse_params(tq: &TokenQueue<char>) -> ParseResult<()> {
+        let mut tq = tq.clone();
+        tq.consume_eq('(')?;
+        tq.parse_separated(parse_letter, ',', Separated::default())?;
+        if tq.consume_eq(')').is_err() {
+            return Err(ParseError::expected("`)`", &tq));
+        }
+        Ok(((), tq.get_idx()))
+    }
+
+    fn parse_signature(tq: &TokenQueue<char>) -> ParseResult<()> {
+        let mut tq = tq.clone();
+        tq.parse(parse_letter)?;
+        tq.with_context("parameter list", parse_params)?;
+        Ok(((), tq.get_idx()))
+    }
+
+    #[test]
+    fn test_wrap() {
+        let err = ParseError::from(LexError::Unmatched { pos: 3 });
+        assert!(err.message() == "Unmatched input at position 3!");
+        assert!(err.cause().is_some_and(|cause| cause.is::<LexError>()));
+
+        let err = ParseError::wrap("bad literal").in_context("expression");
+        assert!(err.to_string() == "bad literal while parsing expression");
+        assert!(err == ParseError::new("bad literal").in_context("expression"));
+    }
+
+    #[test]
+    fn test_with_context() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("f(a,b;".chars().collect::<Vec<_>>());
+
+        let err = tq
+            .with_context("function signature", parse_signature)
+            .unwrap_err();
+        assert!(err.contexts() == ["parameter list", "function signature"]);
+        assert!(
+            err.to_string()
+                == "Expected `)` but found `';'`! (at token 5) while parsing \
+                    function signature → parameter list"
+        );
+
+        Ok(())
+    }
+
+    /// Parses `a` then `b`.
+    fn parse_ab(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        tq.consume_eq('a')?;
+        tq.consume_eq('b')?;
+        Ok(("ab", tq.get_idx()))
+    }
+
+    /// Parses `a` then `c`.
+    fn parse_ac(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        tq.consume_eq('a')?;
+        tq.consume_eq('c')?;
+        Ok(("ac", tq.get_idx()))
+    }
+
+    /// Parses `d`.
+    fn parse_d(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        tq.consume_eq('d')?;
+        Ok(("d", tq.get_idx()))
+    }
+
+    #[test]
+    fn test_one_of() -> anyhow::Result<()> {
+        let tq = |s: &str| TokenQueue::from(s.chars().collect::<Vec<_>>());
+        let alternatives = [
+            ("`ab`", parse_ab as fn(&_) -> _),
+            ("`ac`", parse_ac),
+            ("`d`", parse_d),
+        ];
+
+        let mut ac = tq("ac");
+        assert!(ac.one_of(&alternatives)? == "ac" && ac.is_consumed());
+
+        // no alternative got past the start
+        let err = tq("x").one_of(&alternatives).unwrap_err();
+        assert!(err.message() == "Expected one of `ab`, `ac`, `d`!");
+        assert!(err.idx() == Some(0));
+        let alt = err.cause().unwrap().downcast_ref::<AltError>().unwrap();
+        assert!(alt.alternatives().len() == 3);
+
+        // `ab` and `ac` tie at token 1
+        let err = tq("ax").one_of(&alternatives).unwrap_err();
+        assert!(err.message() == "Expected one of `ab`, `ac`!");
+        assert!(err.idx() == Some(1));
+
+        // only `ab` gets to token 2
+        let mut alt = AltError::new(0);
+        alt.push("`ab`", ParseError::at(2, "Expected `c`!"));
+        alt.push("`d`", ParseError::at(0, "Expected `d`!"));
+        assert!(alt.primary() == ParseError::at(2, "Expected `c`!"));
+        assert!(alt.to_string() == "Expected one of `ab`, `d`!");
+
+        Ok(())
+    }
+
+    /// Parses `ab` or `d`, failing at the start if neither parses.
+    fn parse_ab_or_d(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        for parse_fn in [parse_ab, parse_d] {
+            if let Ok(val) = tq.parse(parse_fn) {
+                return Ok((val, tq.get_idx()));
+            }
+        }
+        Err(ParseError::at(tq.get_idx(), "Expected `ab` or `d`!"))
+    }
+
+    #[test]
+    fn test_farthest_failure() -> anyhow::Result<()> {
+        let tq = |s: &str| TokenQueue::from(s.chars().collect::<Vec<_>>());
+
+        // `ab` got to token 1 before `d` failed at the start
+        let mut ax = tq("ax");
+        let err = ax.parse(parse_ab_or_d).unwrap_err();
+        assert!(err.idx() == Some(1) && ax.get_idx() == 0);
+        assert!(ax.farthest_failure() == Some(err));
+
+        let mut x = tq("x");
+        let err = x.parse(parse_ab_or_d).unwrap_err();
+        assert!(err == ParseError::at(0, "Expected `ab` or `d`!"));
+
+        // each top-level parse starts afresh
+        let mut dx = tq("dax");
+        assert!(dx.parse(parse_ab_or_d)? == "d");
+        let err = dx.parse(parse_d).unwrap_err();
+        assert!(err.idx() == Some(1));
+
+        Ok(())
+    }
+
+    /// Parses `f`, commits, then parses `x`.
+    fn parse_fx(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        tq.consume_eq('f')?;
+        tq.cut();
+        tq.consume_eq('x')?;
+        Ok(("fx", tq.get_idx()))
+    }
+
+    /// Parses `f`.
+    fn parse_f(tq: &TokenQueue<char>) -> ParseResult<&'static str> {
+        let mut tq = tq.clone();
+        tq.consume_eq('f')?;
+        Ok(("f", tq.get_idx()))
+    }
+
+    /// Parses `fx` then fails.
+    fn parse_fx_then_fail(tq: &TokenQueue<char>) -> ParseResult<()> {
+        let mut tq = tq.clone();
+        tq.parse(parse_fx)?;
+        Err(ParseError::at(tq.get_idx(), "Expected nothing!"))
+    }
+
+    #[test]
+    fn test_cut() -> anyhow::Result<()> {
+        let tq = |s: &str| TokenQueue::from(s.chars().collect::<Vec<_>>());
+        let alternatives =
+            [("`fx`", parse_fx as fn(&_) -> _), ("`f`", parse_f)];
+
+        assert!(tq("fx").one_of(&alternatives)? == "fx");
+        let err = tq("fy").one_of(&alternatives).unwrap_err();
+        assert!(err.is_cut() && err.idx() == Some(1));
+        let err = tq("g").one_of(&alternatives).unwrap_err();
+        assert!(!err.is_cut());
+
+        // a cut in a parse function that succeeded doesn't commit its caller
+        let err = tq("fx").parse(parse_fx_then_fail).unwrap_err();
+        assert!(!err.is_cut());
+
+        // a trailing separator is allowed, but `fy` was committed to
+        let options = Separated::default().with_trailing(Trailing::Allow);
+        let err = tq("fx,fy")
+            .parse_separated(parse_fx, ',', options)
+            .unwrap_err();
+        assert!(err.is_cut() && err.idx() == Some(4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expect() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec![Some('a'), None]);
+
+        assert!(expect!(tq, Some(c) => c)? == 'a');
+        let err = expect!(tq, Some(c) => c).unwrap_err();
+        assert!(
+            err == ParseError::at(1, "Expected `Some(c)` but found `None`!")
+        );
+        assert!(tq.get_idx() == 1);
+        expect!(tq, None)?;
+        assert!(tq.is_consumed());
+
+        // the token matches, but consuming it fails
+        let mut tq = TokenQueue::from(vec!['a', 'b']).with_fuel(0);
+        let err = expect!(tq, c @ 'a' => c).unwrap_err();
+        assert!(err.message() == "Ran out of fuel while parsing!");
+        assert!(tq.get_idx() == 0);
+
+        let mut tq = TokenQueue::from(vec!['a', 'b']).with_max_diagnostics(0);
+        tq.warn(Diagnostic::warning("`a` is deprecated", Span::new(0, 1)));
+        assert!(tq.has_given_up() && expect!(tq, 'a').is_err());
+
+        // the queue expression is evaluated once
+        fn counted<'a>(
+            tq: &'a mut TokenQueue<char>,
+            calls: &Cell<usize>,
+        ) -> &'a mut TokenQueue<char> {
+            calls.set(calls.get() + 1);
+            tq
+        }
+        let mut tq = TokenQueue::from(vec!['a', 'b']);
+        let calls = Cell::new(0);
+        assert!(expect!(*counted(&mut tq, &calls), 'b').is_err());
+        assert!(expect!(*counted(&mut tq, &calls), c => c)? == 'a');
+        assert!(calls.get() == 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_spanned() -> anyhow::Result<()> {
+        // the items a logos lexer's `spanned` iterator yields
+        let items = vec![(Ok::<_, ()>('a'), 0..1), (Ok('b'), 2..4)];
+        let tq = TokenQueue::<Spanned<char>>::from_spanned(items)?;
+        assert!(
+            tq.remaining()
+                == [
+                    Spanned::new('a', Span::new(0, 1)),
+                    Spanned::new('b', Span::new(2, 4))
+                ]
+        );
+        assert!(tq.span_between(0, 2) == Span::new(0, 4));
+        // indices past the end are clamped to it
+        assert!(tq.span_between(1, 10) == Span::new(2, 4));
+        assert!(tq.span_between(5, 10) == Span::new(4, 4));
+
+        let items = vec![(Ok('a'), 0..1), (Err(()), 1..2)];
+        let err = TokenQueue::<Spanned<char>>::from_spanned(items).unwrap_err();
+        assert!(matches!(err, LexError::Unmatched { pos: 1 }));
+
+        Ok(())
+    }
+
+    /// Parses digits like a `nom` parser, returning the rest of the input
+    /// first.
+    fn digits(input: &[char]) -> Result<(&[char], u32), &'static str> {
+        let len = input.iter().take_while(|c| c.is_ascii_digit()).count();
+        if len == 0 {
+            return Err("Expected a digit!");
+        }
+        let value = input[..len]
+            .iter()
+            .fold(0, |value, c| value * 10 + c.to_digit(10).unwrap());
+        Ok((&input[len..], value))
+    }
+
+    #[test]
+    fn test_parse_slice() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from("12+3".chars().collect::<Vec<_>>());
+
+        assert!(tq.parse_slice(digits)? == 12);
+        assert!(tq.remaining() == ['+', '3']);
+        let err = tq.parse_slice(digits).unwrap_err();
+        assert!(err == ParseError::at(2, "Expected a digit!"));
+        tq.consume_eq('+')?;
+        assert!(tq.parse_slice(digits)? == 3 && tq.is_consumed());
+
+        Ok(())
+    }
+
+    fn parse_parens(tq: &TokenQueue<char>) -> ParseResult<String> {
+        let mut tq = tq.clone();
+        tq.consume_eq('(')?;
+        let mut letters = String::new();
+        while let Ok(c) = tq.consume_matching(|c| c.is_alphabetic()) {
+            letters.push(*c);
+        }
+        tq.consume_eq(')')?;
+        Ok((letters, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_newlines() -> anyhow::Result<()> {
+        let mut tq =
+            TokenQueue::from("a\n\n(b\nc\n)\n".chars().collect::<Vec<_>>())
+                .with_newline(|c| *c == '\n');
+
+        assert!(tq.newlines_significant());
+        tq.consume_eq('a')?;
+        assert!(tq.at(&'\n'));
+        tq.skip_newlines()?;
+        assert!(tq.parse(parse_parens).is_err() && tq.get_idx() == 3);
+        assert!(tq.parse_with_newlines(false, parse_parens)? == "bc");
+        assert!(tq.newlines_significant() && tq.at(&'\n'));
+        tq.consume_eq('\n')?;
+        assert!(tq.is_consumed());
+
+        let tq = TokenQueue::from(vec!['\n', '\n'])
+            .with_newline(|c| *c == '\n')
+            .with_newlines_significant(false);
+        assert!(tq.is_consumed() && tq.peek().is_err());
+
+        Ok(())
+    }
+
+    /// Parses `a`* by trying every split into two halves, which takes
+    /// exponential time.
+    fn parse_slowly(tq: &TokenQueue<char>) -> ParseResult<()> {
+        let mut tq = tq.clone();
+
+        if tq.consume_eq('a').is_ok() {
+            let mut backtrack = tq.clone();
+            backtrack.parse(parse_slowly)?;
+            tq.parse(parse_slowly)?;
+        }
+
+        Ok(((), tq.get_idx()))
+    }
+
+    /// Parses `a` `b`, warning that `a` is deprecated.
+    fn parse_deprecated(tq: &TokenQueue<char>) -> ParseResult<()> {
+        let mut tq = tq.clone();
+        tq.consume_eq('a')?;
+        tq.warn(Diagnostic::warning("`a` is deprecated", Span::new(0, 1)));
+        tq.consume_eq('b')?;
+        Ok(((), tq.get_idx()))
+    }
+
+    #[test]
+    fn test_warnings() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a', 'b']);
+        tq.parse(parse_deprecated)?;
+        let warnings = tq.take_warnings();
+        assert!(
+            warnings
+                == [Diagnostic::warning("`a` is deprecated", Span::new(0, 1))]
+        );
+        assert!(tq.take_warnings().is_empty());
+
+        // warnings from a failed parse are discarded
+        let mut tq = TokenQueue::from(vec!['a', 'c']);
+        assert!(tq.parse(parse_deprecated).is_err());
+        assert!(tq.take_warnings().is_empty());
+
+        Ok(())
+    }
+
+    /// Collects the names declared by `f<name>` items, skipping calls.
+    fn scan_declarations(
+        tq: &TokenQueue<char>,
+        declared: &mut HashSet<char>,
+    ) -> ParseResult<()> {
+        let mut tq = tq.clone();
+        while let Ok(&item) = tq.consume() {
+            let name = *tq.consume()?;
+            if item == 'f' {
+                declared.insert(name);
+            }
+        }
+        Ok(((), tq.get_idx()))
+    }
+
+    /// Parses `f<name>` and `c<name>` items, returning the names called.
+    fn parse_calls(
+        tq: &TokenQueue<char>,
+        declared: &mut HashSet<char>,
+    ) -> ParseResult<Vec<char>> {
+        let mut tq = tq.clone();
+        let mut calls = Vec::new();
+        while let Ok(&item) = tq.consume() {
+            let name = *tq.consume()?;
+            if item == 'c' {
+                if !declared.contains(&name) {
+                    return Err(ParseError::at(
+                        tq.get_idx() - 1,
+                        format!("`{name}` is not declared!"),
+                    ));
+                }
+                calls.push(name);
+            }
+        }
+        Ok((calls, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_parse_two_phase() -> anyhow::Result<()> {
+        // `a` is called before it's declared
+        let mut tq = TokenQueue::from("cafacb".chars().collect::<Vec<_>>());
+        let mut declared = HashSet::new();
+        let err = tq
+            .parse_two_phase(scan_declarations, parse_calls, &mut declared)
+            .unwrap_err();
+        assert!(err == ParseError::at(5, "`b` is not declared!"));
+        assert!(tq.get_idx() == 0);
+
+        let mut tq = TokenQueue::from("cafacafb".chars().collect::<Vec<_>>());
+        let mut declared = HashSet::new();
+        let calls =
+            tq.parse_two_phase(scan_declarations, parse_calls, &mut declared)?;
+        assert!(calls == ['a', 'a'] && tq.is_consumed());
+        assert!(declared == HashSet::from(['a', 'b']));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_input() {
+        let status = |s: &str| {
+            TokenQueue::from(s.chars().collect::<Vec<_>>())
+                .with_newline(|c| *c == '\n')
+                .with_newlines_significant(false)
+                .parse_input(parse_nested)
+        };
+        assert!(status("(())") == ParseStatus::Complete(2));
+        assert!(matches!(status("(()\n"), ParseStatus::Incomplete(_)));
+        assert!(matches!(status("(()]"), ParseStatus::Error(_)));
+        assert!(
+            status("()(")
+                == ParseStatus::Error(ParseError::at(
+                    2,
+                    "Expected the end of input!"
+                ))
+        );
+    }
+
+    #[test]
+    fn test_fuel() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['a'; 4]).with_fuel(1000);
+        tq.parse(parse_slowly)?;
+        assert!(tq.remaining_fuel().is_some_and(|fuel| fuel < 1000));
+
+        let mut tq = TokenQueue::from(vec!['a'; 64]).with_fuel(1000);
+        let err = tq.parse(parse_slowly).unwrap_err();
+        assert!(err.message() == "Ran out of fuel while parsing!");
+        assert!(tq.remaining_fuel() == Some(0));
+
+        Ok(())
+    }
+
+    /// Parses letters, reporting and skipping anything else to recover.
+    fn parse_recovering(tq: &TokenQueue<char>) -> ParseResult<usize> {
+        let mut tq = tq.clone();
+        let mut letters = 0;
+        while !tq.is_consumed() {
+            let idx = tq.get_idx();
+            match tq.consume_matching(char::is_ascii_alphabetic) {
+                Ok(_) => letters += 1,
+                Err(err) => {
+                    let span = Span::new(idx, idx + 1);
+                    tq.warn(Diagnostic::error(err.message(), span));
+                    tq.increment()?;
+                }
+            }
+        }
+        Ok((letters, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_max_diagnostics() -> anyhow::Result<()> {
+        let mut tq =
+            TokenQueue::from(vec!['a', '1', '2']).with_max_diagnostics(2);
+        assert!(tq.parse(parse_recovering)? == 1);
+        assert!(!tq.has_given_up() && tq.take_warnings().len() == 2);
+
+        let mut tq = TokenQueue::from(vec!['1', 'a', '2', '3', '4', 'b'])
+            .with_max_diagnostics(2);
+        let err = tq.parse(parse_recovering).unwrap_err();
+        assert!(err.message() == "Too many errors, giving up!" && err.is_cut());
+        assert!(tq.has_given_up());
+        let warnings = tq.take_warnings();
+        assert!(warnings.len() == 3);
+        assert!(
+            warnings[2]
+                == Diagnostic::new(
+                    Severity::Note,
+                    "Too many errors, giving up!",
+                    Span::new(3, 4)
+                )
+        );
+
+        Ok(())
+    }
+}
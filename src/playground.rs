@@ -0,0 +1,170 @@
+//! A lexer and parser pipeline driven by an EBNF grammar, for running
+//! `rlrl`-based tooling in browser playgrounds.
+//!
+//! [Playground] only takes and returns strings, with results encoded as
+//! JSON, so its methods can be exported to JavaScript from a `cdylib` crate
+//! with thin `#[wasm_bindgen]` wrappers. This crate doesn't depend on
+//! `wasm-bindgen` itself, so nothing here is specific to WebAssembly.
+
+use crate::diagnostic::Diagnostic;
+use crate::dynamic::DynamicToken;
+use crate::earley::{EarleyParser, ParseTree};
+use crate::grammar::{Grammar, TerminalLexer};
+use crate::json::json_string;
+use crate::parse::TokenQueue;
+use crate::span::Span;
+
+/// Lexes and parses input with a grammar loaded from EBNF. Terminals are
+/// quoted literals or identifiers defined by a `/regex/`, and whitespace
+/// between tokens is ignored.
+pub struct Playground {
+    lexer: TerminalLexer,
+    parser: EarleyParser<DynamicToken>,
+}
+
+impl Playground {
+    /// Load the EBNF grammar `grammar`, returning an error message if it's
+    /// invalid.
+    pub fn new(grammar: &str) -> Result<Self, String> {
+        let build = || -> crate::Result<Self> {
+            let grammar = Grammar::from_ebnf(grammar)?;
+            Ok(Self {
+                lexer: TerminalLexer::new(&grammar)?,
+                parser: EarleyParser::new(&grammar, DynamicToken::kind)?,
+            })
+        };
+        build().map_err(|err| err.to_string())
+    }
+
+    /// Lex `input`, returning a JSON array of `{"kind", "start", "end"}`
+    /// objects, one per token, with byte offsets. On failure, returns a JSON
+    /// array of diagnostics instead.
+    pub fn lex(&self, input: &str) -> Result<String, String> {
+        let tokens = self.tokenize(input).map_err(diagnostics_json)?;
+        let entries: Vec<String> = tokens
+            .iter()
+            .map(|token| {
+                format!(
+                    r#"{{"kind":{},"start":{},"end":{}}}"#,
+                    json_string(&token.kind),
+                    token.span.start,
+                    token.span.end
+                )
+            })
+            .collect();
+        Ok(format!("[{}]", entries.join(",")))
+    }
+
+    /// Parse `input`, returning its parse tree as JSON. Nodes are
+    /// `{"rule", "children"}` objects and leaves are `{"kind", "text",
+    /// "start", "end"}` objects. If the input is ambiguous, one parse is
+    /// returned. On failure, returns a JSON array of diagnostics instead.
+    pub fn parse(&self, input: &str) -> Result<String, String> {
+        let tokens = self.tokenize(input).map_err(diagnostics_json)?;
+        let spans: Vec<Span> = tokens.iter().map(|token| token.span).collect();
+        let forest =
+            self.parser
+                .parse(&TokenQueue::from(tokens))
+                .map_err(|err| {
+                    // point at the token the error is at, or the end of input
+                    let span = err
+                        .idx()
+                        .and_then(|idx| spans.get(idx).copied())
+                        .unwrap_or(Span::new(input.len(), input.len()));
+                    diagnostics_json(vec![Diagnostic::error(
+                        err.to_string(),
+                        span,
+                    )])
+                })?;
+        let tree = forest
+            .trees(1)
+            .into_iter()
+            .next()
+            .expect("A successful parse has at least one tree");
+        Ok(tree_json(&tree, input, &spans))
+    }
+
+    fn tokenize(
+        &self,
+        input: &str,
+    ) -> Result<Vec<DynamicToken>, Vec<Diagnostic>> {
+        self.lexer.lex(input).map_err(|err| {
+            let pos = err.pos().unwrap_or(0);
+            vec![Diagnostic::error(err.to_string(), Span::new(pos, pos))]
+        })
+    }
+}
+
+fn tree_json(tree: &ParseTree, input: &str, spans: &[Span]) -> String {
+    match tree {
+        ParseTree::Leaf { terminal, idx } => {
+            let span = spans[*idx];
+            format!(
+                r#"{{"kind":{},"text":{},"start":{},"end":{}}}"#,
+                json_string(terminal),
+                json_string(&input[span.start..span.end]),
+                span.start,
+                span.end
+            )
+        }
+        ParseTree::Node { lhs, children, .. } => {
+            let children: Vec<String> = children
+                .iter()
+                .map(|child| tree_json(child, input, spans))
+                .collect();
+            format!(
+                r#"{{"rule":{},"children":[{}]}}"#,
+                json_string(lhs),
+                children.join(",")
+            )
+        }
+    }
+}
+
+fn diagnostics_json(diagnostics: Vec<Diagnostic>) -> String {
+    let entries: Vec<String> = diagnostics
+        .iter()
+        .map(|diagnostic| {
+            format!(
+                r#"{{"severity":"{}","message":{},"start":{},"end":{}}}"#,
+                diagnostic.severity,
+                json_string(&diagnostic.message),
+                diagnostic.span.start,
+                diagnostic.span.end
+            )
+        })
+        .collect();
+    format!("[{}]", entries.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::playground::Playground;
+
+    const GRAMMAR: &str = r#"
+        sum = sum "+" num | num ;
+        num = /[0-9]+/ ;
+    "#;
+
+    #[test]
+    fn test_playground() {
+        let playground = Playground::new(GRAMMAR).unwrap();
+
+        assert!(
+            playground.lex("1 + 23").unwrap()
+                == r#"[{"kind":"num","start":0,"end":1},{"kind":"+","start":2,"end":3},{"kind":"num","start":4,"end":6}]"#
+        );
+        assert!(
+            playground.parse("1+2").unwrap()
+                == r#"{"rule":"sum","children":[{"rule":"sum","children":[{"kind":"num","text":"1","start":0,"end":1}]},{"kind":"+","text":"+","start":1,"end":2},{"kind":"num","text":"2","start":2,"end":3}]}"#
+        );
+
+        let err = playground.lex("1 ? 2").unwrap_err();
+        assert!(err.starts_with(r#"[{"severity":"error","message":"#));
+        assert!(err.ends_with(r#""start":2,"end":2}]"#));
+        let err = playground.parse("1 + + 2").unwrap_err();
+        assert!(err.ends_with(r#""start":4,"end":5}]"#));
+
+        assert!(Playground::new("sum = ").is_err());
+    }
+}
@@ -1,2 +1,18 @@
-pub use crate::lex::*;
-pub use crate::parse::*;
+//! Imports everything needed to write a lexer and a hand-written parser:
+//! `use rlrl::prelude::*;`. With the `derive` feature, this includes the
+//! `Parse` derive macro along with the [Parse] trait.
+//!
+//! # Stability
+//!
+//! Items are only added to the prelude in minor releases and only removed in
+//! major releases. Names likely to clash with other preludes, like `Result`
+//! and `Error`, are never exported; use [crate::Result] and
+//! [crate::parse::Result] by path.
+pub use crate::expect;
+pub use crate::keyword::Identifier;
+pub use crate::lex::{HandlerError, LexError, LexResult, Lexer};
+pub use crate::parse::{
+    Parse, ParseError, ParseFn, ParseResult, Separated, TokenQueue, Trailing,
+};
+pub use crate::span::{Span, Spanned};
+pub use crate::value::TokenValue;
@@ -0,0 +1,236 @@
+//! A document algebra for pretty-printing, in the style of Wadler's "A
+//! prettier printer", so languages parsed with `rlrl` can be formatted too.
+//!
+//! A [Doc] describes text with the places it may be broken across lines.
+//! [Doc::group] marks a part that's printed on one line if it fits in the
+//! width and has every [Doc::line] in it broken otherwise.
+
+/// Represents a document to be laid out by [Doc::render].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Doc {
+    /// The empty document.
+    Nil,
+    /// Text, which shouldn't contain newlines.
+    Text(String),
+    /// A line break, printed as the given text if its group is flat.
+    Line(&'static str),
+    /// A line break that's always taken, breaking every enclosing group.
+    HardLine,
+    /// The documents one after another.
+    Concat(Vec<Doc>),
+    /// The document with lines broken inside it indented by the given number
+    /// of extra spaces.
+    Nest(usize, Box<Doc>),
+    /// The document, printed flat if it fits.
+    Group(Box<Doc>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Flat,
+    Break,
+}
+
+impl Doc {
+    /// Create a document containing the text `text`.
+    pub fn text(text: impl Into<String>) -> Self {
+        Self::Text(text.into())
+    }
+
+    /// Create a line break that's printed as a space if its group is flat.
+    pub fn line() -> Self {
+        Self::Line(" ")
+    }
+
+    /// Create a line break that's printed as nothing if its group is flat.
+    pub fn softline() -> Self {
+        Self::Line("")
+    }
+
+    /// Create a line break that's always taken.
+    pub fn hardline() -> Self {
+        Self::HardLine
+    }
+
+    /// Create a document from `docs` one after another.
+    pub fn concat(docs: impl IntoIterator<Item = Doc>) -> Self {
+        Self::Concat(docs.into_iter().collect())
+    }
+
+    /// Create a document from `docs` with `separator` between each pair.
+    pub fn join(docs: impl IntoIterator<Item = Doc>, separator: Doc) -> Self {
+        let mut joined = Vec::new();
+        for (i, doc) in docs.into_iter().enumerate() {
+            if i > 0 {
+                joined.push(separator.clone());
+            }
+            joined.push(doc);
+        }
+        Self::Concat(joined)
+    }
+
+    /// Follow this document with `other`.
+    pub fn append(self, other: Doc) -> Self {
+        match self {
+            Self::Concat(mut docs) => {
+                docs.push(other);
+                Self::Concat(docs)
+            }
+            doc => Self::Concat(vec![doc, other]),
+        }
+    }
+
+    /// Indent lines broken inside this document by `indent` extra spaces.
+    pub fn nest(self, indent: usize) -> Self {
+        Self::Nest(indent, Box::new(self))
+    }
+
+    /// Print this document on one line if it fits, and break its lines
+    /// otherwise.
+    pub fn group(self) -> Self {
+        Self::Group(Box::new(self))
+    }
+
+    /// Lay the document out in lines of at most `width` characters where
+    /// possible, returning the text.
+    pub fn render(&self, width: usize) -> String {
+        let mut out = String::new();
+        let mut col = 0;
+        // the indentation of a new line is only written before the text on
+        // it, so blank lines don't end in whitespace
+        let mut pending_indent = 0;
+        let mut stack: Vec<(usize, Mode, &Doc)> = vec![(0, Mode::Break, self)];
+
+        while let Some((indent, mode, doc)) = stack.pop() {
+            match doc {
+                Self::Nil => {}
+                Self::Text(text) => {
+                    write_indented(&mut out, &mut pending_indent, text);
+                    col += text.chars().count();
+                }
+                Self::Line(flat) if mode == Mode::Flat => {
+                    write_indented(&mut out, &mut pending_indent, flat);
+                    col += flat.chars().count();
+                }
+                Self::Line(_) | Self::HardLine => {
+                    out.push('\n');
+                    pending_indent = indent;
+                    col = indent;
+                }
+                Self::Concat(docs) => stack
+                    .extend(docs.iter().rev().map(|doc| (indent, mode, doc))),
+                Self::Nest(extra, doc) => {
+                    stack.push((indent + extra, mode, doc))
+                }
+                Self::Group(doc) => {
+                    let fits = mode == Mode::Flat
+                        || fits(width.saturating_sub(col), doc, &stack);
+                    let mode = if fits { Mode::Flat } else { Mode::Break };
+                    stack.push((indent, mode, doc));
+                }
+            }
+        }
+
+        out
+    }
+}
+
+/// Write `text` to `out`, after the `pending` indentation of its line if it's
+/// the first text on the line.
+fn write_indented(out: &mut String, pending: &mut usize, text: &str) {
+    if !text.is_empty() {
+        out.extend(std::iter::repeat_n(' ', *pending));
+        *pending = 0;
+        out.push_str(text);
+    }
+}
+
+/// Return true if `doc` printed flat, followed by `rest` up to its next line
+/// break, takes at most `width` characters.
+fn fits(width: usize, doc: &Doc, rest: &[(usize, Mode, &Doc)]) -> bool {
+    let mut width = width as isize;
+    let mut stack: Vec<(Mode, &Doc)> = vec![(Mode::Flat, doc)];
+    let mut rest = rest.iter().rev();
+
+    while width >= 0 {
+        let (mode, doc) = match stack.pop() {
+            Some(next) => next,
+            None => match rest.next() {
+                Some(&(_, mode, doc)) => (mode, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Nil => {}
+            Doc::Text(text) => width -= text.chars().count() as isize,
+            Doc::Line(flat) if mode == Mode::Flat => {
+                width -= flat.chars().count() as isize
+            }
+            // a hard line can't be printed flat
+            Doc::HardLine if mode == Mode::Flat => return false,
+            Doc::Line(_) | Doc::HardLine => return true,
+            Doc::Concat(docs) => {
+                stack.extend(docs.iter().rev().map(|doc| (mode, doc)))
+            }
+            Doc::Nest(_, doc) | Doc::Group(doc) => stack.push((mode, doc)),
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pretty::Doc;
+
+    fn list(items: &[&str]) -> Doc {
+        Doc::text("[")
+            .append(
+                Doc::softline()
+                    .append(Doc::join(
+                        items.iter().map(|item| Doc::text(*item)),
+                        Doc::text(",").append(Doc::line()),
+                    ))
+                    .nest(2),
+            )
+            .append(Doc::softline())
+            .append(Doc::text("]"))
+            .group()
+    }
+
+    #[test]
+    fn test_render() {
+        let doc = list(&["1", "2", "3"]);
+        assert!(doc.render(80) == "[1, 2, 3]");
+        assert!(doc.render(9) == "[1, 2, 3]");
+        assert!(doc.render(8) == "[\n  1,\n  2,\n  3\n]");
+        assert!(Doc::Nil.render(80).is_empty());
+    }
+
+    #[test]
+    fn test_render_nested() {
+        let doc = Doc::text("f")
+            .append(list(&["aaaa", "bb"]))
+            .append(Doc::text(";"));
+        // the text after the group counts towards whether it fits
+        assert!(doc.render(12) == "f[aaaa, bb];");
+        assert!(doc.render(11) == "f[\n  aaaa,\n  bb\n];");
+
+        let doc = Doc::text("{")
+            .append(Doc::hardline().append(Doc::text("a")).nest(4))
+            .append(Doc::line())
+            .append(Doc::text("}"))
+            .group();
+        assert!(doc.render(80) == "{\n    a\n}");
+
+        // blank lines aren't indented, but spaces in the text are kept
+        let doc = Doc::text("x")
+            .append(Doc::hardline())
+            .append(Doc::hardline())
+            .append(Doc::text("a  "))
+            .append(Doc::hardline())
+            .append(Doc::text("b"))
+            .nest(2);
+        assert!(doc.render(80) == "x\n\n  a  \n  b");
+    }
+}
@@ -0,0 +1,170 @@
+//! Bottom-up rewriting of syntax trees, for passes like constant folding or
+//! desugaring. A tree type implements [Rewritable] to say where its children
+//! are, and a pass implements [Rewriter] to say what to do with each node.
+//!
+//! ```
+//! use rlrl::rewrite::{Rewritable, Transform};
+//!
+//! enum Expr {
+//!     Num(i64),
+//!     Var(String),
+//!     Add(Box<Expr>, Box<Expr>),
+//! }
+//!
+//! impl Rewritable for Expr {
+//!     fn map_children(self, f: &mut dyn FnMut(Self) -> Self) -> Self {
+//!         match self {
+//!             Expr::Add(lhs, rhs) => {
+//!                 Expr::Add(Box::new(f(*lhs)), Box::new(f(*rhs)))
+//!             }
+//!             leaf => leaf,
+//!         }
+//!     }
+//! }
+//!
+//! // `(1 + 2) + x` folds to `3 + x`
+//! let expr = Expr::Add(
+//!     Box::new(Expr::Add(Box::new(Expr::Num(1)), Box::new(Expr::Num(2)))),
+//!     Box::new(Expr::Var("x".into())),
+//! );
+//! let folded = expr.rewrite_with(&mut |expr| match expr {
+//!     Expr::Add(lhs, rhs) => match (*lhs, *rhs) {
+//!         (Expr::Num(a), Expr::Num(b)) => {
+//!             Transform::Replace(Expr::Num(a + b))
+//!         }
+//!         (lhs, rhs) => {
+//!             Transform::Keep(Expr::Add(lhs.into(), rhs.into()))
+//!         }
+//!     },
+//!     expr => Transform::Keep(expr),
+//! });
+//! let Expr::Add(lhs, _) = folded else { panic!() };
+//! assert!(matches!(*lhs, Expr::Num(3)));
+//! ```
+
+use crate::dynamic::DynamicAst;
+
+/// What a [Rewriter] does with a node.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transform<T> {
+    /// Keep the node, which may still have been changed in place.
+    Keep(T),
+    /// Replace the node with a new one, which is rewritten in turn.
+    Replace(T),
+}
+
+impl<T> Transform<T> {
+    /// Take the node, whether kept or replaced.
+    pub fn into_inner(self) -> T {
+        match self {
+            Self::Keep(node) | Self::Replace(node) => node,
+        }
+    }
+}
+
+/// A pass over trees of type `T`, like constant folding. Implemented for
+/// closures taking a node and returning a [Transform].
+pub trait Rewriter<T> {
+    /// Rewrite `node`, whose children have already been rewritten.
+    fn rewrite(&mut self, node: T) -> Transform<T>;
+}
+
+impl<T, F> Rewriter<T> for F
+where
+    F: FnMut(T) -> Transform<T>,
+{
+    fn rewrite(&mut self, node: T) -> Transform<T> {
+        self(node)
+    }
+}
+
+/// Implemented by tree types that can be rewritten by a [Rewriter].
+pub trait Rewritable: Sized {
+    /// Rebuild the node with `f` applied to each of its children, in order.
+    /// Leaves are returned as they are.
+    fn map_children(self, f: &mut dyn FnMut(Self) -> Self) -> Self;
+
+    /// Rewrite the tree bottom-up with `rewriter`: the children of each node
+    /// are rewritten before the node itself. A [Transform::Replace]ment is
+    /// rewritten again, children first, so desugared nodes pass through the
+    /// same pass; rewriters must eventually keep every node they're given.
+    fn rewrite_with(self, rewriter: &mut impl Rewriter<Self>) -> Self {
+        let node = self.map_children(&mut |child| child.rewrite_with(rewriter));
+        match rewriter.rewrite(node) {
+            Transform::Keep(node) => node,
+            Transform::Replace(node) => node.rewrite_with(rewriter),
+        }
+    }
+}
+
+impl Rewritable for DynamicAst {
+    fn map_children(self, f: &mut dyn FnMut(Self) -> Self) -> Self {
+        match self {
+            Self::Token(token) => Self::Token(token),
+            Self::Node {
+                rule,
+                children,
+                span,
+            } => Self::Node {
+                rule,
+                children: children.into_iter().map(f).collect(),
+                span,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::{DynamicAst, DynamicToken};
+    use crate::rewrite::{Rewritable, Transform};
+    use crate::span::Span;
+
+    fn token(text: &str, start: usize) -> DynamicAst {
+        DynamicAst::Token(DynamicToken {
+            kind: text.into(),
+            text: text.into(),
+            span: Span::new(start, start + text.len()),
+        })
+    }
+
+    fn node(rule: &str, children: Vec<DynamicAst>) -> DynamicAst {
+        let span = Span::new(
+            children.first().map_or(0, |child| child.span().start),
+            children.last().map_or(0, |child| child.span().end),
+        );
+        DynamicAst::Node {
+            rule: rule.into(),
+            children,
+            span,
+        }
+    }
+
+    #[test]
+    fn test_rewrite_with() {
+        // `((a))` desugars to `a`
+        let ast = node(
+            "paren",
+            vec![
+                token("(", 0),
+                node(
+                    "paren",
+                    vec![token("(", 1), token("a", 2), token(")", 3)],
+                ),
+                token(")", 4),
+            ],
+        );
+        let mut rewrites = 0;
+        let ast = ast.rewrite_with(&mut |ast: DynamicAst| {
+            if ast.kind() != "paren" {
+                return Transform::Keep(ast);
+            }
+            rewrites += 1;
+            Transform::Replace(ast.children()[1].clone())
+        });
+        assert!(ast == token("a", 2));
+        assert!(rewrites == 2);
+
+        assert!(Transform::Replace(1).into_inner() == 1);
+    }
+}
@@ -0,0 +1,194 @@
+use crate::diagnostic::Diagnostic;
+use crate::span::Span;
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::hash::Hash;
+
+/// A value declared in a [ScopeStack], with the span it was declared at.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding<V> {
+    pub value: V,
+    pub span: Span,
+}
+
+/// Returned by [ScopeStack::declare] when a name is declared twice in the
+/// same scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Redeclared {
+    /// Span of the declaration already in scope.
+    pub previous: Span,
+    /// Span of the rejected declaration.
+    pub span: Span,
+}
+
+impl Redeclared {
+    /// Create an error diagnostic at the rejected declaration of `name`,
+    /// noting where it was first declared.
+    pub fn diagnostic(&self, name: impl Display) -> Diagnostic {
+        Diagnostic::error(format!("`{name}` is already declared!"), self.span)
+            .with_note(format!("first declared at {}", self.previous))
+    }
+}
+
+impl Display for Redeclared {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Redeclared at {}, already declared at {}!",
+            self.span, self.previous
+        )
+    }
+}
+
+impl std::error::Error for Redeclared {}
+
+/// A stack of nested scopes mapping names of type `K` to values of type `V`,
+/// for resolving names while parsing, typically passed to parse functions as
+/// the context of
+/// [TokenQueue::parse_with_mut](crate::parse::TokenQueue::parse_with_mut).
+///
+/// Lookups search from the innermost scope out, so declarations shadow those
+/// in enclosing scopes. The stack starts with one global scope, which is
+/// never popped.
+#[derive(Debug, Clone)]
+pub struct ScopeStack<K, V> {
+    scopes: Vec<HashMap<K, Binding<V>>>,
+}
+
+impl<K, V> Default for ScopeStack<K, V> {
+    fn default() -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+        }
+    }
+}
+
+impl<K: Eq + Hash, V> ScopeStack<K, V> {
+    /// Create a stack with an empty global scope.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enter a new innermost scope.
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    /// Leave the innermost scope, returning its bindings, or `None` if it's
+    /// the global scope.
+    pub fn pop_scope(&mut self) -> Option<HashMap<K, Binding<V>>> {
+        match self.scopes.len() {
+            1 => None,
+            _ => self.scopes.pop(),
+        }
+    }
+
+    /// Get the number of scopes, counting the global scope.
+    pub fn depth(&self) -> usize {
+        self.scopes.len()
+    }
+
+    /// Declare `key` in the innermost scope, shadowing any declaration in an
+    /// enclosing scope. Returns an error, keeping the first declaration, if
+    /// `key` is already declared in the innermost scope.
+    pub fn declare(
+        &mut self,
+        key: K,
+        value: V,
+        span: Span,
+    ) -> Result<(), Redeclared> {
+        let scope = self.scopes.last_mut().expect("The global scope remains");
+        if let Some(previous) = scope.get(&key) {
+            return Err(Redeclared {
+                previous: previous.span,
+                span,
+            });
+        }
+        scope.insert(key, Binding { value, span });
+        Ok(())
+    }
+
+    /// Look up the innermost declaration of `key`.
+    pub fn lookup<Q>(&self, key: &Q) -> Option<&Binding<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.scopes.iter().rev().find_map(|scope| scope.get(key))
+    }
+
+    /// Look up the innermost declaration of `key`, mutably.
+    pub fn lookup_mut<Q>(&mut self, key: &Q) -> Option<&mut Binding<V>>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(key))
+    }
+
+    /// Return true if `key` is declared in the innermost scope.
+    pub fn is_declared_here<Q>(&self, key: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.scopes
+            .last()
+            .is_some_and(|scope| scope.contains_key(key))
+    }
+
+    /// Iterate over the names visible from the innermost scope, like
+    /// candidates for [suggest](crate::suggest::suggest). Shadowed names are
+    /// listed once.
+    pub fn visible(&self) -> impl Iterator<Item = &K> {
+        let scopes = &self.scopes;
+        scopes.iter().enumerate().rev().flat_map(move |(i, scope)| {
+            scope.keys().filter(move |key| {
+                !scopes[i + 1..].iter().any(|inner| inner.contains_key(*key))
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::scope::{Redeclared, ScopeStack};
+    use crate::span::Span;
+
+    #[test]
+    fn test_scope_stack() {
+        let mut scopes: ScopeStack<String, i64> = ScopeStack::new();
+        scopes.declare("x".into(), 1, Span::new(0, 1)).unwrap();
+        scopes.declare("y".into(), 2, Span::new(2, 3)).unwrap();
+
+        scopes.push_scope();
+        scopes.declare("x".into(), 3, Span::new(4, 5)).unwrap();
+        assert!(scopes.lookup("x").unwrap().value == 3);
+        assert!(scopes.lookup("y").unwrap().value == 2);
+        assert!(scopes.is_declared_here("x") && !scopes.is_declared_here("y"));
+        let mut visible: Vec<&String> = scopes.visible().collect();
+        visible.sort();
+        assert!(visible == ["x", "y"]);
+
+        let err = scopes.declare("x".into(), 4, Span::new(6, 7)).unwrap_err();
+        assert!(
+            err == Redeclared {
+                previous: Span::new(4, 5),
+                span: Span::new(6, 7)
+            }
+        );
+        let diagnostic = err.diagnostic("x");
+        assert!(diagnostic.message == "`x` is already declared!");
+        assert!(diagnostic.notes == ["first declared at 4..5"]);
+
+        scopes.lookup_mut("y").unwrap().value = 5;
+        assert!(scopes.pop_scope().is_some_and(|scope| scope.len() == 1));
+        assert!(scopes.lookup("x").unwrap().value == 1);
+        assert!(scopes.lookup("y").unwrap().value == 5);
+        assert!(scopes.pop_scope().is_none() && scopes.depth() == 1);
+    }
+}
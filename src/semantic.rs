@@ -0,0 +1,122 @@
+use crate::lex::LexerMatch;
+use crate::source::SourceFile;
+
+/// Implemented by token types to classify tokens for semantic highlighting
+/// with [semantic_tokens].
+pub trait TokenKind {
+    /// Get the index of the token's type in the language server's semantic
+    /// token legend, or `None` to leave the token unhighlighted.
+    fn token_type(&self) -> Option<u32>;
+
+    /// Get the bit set of the token's modifiers in the legend. The default
+    /// has no modifiers.
+    fn token_modifiers(&self) -> u32 {
+        0
+    }
+}
+
+/// Encode the tokens lexed from `source` as the `data` of Language Server
+/// Protocol `SemanticTokens`: five integers per token, giving the line and
+/// start column relative to the previous token, the length, the type and the
+/// modifiers. Columns and lengths count UTF-16 code units. Tokens spanning
+/// several lines are split into one entry per line.
+pub fn semantic_tokens<T: TokenKind>(
+    source: &SourceFile,
+    matches: &[LexerMatch<T>],
+) -> Vec<u32> {
+    let mut data = Vec::new();
+    let (mut prev_line, mut prev_col) = (0, 0);
+
+    for lexer_match in matches {
+        let Some(token_type) = lexer_match.token().token_type() else {
+            continue;
+        };
+        let modifiers = lexer_match.token().token_modifiers();
+        let span = lexer_match.span();
+        let (start_line, start_col) = source.utf16_line_col(span.start);
+        let (end_line, end_col) = source.utf16_line_col(span.end);
+
+        for line in start_line..=end_line {
+            let col = if line == start_line { start_col } else { 0 };
+            let end = match line == end_line {
+                true => end_col,
+                false => source.line(line).encode_utf16().count(),
+            };
+            if end <= col {
+                continue;
+            }
+            let delta_col = if line == prev_line {
+                col - prev_col
+            } else {
+                col
+            };
+            data.extend([
+                (line - prev_line) as u32,
+                delta_col as u32,
+                (end - col) as u32,
+                token_type,
+                modifiers,
+            ]);
+            (prev_line, prev_col) = (line, col);
+        }
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lex::{LexResult, Lexer};
+    use crate::semantic::{TokenKind, semantic_tokens};
+    use crate::source::SourceFile;
+    use std::error::Error;
+
+    enum Token {
+        Keyword,
+        Ident,
+        Str,
+        Punct,
+    }
+
+    impl TokenKind for Token {
+        fn token_type(&self) -> Option<u32> {
+            match self {
+                Self::Keyword => Some(0),
+                Self::Ident => Some(1),
+                Self::Str => Some(2),
+                Self::Punct => None,
+            }
+        }
+
+        fn token_modifiers(&self) -> u32 {
+            match self {
+                Self::Keyword => 0b1,
+                _ => 0,
+            }
+        }
+    }
+
+    #[test]
+    fn test_semantic_tokens() -> Result<(), Box<dyn Error>> {
+        let mut lexer: Lexer<Token> = Lexer::c_style();
+        lexer.add_keyword_rule("let", |_| LexResult::Token(Token::Keyword));
+        lexer.add_rule(r"[a-zé]+", |_| LexResult::Token(Token::Ident));
+        lexer.add_rule(r#""[^"]*""#, |_| LexResult::Token(Token::Str));
+        lexer.add_rule(r"[=;]", |_| LexResult::Token(Token::Punct));
+
+        let source = SourceFile::new("test", "let é = \"a\nbc\";\n  x;");
+        let matches = lexer.lex_matches(source.text())?;
+        assert!(
+            semantic_tokens(&source, &matches)
+                == vec![
+                    0, 0, 3, 0, 1, // let
+                    0, 4, 1, 1, 0, // é
+                    0, 4, 2, 2, 0, // "a
+                    1, 0, 3, 2, 0, // bc"
+                    1, 2, 1, 1, 0, // x
+                ]
+        );
+
+        Ok(())
+    }
+}
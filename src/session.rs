@@ -0,0 +1,381 @@
+//! Lexing and parsing input fed in pieces, like the lines of a REPL, where a
+//! statement may continue over several lines.
+//!
+//! ```
+//! use rlrl::lex::{LexResult, Lexer};
+//! use rlrl::session::{LexSession, LexStatus};
+//!
+//! let mut lexer: Lexer<String> = Lexer::c_style();
+//! lexer.add_rule(r#""[^"]*""#, |m| LexResult::Token(m.as_str().into()));
+//! lexer.add_rule(r"\w+", |m| LexResult::Token(m.as_str().into()));
+//!
+//! let mut session =
+//!     LexSession::new(&lexer).with_continuation("\"", "\"", "string");
+//! // the string is unterminated, so prompt for another line
+//! assert!(
+//!     session.feed("print \"hello\n").unwrap()
+//!         == LexStatus::Incomplete("string".into())
+//! );
+//! let LexStatus::Complete(lexed) = session.feed("world\"\n").unwrap() else {
+//!     panic!();
+//! };
+//! assert!(lexed.text(1) == Some("\"hello\nworld\""));
+//! ```
+
+use crate::lex::{HandlerError, LexError, Lexed, Lexer};
+use crate::parse::{ParseError, ParseFn, ParseStatus, TokenQueue};
+use crate::span::Spanned;
+
+/// A construct that can span several pieces of input, like a string or
+/// block comment, that a [LexSession] waits to see the end of.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Continuation {
+    open: String,
+    close: String,
+    what: String,
+}
+
+impl Continuation {
+    /// Scan `s` from byte `from`, inside the construct, for its close
+    /// delimiter, returning true if it's found. A close delimiter preceded
+    /// by a backslash doesn't count, and `escaped` is whether the character
+    /// before `from` is one. Otherwise `from` and `escaped` are left where
+    /// the scan can resume once more input is added.
+    fn closes(&self, s: &str, from: &mut usize, escaped: &mut bool) -> bool {
+        for (i, c) in s[*from..].char_indices() {
+            let i = *from + i;
+            // a close delimiter may start here once more input is added
+            if i + self.close.len() > s.len() {
+                *from = i;
+                return false;
+            }
+            if !*escaped && s[i..].starts_with(self.close.as_str()) {
+                return true;
+            }
+            *escaped = !*escaped && c == '\\';
+        }
+        *from = s.len();
+        false
+    }
+}
+
+/// A construct left open by the input a [LexSession] holds back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Open {
+    /// The index of the construct's [Continuation].
+    continuation: usize,
+    /// Where the construct starts in the input.
+    start: usize,
+    /// Where to resume scanning for the close delimiter.
+    resume: usize,
+    /// Whether the character before `resume` is an escaping backslash.
+    escaped: bool,
+}
+
+/// The result of feeding input to a [LexSession].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LexStatus<T> {
+    /// The input fed since the last complete result was lexed: the tokens,
+    /// with spans into the input they were lexed from.
+    Complete(Lexed<T>),
+    /// The input ends inside the construct described, like "string", so
+    /// more input is needed before it can be lexed.
+    Incomplete(String),
+}
+
+/// Lexes input fed in pieces with a [Lexer], holding input back while it
+/// ends inside a construct that continues onto the next piece, like an
+/// unterminated string or an open block comment. REPLs can use the
+/// [LexStatus::Incomplete] result to prompt for a continuation line.
+///
+/// Input is held back until it's complete, then lexed all at once, so the
+/// tokens are the same as if it had been given in one piece.
+pub struct LexSession<'l, T, E = LexError> {
+    lexer: &'l Lexer<T, E>,
+    continuations: Vec<Continuation>,
+    pending: String,
+    open: Option<Open>,
+}
+
+impl<'l, T, E: HandlerError> LexSession<'l, T, E> {
+    /// Create a session lexing with `lexer`, with no constructs that
+    /// continue over pieces of input.
+    pub fn new(lexer: &'l Lexer<T, E>) -> Self {
+        Self {
+            lexer,
+            continuations: Vec::new(),
+            pending: String::new(),
+            open: None,
+        }
+    }
+
+    /// Wait for more input while the input ends inside a construct starting
+    /// with `open` and ending with `close`, like a string between `"`s,
+    /// described as `what` in [LexStatus::Incomplete]. A `close` preceded by
+    /// a backslash doesn't end the construct.
+    ///
+    /// The construct is only recognized where a token (or input no rule
+    /// matches) starts, so an `open` inside another token, like a quote in
+    /// a comment, is ignored.
+    pub fn with_continuation(
+        mut self,
+        open: &str,
+        close: &str,
+        what: &str,
+    ) -> Self {
+        self.continuations.push(Continuation {
+            open: open.to_owned(),
+            close: close.to_owned(),
+            what: what.to_owned(),
+        });
+        self
+    }
+
+    /// Add `input` to the input held back, lexing it all if it's complete.
+    /// If lexing fails, the input held back is discarded.
+    pub fn feed(&mut self, input: &str) -> Result<LexStatus<T>, E> {
+        self.pending.push_str(input);
+        if let Some(what) = self.incomplete() {
+            return Ok(LexStatus::Incomplete(what.to_owned()));
+        }
+        self.finish().map(LexStatus::Complete)
+    }
+
+    /// Lex the input held back, whether or not it's complete, like at the
+    /// end of input.
+    pub fn finish(&mut self) -> Result<Lexed<T>, E> {
+        self.open = None;
+        self.lexer.lex_owned(std::mem::take(&mut self.pending))
+    }
+
+    /// Borrow the input held back.
+    pub fn pending(&self) -> &str {
+        &self.pending
+    }
+
+    /// Discard the input held back, like when a REPL user cancels a
+    /// statement.
+    pub fn reset(&mut self) {
+        self.pending.clear();
+        self.open = None;
+    }
+
+    /// Find the first construct left open by the input held back, checking
+    /// where each token or unmatched region starts. Input before a construct
+    /// found open by an earlier call isn't checked again, and while it stays
+    /// open only the input added since is scanned.
+    fn incomplete(&mut self) -> Option<&str> {
+        let s = self.pending.as_str();
+        let from = match self.open.take() {
+            Some(mut open) => {
+                let continuation = &self.continuations[open.continuation];
+                if !continuation.closes(s, &mut open.resume, &mut open.escaped)
+                {
+                    self.open = Some(open);
+                    return Some(&continuation.what);
+                }
+                open.start
+            }
+            None => self.lexer.skip_preamble(s, None),
+        };
+
+        for (_, span) in self.lexer.regions(s, from) {
+            for (idx, continuation) in self.continuations.iter().enumerate() {
+                if !s[span.start..].starts_with(continuation.open.as_str()) {
+                    continue;
+                }
+                let mut resume = span.start + continuation.open.len();
+                let mut escaped = false;
+                if !continuation.closes(s, &mut resume, &mut escaped) {
+                    self.open = Some(Open {
+                        continuation: idx,
+                        start: span.start,
+                        resume,
+                        escaped,
+                    });
+                    return Some(&continuation.what);
+                }
+            }
+        }
+        None
+    }
+}
+
+/// Lexes and parses input fed in pieces, like a [LexSession], also holding
+/// input back while it parses up to the end and needs more tokens, like a
+/// statement missing its closing `}`. Each complete piece of input is parsed
+/// with a [ParseFn] over its spanned tokens.
+pub struct ParseSession<'l, T, O, E = LexError> {
+    lex: LexSession<'l, T, E>,
+    parse_fn: ParseFn<Spanned<T>, O>,
+}
+
+impl<'l, T, O, E: HandlerError> ParseSession<'l, T, O, E> {
+    /// Create a session lexing with `lex` and parsing with `parse_fn`.
+    pub fn new(
+        lex: LexSession<'l, T, E>,
+        parse_fn: ParseFn<Spanned<T>, O>,
+    ) -> Self {
+        Self { lex, parse_fn }
+    }
+
+    /// Add `input` to the input held back, and parse it all if it lexes and
+    /// parses to the end. [ParseStatus::Incomplete] means the input should
+    /// be continued, and holds the error at the end of it; an unterminated
+    /// construct of the [LexSession] is reported without an index. The input
+    /// held back is discarded if lexing or parsing fails.
+    pub fn feed(&mut self, input: &str) -> Result<ParseStatus<O>, E> {
+        let lexed = match self.lex.feed(input)? {
+            LexStatus::Complete(lexed) => lexed,
+            LexStatus::Incomplete(what) => {
+                let err = ParseError::new(format!("Unterminated {what}!"));
+                return Ok(ParseStatus::Incomplete(err));
+            }
+        };
+        let (source, matches) = lexed.into_parts();
+        let tokens: Vec<Spanned<T>> =
+            matches.into_iter().map(Spanned::from).collect();
+        let status = TokenQueue::from(tokens).parse_input(self.parse_fn);
+        // hold the input back again until the rest of it is fed
+        if matches!(status, ParseStatus::Incomplete(_)) {
+            self.lex.pending = source;
+        }
+        Ok(status)
+    }
+
+    /// Borrow the input held back.
+    pub fn pending(&self) -> &str {
+        self.lex.pending()
+    }
+
+    /// Discard the input held back.
+    pub fn reset(&mut self) {
+        self.lex.reset();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lex::{LexResult, Lexer};
+    use crate::parse::{ParseError, ParseResult, ParseStatus, TokenQueue};
+    use crate::session::{LexSession, LexStatus, ParseSession};
+    use crate::span::Spanned;
+
+    fn setup_lexer() -> Lexer<String> {
+        let mut lexer = Lexer::c_style();
+        lexer.add_rule(r#""([^"\\]|\\.)*""#, |m| {
+            LexResult::Token(m.as_str().into())
+        });
+        lexer.add_rule(r"[\w/*]+", |m| LexResult::Token(m.as_str().into()));
+        lexer
+    }
+
+    fn tokens(status: LexStatus<String>) -> Vec<String> {
+        match status {
+            LexStatus::Complete(lexed) => lexed
+                .into_parts()
+                .1
+                .into_iter()
+                .map(|m| m.into_token())
+                .collect(),
+            LexStatus::Incomplete(what) => panic!("Incomplete {what}"),
+        }
+    }
+
+    #[test]
+    fn test_lex_session() -> anyhow::Result<()> {
+        let lexer = setup_lexer();
+        let mut session = LexSession::new(&lexer)
+            .with_continuation("\"", "\"", "string")
+            .with_continuation("/*", "*/", "block comment");
+
+        assert!(tokens(session.feed("a b\n")?) == ["a", "b"]);
+
+        let status = session.feed("a /* one\n")?;
+        assert!(status == LexStatus::Incomplete("block comment".into()));
+        assert!(session.pending() == "a /* one\n");
+        assert!(tokens(session.feed("two */ b\n")?) == ["a", "b"]);
+
+        // escaped quotes don't close strings, and quotes in comments don't
+        // open them
+        let status = session.feed(r#""x\" // "#)?;
+        assert!(status == LexStatus::Incomplete("string".into()));
+        assert!(tokens(session.feed("\"\n")?) == [r#""x\" // ""#]);
+        assert!(tokens(session.feed("a // \"\n")?) == ["a"]);
+
+        session.feed("\"abc")?;
+        session.reset();
+        assert!(session.pending().is_empty());
+
+        session.feed("\"abc")?;
+        assert!(session.finish().is_err());
+
+        // delimiters and escapes split between pieces of input
+        let status = session.feed("/* a *")?;
+        assert!(status == LexStatus::Incomplete("block comment".into()));
+        assert!(tokens(session.feed("/ b\n")?) == ["b"]);
+        session.feed("\"a\\")?;
+        let status = session.feed("\"")?;
+        assert!(status == LexStatus::Incomplete("string".into()));
+        assert!(tokens(session.feed("\"\n")?) == ["\"a\\\"\""]);
+
+        // a skipped shebang line doesn't open a string
+        let lexer = setup_lexer().with_skip_shebang(true);
+        let mut session =
+            LexSession::new(&lexer).with_continuation("\"", "\"", "string");
+        assert!(tokens(session.feed("#!/bin/lang \"\na\n")?) == ["a"]);
+
+        Ok(())
+    }
+
+    /// Parses a word or a `{` ... `}` block of them, returning the number of
+    /// words.
+    fn parse_block(tq: &TokenQueue<Spanned<String>>) -> ParseResult<usize> {
+        let mut tq = tq.clone();
+        if tq.consume_eq("{".to_owned()).is_err() {
+            let word = tq.consume()?;
+            if word.value == "}" {
+                return Err(ParseError::at(
+                    tq.get_idx() - 1,
+                    "Unexpected `}`!",
+                ));
+            }
+            return Ok((1, tq.get_idx()));
+        }
+        let mut words = 0;
+        while !tq.at(&"}".to_owned()) {
+            words += tq.parse(parse_block)?;
+        }
+        tq.increment()?;
+        Ok((words, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_parse_session() -> anyhow::Result<()> {
+        let mut lexer = Lexer::new();
+        lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+        lexer.add_rule(r#"[{}]|\w+|"[^"]*""#, |m| {
+            LexResult::Token(m.as_str().to_owned())
+        });
+        let lex =
+            LexSession::new(&lexer).with_continuation("\"", "\"", "string");
+        let mut session = ParseSession::new(lex, parse_block);
+
+        assert!(session.feed("word\n")? == ParseStatus::Complete(1));
+        assert!(matches!(session.feed("{ a\n")?, ParseStatus::Incomplete(_)));
+        let status = session.feed("\"b\n")?;
+        assert!(
+            status
+                == ParseStatus::Incomplete(ParseError::new(
+                    "Unterminated string!"
+                ))
+        );
+        assert!(session.feed("c\" }\n")? == ParseStatus::Complete(2));
+        assert!(session.pending().is_empty());
+
+        assert!(matches!(session.feed("} a\n")?, ParseStatus::Error(_)));
+        assert!(session.pending().is_empty());
+
+        Ok(())
+    }
+}
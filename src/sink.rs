@@ -0,0 +1,215 @@
+//! Pluggable reporting of diagnostics: lexers and parse drivers push the
+//! warnings and errors they produce into an [ErrorSink], which decides what
+//! to do with them, so whether to collect every problem, stop at the first
+//! error, or print them as they come is chosen by the caller, not the
+//! grammar.
+//!
+//! ```
+//! use rlrl::diagnostic::{Diagnostic, Severity};
+//! use rlrl::lex::{LexResult, Lexer};
+//! use rlrl::sink::FailFast;
+//!
+//! let mut lexer: Lexer<char> = Lexer::new();
+//! lexer.add_rule(r"[a-z]", |m| {
+//!     LexResult::Token(m.as_str().chars().next().unwrap())
+//! });
+//!
+//! let mut sink = FailFast::new(Vec::new());
+//! assert!(lexer.lex_reporting("ab", &mut sink).is_some());
+//! assert!(lexer.lex_reporting("a?", &mut sink).is_none());
+//! let diagnostics = sink.into_inner();
+//! assert!(diagnostics.len() == 1);
+//! assert!(diagnostics[0].severity == Severity::Error);
+//! ```
+
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::source::SourceFile;
+use std::ops::ControlFlow;
+
+/// A destination for the diagnostics produced while lexing and parsing.
+pub trait ErrorSink {
+    /// Report `diagnostic`. Returning [ControlFlow::Break] asks the caller
+    /// to stop and fail, even if the diagnostic is only a warning.
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()>;
+}
+
+/// Collects every diagnostic, in order, and never stops.
+impl ErrorSink for Vec<Diagnostic> {
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        self.push(diagnostic);
+        ControlFlow::Continue(())
+    }
+}
+
+impl<S: ErrorSink + ?Sized> ErrorSink for &mut S {
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        (**self).report(diagnostic)
+    }
+}
+
+/// Passes diagnostics on to another sink, stopping at the first error.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct FailFast<S> {
+    inner: S,
+}
+
+impl<S> FailFast<S> {
+    /// Create a sink passing diagnostics on to `inner`.
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    /// Get the sink diagnostics were passed on to.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: ErrorSink> ErrorSink for FailFast<S> {
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        let severity = diagnostic.severity;
+        self.inner.report(diagnostic)?;
+        match severity {
+            Severity::Error => ControlFlow::Break(()),
+            _ => ControlFlow::Continue(()),
+        }
+    }
+}
+
+/// Prints each diagnostic to stderr as it's reported, rendered against its
+/// source file like [Diagnostic::render], and never stops.
+#[derive(Debug, Clone, Copy)]
+pub struct Stderr<'a> {
+    source: &'a SourceFile,
+}
+
+impl<'a> Stderr<'a> {
+    /// Create a sink printing diagnostics about `source`.
+    pub fn new(source: &'a SourceFile) -> Self {
+        Self { source }
+    }
+}
+
+impl ErrorSink for Stderr<'_> {
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        eprint!("{}", diagnostic.render(self.source));
+        ControlFlow::Continue(())
+    }
+}
+
+/// Converts each diagnostic to a Language Server Protocol diagnostic about
+/// its source file, collecting them to be published together, as a
+/// `textDocument/publishDiagnostics` notification replaces a document's
+/// diagnostics. Never stops.
+#[cfg(feature = "lsp")]
+#[derive(Debug, Clone)]
+pub struct LspSink<'a> {
+    source: &'a SourceFile,
+    diagnostics: Vec<crate::lsp::Diagnostic>,
+}
+
+#[cfg(feature = "lsp")]
+impl<'a> LspSink<'a> {
+    /// Create a sink converting diagnostics about `source`.
+    pub fn new(source: &'a SourceFile) -> Self {
+        Self {
+            source,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Take the diagnostics converted so far, to publish.
+    pub fn take_diagnostics(&mut self) -> Vec<crate::lsp::Diagnostic> {
+        std::mem::take(&mut self.diagnostics)
+    }
+}
+
+#[cfg(feature = "lsp")]
+impl ErrorSink for LspSink<'_> {
+    fn report(&mut self, diagnostic: Diagnostic) -> ControlFlow<()> {
+        self.diagnostics.push(diagnostic.to_lsp(self.source));
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::diagnostic::Diagnostic;
+    use crate::lex::{LexResult, Lexer};
+    use crate::parse::{ParseError, ParseResult, TokenQueue};
+    use crate::sink::{ErrorSink, FailFast};
+    use crate::span::{Span, Spanned};
+    use std::ops::ControlFlow;
+
+    fn lexer() -> Lexer<char> {
+        let mut lexer: Lexer<char> = Lexer::new();
+        lexer.add_rule(r"[a-z]", |m| {
+            LexResult::Token(m.as_str().chars().next().unwrap())
+        });
+        lexer.add_rule(r"[A-Z]", |m| {
+            let c = m.as_str().chars().next().unwrap();
+            LexResult::Warn(c.to_ascii_lowercase(), "Lowercased".into())
+        });
+        lexer.add_rule(r"\s+", |_| LexResult::Ignore);
+        lexer
+    }
+
+    /// Parses letters up to `z`, warning about each `b`.
+    fn parse_letters(tq: &TokenQueue<Spanned<char>>) -> ParseResult<usize> {
+        let mut tq = tq.clone();
+        let mut count = 0;
+        while tq.peek()?.value != 'z' {
+            let letter = tq.consume_cloned()?;
+            if letter.value == 'b' {
+                tq.warn(Diagnostic::warning("Found `b`", letter.span));
+            }
+            count += 1;
+        }
+        tq.consume_eq('z')?;
+        Ok((count, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_sinks() {
+        let lexer = lexer();
+
+        let mut all = Vec::new();
+        assert!(lexer.lex_reporting("A b ?", &mut all).is_none());
+        let messages: Vec<&str> = all
+            .iter()
+            .map(|diagnostic| diagnostic.message.as_str())
+            .collect();
+        assert!(messages == ["Lowercased", "Unmatched input at position 4!"]);
+        assert!(all[1].span == Span::new(4, 4));
+
+        // a warning doesn't stop a fail-fast sink
+        let mut sink = FailFast::new(Vec::new());
+        let matches = lexer.lex_reporting("AbZ", &mut sink).unwrap();
+        let mut tq = TokenQueue::from(
+            matches.into_iter().map(Spanned::from).collect::<Vec<_>>(),
+        );
+        assert!(tq.parse_reporting(parse_letters, &mut sink) == Some(2));
+        assert!(sink.into_inner().len() == 3);
+
+        let mut tq = TokenQueue::from(vec![
+            Spanned::new('b', Span::new(0, 1)),
+            Spanned::new('y', Span::new(2, 3)),
+        ]);
+        let mut all = Vec::new();
+        assert!(tq.parse_reporting(parse_letters, &mut all).is_none());
+        // warnings from the failed parse are discarded
+        assert!(all.len() == 1 && all[0].span == Span::new(3, 3));
+        let err =
+            ParseError::at(2, "Couldn't get token from empty TokenQueue!");
+        assert!(all[0].message == err.to_string());
+
+        // a sink can stop at a warning
+        struct NoWarnings;
+        impl ErrorSink for NoWarnings {
+            fn report(&mut self, _: Diagnostic) -> ControlFlow<()> {
+                ControlFlow::Break(())
+            }
+        }
+        assert!(lexer.lex_reporting("A", &mut NoWarnings).is_none());
+    }
+}
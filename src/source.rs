@@ -0,0 +1,287 @@
+use crate::span::Span;
+
+/// Which character sequences end a line in a [SourceFile], deciding how
+/// byte offsets map to lines and columns.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEndings {
+    /// Only `\n` ends a line, and `\r` is an ordinary character, counted in
+    /// columns.
+    Lf,
+    /// `\n` and `\r\n` end lines. The `\r` of a `\r\n` is part of the line
+    /// ending, so an offset at either of its bytes is at the end of the
+    /// line, not a column past it.
+    #[default]
+    LfOrCrLf,
+    /// `\n`, `\r\n` and a lone `\r` (classic Mac OS) end lines.
+    All,
+}
+
+/// Represents a named source text, with the line index needed to turn byte
+/// offsets into line and column numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    line_endings: LineEndings,
+    tab_width: usize,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    /// Create a source file named `name` containing `text`, with lines
+    /// ended by `\n` or `\r\n`.
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            text: text.into(),
+            line_endings: LineEndings::default(),
+            tab_width: 1,
+            line_starts: Vec::new(),
+        }
+        .with_line_endings(LineEndings::default())
+    }
+
+    /// Set which character sequences end lines.
+    pub fn with_line_endings(mut self, line_endings: LineEndings) -> Self {
+        let bytes = self.text.as_bytes();
+        let ends_line = |i: usize| match bytes[i] {
+            b'\n' => true,
+            b'\r' => {
+                line_endings == LineEndings::All
+                    && bytes.get(i + 1) != Some(&b'\n')
+            }
+            _ => false,
+        };
+        self.line_starts = vec![0];
+        self.line_starts
+            .extend((0..bytes.len()).filter(|i| ends_line(*i)).map(|i| i + 1));
+        self.line_endings = line_endings;
+        self
+    }
+
+    /// Get which character sequences end lines.
+    pub fn line_endings(&self) -> LineEndings {
+        self.line_endings
+    }
+
+    /// Set the tab stop width used by [SourceFile::line_col], so a tab
+    /// advances the column to the next multiple of `width` (plus one), as
+    /// most terminals show it. The default of 1 counts a tab as one column,
+    /// as most editors report it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is 0.
+    pub fn with_tab_width(mut self, width: usize) -> Self {
+        assert!(width > 0, "Tab width passed to SourceFile must be positive");
+        self.tab_width = width;
+        self
+    }
+
+    /// Get the tab stop width.
+    pub fn tab_width(&self) -> usize {
+        self.tab_width
+    }
+
+    /// Borrow the name of the file.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Borrow the text of the file.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Get the number of lines in the file.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Get the 0-based index of the line containing the byte `offset`.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        }
+    }
+
+    /// Get the 1-based (line, column) of the byte `offset`, counting columns
+    /// in characters, with tabs expanded to the tab width (see
+    /// [SourceFile::with_tab_width]).
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_index(offset);
+        let col = self.before(line, offset).chars().fold(0, |col, c| match c {
+            '\t' => (col / self.tab_width + 1) * self.tab_width,
+            _ => col + 1,
+        });
+        (line + 1, col + 1)
+    }
+
+    /// Get the 1-based (line, column) of the byte `offset`, counting columns
+    /// in bytes, as some compilers and tools report them.
+    pub fn line_byte_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_index(offset);
+        (line + 1, self.before(line, offset).len() + 1)
+    }
+
+    /// Get the 0-based (line, column) of the byte `offset`, counting columns
+    /// in UTF-16 code units, as positions are counted by the Language Server
+    /// Protocol.
+    pub fn utf16_line_col(&self, offset: usize) -> (usize, usize) {
+        let line = self.line_index(offset);
+        (line, self.before(line, offset).encode_utf16().count())
+    }
+
+    /// Borrow the text of the 0-based line `line` before the byte `offset`,
+    /// leaving out the `\r` of a `\r\n` line ending.
+    fn before(&self, line: usize, offset: usize) -> &str {
+        let start = self.line_starts[line];
+        let offset = offset.min(self.text.len());
+        let before = &self.text[start..offset];
+        match self.line_endings {
+            LineEndings::Lf => before,
+            _ if self.text[offset..].starts_with('\n') => {
+                before.strip_suffix('\r').unwrap_or(before)
+            }
+            _ => before,
+        }
+    }
+
+    /// Borrow the text of the 0-based line `line`, without its line ending.
+    pub fn line(&self, line: usize) -> &str {
+        let start = self.line_starts[line];
+        let end = self
+            .line_starts
+            .get(line + 1)
+            .map_or(self.text.len(), |next| next - 1);
+        self.text[start..end].trim_end_matches('\r')
+    }
+}
+
+/// Text with its line endings normalized to `\n`, so tokens lexed from it
+/// never contain a `\r`, that maps spans back into the original text.
+///
+/// ```
+/// use rlrl::source::{NormalizedText, SourceFile};
+/// use rlrl::span::Span;
+///
+/// let original = "a\r\nbc";
+/// let normalized = NormalizedText::new(original);
+/// assert!(normalized.text() == "a\nbc");
+/// // `bc` is at 2..4 in the normalized text
+/// let span = normalized.original_span(Span::new(2, 4));
+/// assert!(span == Span::new(3, 5));
+/// assert!(SourceFile::new("x", original).line_col(span.start) == (2, 1));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NormalizedText {
+    text: String,
+    /// Offsets into `text` each removed `\r` was just before, in order.
+    removed: Vec<usize>,
+}
+
+impl NormalizedText {
+    /// Normalize the line endings of `s`, replacing each `\r\n` and lone
+    /// `\r` with `\n`.
+    pub fn new(s: &str) -> Self {
+        let mut text = String::with_capacity(s.len());
+        let mut removed = Vec::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '\r' if chars.peek() == Some(&'\n') => removed.push(text.len()),
+                '\r' => text.push('\n'),
+                c => text.push(c),
+            }
+        }
+        Self { text, removed }
+    }
+
+    /// Borrow the normalized text.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Map the byte offset `offset` into the normalized text to the original
+    /// text. An offset where a `\r` was removed maps to after it if
+    /// `after_cr`, else before it.
+    fn original_offset(&self, offset: usize, after_cr: bool) -> usize {
+        let shift = match after_cr {
+            true => self.removed.partition_point(|pos| *pos <= offset),
+            false => self.removed.partition_point(|pos| *pos < offset),
+        };
+        offset + shift
+    }
+
+    /// Map the span `span` of the normalized text to the original text. A
+    /// span starting at a `\n` that replaced a `\r\n` starts at the `\n`,
+    /// and one ending there ends before the `\r`.
+    pub fn original_span(&self, span: Span) -> Span {
+        Span::new(
+            self.original_offset(span.start, true),
+            self.original_offset(span.end, span.is_empty()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::source::{LineEndings, NormalizedText, SourceFile};
+    use crate::span::Span;
+
+    #[test]
+    fn test_line_col() {
+        let source = SourceFile::new("test", "ab\r\ncdé\n\nf");
+
+        assert!(source.line_count() == 4);
+        assert!(source.line_col(0) == (1, 1));
+        assert!(source.line_col(4) == (2, 1));
+        assert!(source.line_col(8) == (2, 4));
+        assert!(source.line_col(10) == (4, 1));
+        assert!(source.line(0) == "ab");
+        assert!(source.line(1) == "cdé");
+        assert!(source.line(2).is_empty());
+        assert!(source.utf16_line_col(8) == (1, 3));
+        assert!(source.utf16_line_col(10) == (3, 0));
+    }
+
+    #[test]
+    fn test_line_endings() {
+        let text = "ab\r\nc\rd";
+        let source = SourceFile::new("test", text);
+        assert!(source.line_count() == 2);
+        assert!(source.line_col(2) == (1, 3) && source.line_col(3) == (1, 3));
+        assert!(source.line_col(6) == (2, 3));
+
+        let source = source.with_line_endings(LineEndings::Lf);
+        assert!(source.line_col(3) == (1, 4));
+        assert!(source.line_col(6) == (2, 3));
+
+        let source = source.with_line_endings(LineEndings::All);
+        assert!(source.line_count() == 3);
+        assert!(source.line_col(6) == (3, 1));
+        assert!(source.line(1) == "c");
+
+        let normalized = NormalizedText::new(text);
+        assert!(normalized.text() == "ab\nc\nd");
+        assert!(normalized.original_span(Span::new(2, 3)) == Span::new(3, 4));
+        assert!(normalized.original_span(Span::new(0, 2)) == Span::new(0, 2));
+        assert!(normalized.original_span(Span::new(5, 6)) == Span::new(6, 7));
+        assert!(normalized.original_span(Span::new(2, 2)) == Span::new(3, 3));
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let source = SourceFile::new("test", "\tab\té");
+        assert!(source.line_col(4) == (1, 5));
+        assert!(source.line_byte_col(6) == (1, 7));
+
+        let source = source.with_tab_width(4);
+        assert!(source.line_col(1) == (1, 5));
+        assert!(source.line_col(4) == (1, 9) && source.line_col(6) == (1, 10));
+        assert!(source.line_byte_col(4) == (1, 5));
+        assert!(source.utf16_line_col(4) == (0, 4));
+    }
+}
@@ -0,0 +1,225 @@
+use std::cmp::{max, min};
+use std::fmt::Display;
+use std::ops::{Deref, Range};
+
+/// Represents the half-open range `start..end` of positions in the input.
+///
+/// Positions are byte offsets for spans produced by the lexer, and token
+/// indices for spans produced by the parsers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Create the span `start..end`.
+    pub fn new(start: usize, end: usize) -> Self {
+        debug_assert!(start <= end, "Span start must not exceed its end!");
+        Self { start, end }
+    }
+
+    /// Get the number of positions covered by the span.
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    /// Return true if the span covers no positions.
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Get the smallest span covering both this span and `other`.
+    pub fn merge(&self, other: Span) -> Span {
+        Span::new(min(self.start, other.start), max(self.end, other.end))
+    }
+
+    /// Return true if the position `pos` is inside the span.
+    pub fn contains(&self, pos: usize) -> bool {
+        self.start <= pos && pos < self.end
+    }
+
+    /// Return true if every position in `other` is inside the span.
+    pub fn contains_span(&self, other: Span) -> bool {
+        self.start <= other.start && other.end <= self.end
+    }
+
+    /// Get the positions covered by both this span and `other`, or `None` if
+    /// the spans don't overlap.
+    pub fn intersect(&self, other: Span) -> Option<Span> {
+        let start = max(self.start, other.start);
+        let end = min(self.end, other.end);
+        match start < end {
+            true => Some(Span::new(start, end)),
+            false => None,
+        }
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl From<Range<usize>> for Span {
+    fn from(value: Range<usize>) -> Self {
+        Span::new(value.start, value.end)
+    }
+}
+
+impl From<Span> for Range<usize> {
+    fn from(value: Span) -> Self {
+        value.start..value.end
+    }
+}
+
+/// Wrapper pairing a value of type `T` with the [Span] it was produced from.
+///
+/// `Spanned<T>` derefs to `T` and compares equal to a bare `T` by value alone,
+/// so parsers written against `T` keep working over spanned tokens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Spanned<T> {
+    pub value: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    /// Wrap `value` with the span `span`.
+    pub fn new(value: T, span: Span) -> Self {
+        Self { value, span }
+    }
+
+    /// Apply `f` to the wrapped value, keeping the span.
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> Spanned<U> {
+        Spanned::new(f(self.value), self.span)
+    }
+
+    /// Borrow the wrapped value, keeping the span.
+    pub fn as_ref(&self) -> Spanned<&T> {
+        Spanned::new(&self.value, self.span)
+    }
+
+    /// Take the wrapped value, discarding the span.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+/// Implemented by tokens that know the span of the input they came from, so
+/// parse errors can be reported at a position in the input and not just at
+/// a token index. The input needn't be text: a token could be an instruction
+/// or a protocol frame, spanning its bytes in a binary input.
+pub trait HasSpan {
+    /// Get the span of the input the token came from.
+    fn span(&self) -> Span;
+}
+
+impl<T> HasSpan for Spanned<T> {
+    fn span(&self) -> Span {
+        self.span
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.value
+    }
+}
+
+impl<T: PartialEq> PartialEq<T> for Spanned<T> {
+    fn eq(&self, other: &T) -> bool {
+        &self.value == other
+    }
+}
+
+impl<T: Display> Display for Spanned<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.value.fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{ParseResult, TokenQueue};
+    use crate::span::{Span, Spanned};
+    use std::ops::Range;
+
+    #[test]
+    fn test_span() {
+        let a = Span::new(2, 5);
+        let b = Span::from(4..9);
+
+        assert!(a.merge(b) == Span::new(2, 9));
+        assert!(a.intersect(b) == Some(Span::new(4, 5)));
+        assert!(a.intersect(Span::new(5, 6)).is_none());
+        assert!(a.contains(4) && !a.contains(5));
+        assert!(a.merge(b).contains_span(a) && !a.contains_span(b));
+        assert!(a.to_string() == "2..5");
+        assert!(Range::from(b) == (4..9));
+        assert!(a.len() == 3 && Span::new(3, 3).is_empty());
+    }
+
+    #[test]
+    fn test_spanned() {
+        let spanned = Spanned::new(4, Span::new(1, 2));
+
+        assert!(spanned == 4);
+        assert!(spanned != Spanned::new(4, Span::new(0, 2)));
+        assert!(*spanned + 1 == 5);
+        assert!(spanned.map(|val| val * 2) == Spanned::new(8, spanned.span));
+    }
+
+    #[test]
+    fn test_spanned_queue() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec![
+            Spanned::new('a', Span::new(0, 1)),
+            Spanned::new('b', Span::new(2, 3)),
+        ]);
+
+        tq.consume_eq('a')?;
+        assert!(tq.prev_span()? == Span::new(0, 1));
+        assert!(tq.peek_span()? == Span::new(2, 3));
+        assert!(tq.consume_eq('a').is_err());
+        assert!(tq.consume()?.is_ascii_lowercase());
+
+        Ok(())
+    }
+
+    fn parse_pair(tq: &TokenQueue<Spanned<char>>) -> ParseResult<(char, char)> {
+        let mut tq = tq.clone();
+
+        let first = **tq.consume()?;
+        let second = **tq.consume()?;
+
+        Ok(((first, second), tq.get_idx()))
+    }
+
+    fn parse_nothing(tq: &TokenQueue<Spanned<char>>) -> ParseResult<()> {
+        Ok(((), tq.get_idx()))
+    }
+
+    #[test]
+    fn test_parse_spanned() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec![
+            Spanned::new('a', Span::new(0, 1)),
+            Spanned::new('b', Span::new(2, 3)),
+            Spanned::new('c', Span::new(5, 7)),
+        ]);
+
+        let empty = tq.parse_spanned(parse_nothing)?;
+        assert!(empty.span == Span::new(0, 0));
+
+        tq.increment()?;
+        let pair = tq.parse_spanned(parse_pair)?;
+        assert!(pair == ('b', 'c'));
+        assert!(pair.span == Span::new(2, 7));
+
+        let empty = tq.parse_spanned(parse_nothing)?;
+        assert!(empty.span == Span::new(7, 7));
+
+        Ok(())
+    }
+}
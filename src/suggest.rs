@@ -0,0 +1,134 @@
+//! Suggestions for unknown names, like a misspelled keyword or an undeclared
+//! variable, ranked by edit distance to the names that are known.
+//!
+//! ```
+//! use rlrl::suggest::{did_you_mean, suggest};
+//!
+//! let known = ["length", "len", "lens", "width"];
+//! assert!(suggest("lenght", known) == ["length"]);
+//! assert!(suggest("lenz", known) == ["len", "lens"]);
+//! assert!(
+//!     did_you_mean("lenz", known).unwrap() == "did you mean `len` or `lens`?"
+//! );
+//! ```
+
+use std::collections::HashSet;
+
+/// The most suggestions [did_you_mean] lists.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Get the number of single character insertions, deletions,
+/// substitutions, and swaps of adjacent characters needed to turn `a` into
+/// `b`, with no character edited twice.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // `distances[i][j]` is the distance between `a[..i]` and `b[..j]`
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution =
+                distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = substitution
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+/// Rank the `candidates` close enough to `word` to be what was meant,
+/// closest first, with ties in the order given. A candidate is close enough
+/// if it's within a third of the word's length in edits (and at least one),
+/// or differs from it only in case. Duplicates and `word` itself are left
+/// out, so keywords and declared names can be chained together.
+pub fn suggest<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    let max = (word.chars().count() / 3).max(1);
+    let mut seen = HashSet::new();
+    let mut ranked: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != word && seen.insert(*candidate))
+        .filter_map(|candidate| {
+            let distance = match candidate.to_lowercase() == word.to_lowercase()
+            {
+                true => 0,
+                false => edit_distance(word, candidate),
+            };
+            (distance <= max).then_some((distance, candidate))
+        })
+        .collect();
+    // stable, so ties keep the order given
+    ranked.sort_by_key(|(distance, _)| *distance);
+    ranked.into_iter().map(|(_, candidate)| candidate).collect()
+}
+
+/// Write a note suggesting the closest few `candidates` to `word`, like
+/// "did you mean `len` or `lens`?", for a
+/// [Diagnostic](crate::diagnostic::Diagnostic) or error message. Returns
+/// `None` if none are close enough.
+pub fn did_you_mean<'a>(
+    word: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<String> {
+    let suggestions: Vec<String> = suggest(word, candidates)
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|suggestion| format!("`{suggestion}`"))
+        .collect();
+    match suggestions.as_slice() {
+        [] => None,
+        [only] => Some(format!("did you mean {only}?")),
+        [rest @ .., last] => {
+            Some(format!("did you mean {} or {last}?", rest.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::suggest::{did_you_mean, edit_distance, suggest};
+
+    #[test]
+    fn test_edit_distance() {
+        assert!(edit_distance("kitten", "sitting") == 3);
+        assert!(edit_distance("", "ab") == 2 && edit_distance("ab", "") == 2);
+        assert!(edit_distance("é", "e") == 1);
+        assert!(edit_distance("ab", "ba") == 1);
+    }
+
+    #[test]
+    fn test_suggest() {
+        let keywords = ["let", "fn", "return"];
+        let declared = ["counter", "count", "let"];
+        let known = keywords.into_iter().chain(declared);
+
+        assert!(suggest("coutn", known.clone()) == ["count"]);
+        assert!(suggest("countre", known.clone()) == ["counter", "count"]);
+        assert!(suggest("Return", known.clone()) == ["return"]);
+        assert!(suggest("xyz", known.clone()).is_empty());
+        assert!(suggest("lt", known.clone()) == ["let"]);
+
+        assert!(did_you_mean("xyz", known.clone()).is_none());
+        assert!(
+            did_you_mean("cuonter", known).unwrap()
+                == "did you mean `counter`?"
+        );
+        assert!(
+            did_you_mean("a", ["b", "c", "d", "e"]).unwrap()
+                == "did you mean `b`, `c` or `d`?"
+        );
+    }
+}
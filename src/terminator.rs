@@ -0,0 +1,194 @@
+use crate::span::{Span, Spanned};
+
+/// Inserts terminator tokens at the ends of lines, like the automatic
+/// semicolons of Go, so grammars can require a terminator after every
+/// statement while programs leave them out.
+///
+/// A terminator is inserted after a token if it can end a statement, and
+/// either a line break follows it before the next token, or it's the last
+/// token. Line breaks are found in the source text between the tokens, so
+/// ignored input like comments spanning lines counts as a line break.
+///
+/// ```
+/// use rlrl::span::{Span, Spanned};
+/// use rlrl::terminator::TerminatorInsertion;
+///
+/// #[derive(Debug, Clone, PartialEq)]
+/// enum Token {
+///     Ident,
+///     Plus,
+///     Semi,
+/// }
+///
+/// let source = "a +\nb\nc";
+/// let tokens = vec![
+///     Spanned::new(Token::Ident, Span::new(0, 1)),
+///     Spanned::new(Token::Plus, Span::new(2, 3)),
+///     Spanned::new(Token::Ident, Span::new(4, 5)),
+///     Spanned::new(Token::Ident, Span::new(6, 7)),
+/// ];
+///
+/// let insertion =
+///     TerminatorInsertion::new(Token::Semi, |token| *token == Token::Ident);
+/// let tokens = insertion.insert(source, tokens);
+/// let kinds: Vec<Token> =
+///     tokens.into_iter().map(Spanned::into_inner).collect();
+/// assert!(
+///     kinds
+///         == [
+///             Token::Ident,
+///             Token::Plus,
+///             Token::Ident,
+///             Token::Semi,
+///             Token::Ident,
+///             Token::Semi,
+///         ]
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct TerminatorInsertion<T> {
+    terminator: T,
+    ends_statement: fn(&T) -> bool,
+    continues_line: Option<fn(&T) -> bool>,
+    at_end_of_input: bool,
+}
+
+impl<T> TerminatorInsertion<T> {
+    /// Create a pass inserting `terminator` after tokens for which
+    /// `ends_statement` returns true, like identifiers, literals, and closing
+    /// brackets.
+    pub fn new(terminator: T, ends_statement: fn(&T) -> bool) -> Self {
+        Self {
+            terminator,
+            ends_statement,
+            continues_line: None,
+            at_end_of_input: true,
+        }
+    }
+
+    /// Don't insert a terminator before tokens for which `continues_line`
+    /// returns true, so a line starting with one continues the statement on
+    /// the line before, like a leading `.` in a chain of method calls.
+    pub fn with_continuation(mut self, continues_line: fn(&T) -> bool) -> Self {
+        self.continues_line = Some(continues_line);
+        self
+    }
+
+    /// Set whether a terminator is inserted after the last token if it can
+    /// end a statement. Defaults to true.
+    pub fn with_end_of_input(mut self, at_end_of_input: bool) -> Self {
+        self.at_end_of_input = at_end_of_input;
+        self
+    }
+}
+
+impl<T: Clone> TerminatorInsertion<T> {
+    /// Insert terminators into `tokens`, lexed from `source`. Each
+    /// terminator has an empty span at the end of the token it follows.
+    /// Tokens whose spans overlap are on the same line.
+    pub fn insert(
+        &self,
+        source: &str,
+        tokens: Vec<Spanned<T>>,
+    ) -> Vec<Spanned<T>> {
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut tokens = tokens.into_iter().peekable();
+        while let Some(token) = tokens.next() {
+            let end = token.span.end;
+            let terminate = (self.ends_statement)(&token.value)
+                && match tokens.peek() {
+                    Some(next) => {
+                        // overlapping or inserted tokens have no gap
+                        source
+                            .get(end..next.span.start)
+                            .is_some_and(|gap| gap.contains('\n'))
+                            && !self
+                                .continues_line
+                                .is_some_and(|continues| continues(&next.value))
+                    }
+                    None => self.at_end_of_input,
+                };
+            out.push(token);
+            if terminate {
+                out.push(Spanned::new(
+                    self.terminator.clone(),
+                    Span::new(end, end),
+                ));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::span::{Span, Spanned};
+    use crate::terminator::TerminatorInsertion;
+
+    #[derive(Debug, Clone, PartialEq)]
+    enum Token {
+        Ident,
+        Return,
+        RBrace,
+        Dot,
+        Semi,
+    }
+
+    fn lex(source: &str) -> Vec<Spanned<Token>> {
+        let mut tokens = Vec::new();
+        for (i, c) in source.char_indices() {
+            let token = match c {
+                'r' => Token::Return,
+                'a'..='z' => Token::Ident,
+                '}' => Token::RBrace,
+                '.' => Token::Dot,
+                ';' => Token::Semi,
+                _ => continue,
+            };
+            tokens.push(Spanned::new(token, Span::new(i, i + 1)));
+        }
+        tokens
+    }
+
+    fn ends_statement(token: &Token) -> bool {
+        matches!(token, Token::Ident | Token::Return | Token::RBrace)
+    }
+
+    #[test]
+    fn test_insert() {
+        let insertion = TerminatorInsertion::new(Token::Semi, ends_statement)
+            .with_continuation(|token| *token == Token::Dot);
+
+        // `# 1` is a comment the lexer ignores
+        let source = "a;\nb # 1\n  .d\nr\n}";
+        let tokens = insertion.insert(source, lex(source));
+        assert!(
+            tokens
+                == [
+                    Spanned::new(Token::Ident, Span::new(0, 1)),
+                    Spanned::new(Token::Semi, Span::new(1, 2)),
+                    Spanned::new(Token::Ident, Span::new(3, 4)),
+                    Spanned::new(Token::Dot, Span::new(11, 12)),
+                    Spanned::new(Token::Ident, Span::new(12, 13)),
+                    Spanned::new(Token::Semi, Span::new(13, 13)),
+                    Spanned::new(Token::Return, Span::new(14, 15)),
+                    Spanned::new(Token::Semi, Span::new(15, 15)),
+                    Spanned::new(Token::RBrace, Span::new(16, 17)),
+                    Spanned::new(Token::Semi, Span::new(17, 17)),
+                ]
+        );
+
+        // a block comment spanning lines is a line break
+        let insertion = insertion.with_end_of_input(false);
+        let source = "a /*\n*/ b";
+        let tokens = insertion.insert(source, lex(source));
+        assert!(tokens.len() == 3 && tokens[1] == Token::Semi);
+
+        // overlapping spans, as from a token split in two, don't panic
+        let tokens = vec![
+            Spanned::new(Token::Ident, Span::new(0, 2)),
+            Spanned::new(Token::Ident, Span::new(1, 2)),
+        ];
+        assert!(insertion.insert("ab", tokens).len() == 2);
+    }
+}
@@ -0,0 +1,116 @@
+//! The [Token] trait, bundling what the toolkit can do with a token type
+//! beyond storing it.
+//!
+//! Types and `impl` blocks in this crate don't require bounds of tokens:
+//! a [Lexer](crate::lex::Lexer) or [TokenQueue] holds any type, and each
+//! method states only the bounds it uses, like `T: PartialEq<U>` for
+//! [TokenQueue::consume_eq] or `T: Clone` for [TokenQueue::consume_cloned].
+//! So a missing bound is reported at the call that needs it, not deep in
+//! generic code. Implementing [Token] gives the methods that classify tokens
+//! by kind, and makes sure the type is `Debug`, for error messages.
+
+use crate::parse::{ParseError, Result, TokenQueue};
+use crate::span::Spanned;
+use std::fmt::Debug;
+
+/// Implemented by token types that can be classified by kind, like an enum
+/// whose variants carry payloads, where the kind is a fieldless copy of the
+/// variant.
+///
+/// ```
+/// use rlrl::parse::TokenQueue;
+/// use rlrl::token::Token;
+///
+/// #[derive(Debug)]
+/// enum Tok {
+///     Num(i64),
+///     Plus,
+/// }
+///
+/// #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// enum Kind {
+///     Num,
+///     Plus,
+/// }
+///
+/// impl Token for Tok {
+///     type Kind = Kind;
+///
+///     fn kind(&self) -> Kind {
+///         match self {
+///             Tok::Num(_) => Kind::Num,
+///             Tok::Plus => Kind::Plus,
+///         }
+///     }
+/// }
+///
+/// let mut tq = TokenQueue::from(vec![Tok::Num(1), Tok::Plus]);
+/// assert!(matches!(tq.consume_kind(Kind::Num), Ok(Tok::Num(1))));
+/// assert!(tq.at_kind(Kind::Plus));
+/// ```
+pub trait Token: Debug {
+    /// The kinds of token, compared to classify tokens.
+    type Kind: Copy + Eq + Debug;
+
+    /// Get the kind of the token.
+    fn kind(&self) -> Self::Kind;
+}
+
+impl<T: Token> Token for Spanned<T> {
+    type Kind = T::Kind;
+
+    fn kind(&self) -> Self::Kind {
+        self.value.kind()
+    }
+}
+
+/// Each character is its own kind, for parsing straight from characters.
+impl Token for char {
+    type Kind = char;
+
+    fn kind(&self) -> Self::Kind {
+        *self
+    }
+}
+
+impl<T: Token> TokenQueue<T> {
+    /// Return true if the front token is of the kind `kind`.
+    pub fn at_kind(&self, kind: T::Kind) -> bool {
+        self.peek().is_ok_and(|token| token.kind() == kind)
+    }
+
+    /// Consume the front token if it's of the kind `kind`, returning it,
+    /// otherwise return an error saying `kind` was expected.
+    pub fn consume_kind(&mut self, kind: T::Kind) -> Result<&T> {
+        if !self.at_kind(kind) {
+            return Err(ParseError::at(
+                self.get_idx(),
+                format!("Expected {kind:?}!"),
+            ));
+        }
+        self.consume()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::TokenQueue;
+    use crate::span::{Span, Spanned};
+
+    #[test]
+    fn test_kinds() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec![
+            Spanned::new('a', Span::new(0, 1)),
+            Spanned::new('b', Span::new(1, 2)),
+        ]);
+
+        assert!(tq.at_kind('a') && !tq.at_kind('b'));
+        assert!(tq.consume_kind('a')?.span == Span::new(0, 1));
+        let err = tq.consume_kind('a').unwrap_err();
+        assert!(err.message() == "Expected 'a'!" && err.idx() == Some(1));
+        tq.consume_kind('b')?;
+        assert!(!tq.at_kind('b'));
+
+        Ok(())
+    }
+}
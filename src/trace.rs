@@ -0,0 +1,168 @@
+//! A log of what a parser did, recorded by a
+//! [TokenQueue](crate::parse::TokenQueue) created
+//! [with tracing](crate::parse::TokenQueue::with_trace), for finding out after the fact
+//! why an input parsed the way it did. Unlike the `tracing` feature, it
+//! needs no subscriber, and records every token peeked and consumed.
+//!
+//! ```
+//! use rlrl::parse::{ParseResult, TokenQueue};
+//!
+//! fn parse_a(tq: &TokenQueue<char>) -> ParseResult<()> {
+//!     let mut tq = tq.clone();
+//!     tq.consume_eq('a')?;
+//!     Ok(((), tq.get_idx()))
+//! }
+//!
+//! let mut tq = TokenQueue::from(vec!['a', 'b']).with_trace();
+//! tq.parse(parse_a).unwrap();
+//! assert!(tq.parse(parse_a).is_err());
+//! assert!(
+//!     tq.take_trace().to_string()
+//!         == "parse () at 0\n  \
+//!               peek 0\n  \
+//!               consume 0\n\
+//!             succeeded, ending at 1\n\
+//!             parse () at 1\n  \
+//!               peek 1\n\
+//!             failed at 1: Expected `'a'` but found `'b'`!\n"
+//! );
+//! ```
+
+use std::fmt::Display;
+
+/// Something a parser did, at the number of parse driver calls in progress
+/// when it happened.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseEvent {
+    pub depth: usize,
+    pub kind: EventKind,
+}
+
+/// The kinds of [ParseEvent].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EventKind {
+    /// A parse driver call started parsing a value of the type named `node`
+    /// at token index `idx`.
+    Enter { node: &'static str, idx: usize },
+    /// The parse driver call last entered succeeded, ending at token index
+    /// `end`.
+    Succeed { end: usize },
+    /// The parse driver call last entered failed, with an error at token
+    /// index `idx`, if known.
+    Fail { idx: Option<usize>, message: String },
+    /// The token at index `idx` was peeked at.
+    Peek { idx: usize },
+    /// The token at index `idx` was consumed.
+    Consume { idx: usize },
+    /// The queue moved back from token index `from` to `to`.
+    Backtrack { from: usize, to: usize },
+}
+
+/// The events recorded while parsing, in order. Displays as an indented
+/// trace, a line per event, with the events inside each parse driver call
+/// indented under it.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ParseTrace {
+    events: Vec<ParseEvent>,
+}
+
+impl ParseTrace {
+    /// Create a trace of `events`.
+    pub fn new(events: Vec<ParseEvent>) -> Self {
+        Self { events }
+    }
+
+    /// Borrow the events, in order.
+    pub fn events(&self) -> &[ParseEvent] {
+        &self.events
+    }
+}
+
+impl Display for ParseTrace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // indent relative to the first event, which may not be at the top
+        // level
+        let base = self.events.first().map_or(0, |event| event.depth);
+        for event in &self.events {
+            let indent = "  ".repeat(event.depth.saturating_sub(base));
+            write!(f, "{indent}")?;
+            match &event.kind {
+                EventKind::Enter { node, idx } => {
+                    writeln!(f, "parse {node} at {idx}")?
+                }
+                EventKind::Succeed { end } => {
+                    writeln!(f, "succeeded, ending at {end}")?
+                }
+                EventKind::Fail {
+                    idx: Some(idx),
+                    message,
+                } => writeln!(f, "failed at {idx}: {message}")?,
+                EventKind::Fail { idx: None, message } => {
+                    writeln!(f, "failed: {message}")?
+                }
+                EventKind::Peek { idx } => writeln!(f, "peek {idx}")?,
+                EventKind::Consume { idx } => writeln!(f, "consume {idx}")?,
+                EventKind::Backtrack { from, to } => {
+                    writeln!(f, "backtrack from {from} to {to}")?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse::{ParseResult, TokenQueue};
+    use crate::trace::{EventKind, ParseEvent};
+
+    /// Parses `(` ... `)` nested to any depth.
+    fn parse_nested(tq: &TokenQueue<char>) -> ParseResult<usize> {
+        let mut tq = tq.clone();
+
+        if tq.consume_eq('(').is_err() {
+            return Ok((0, tq.get_idx()));
+        }
+        let depth = tq.parse(parse_nested)?;
+        tq.consume_eq(')')?;
+
+        Ok((depth + 1, tq.get_idx()))
+    }
+
+    #[test]
+    fn test_trace() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec!['(', ')', 'x']).with_trace();
+        tq.parse(parse_nested)?;
+        tq.go_to(0)?;
+
+        let trace = tq.take_trace();
+        assert!(
+            trace.to_string()
+                == "parse usize at 0\n  \
+                      peek 0\n  \
+                      consume 0\n  \
+                      parse usize at 1\n    \
+                        peek 1\n  \
+                      succeeded, ending at 1\n  \
+                      peek 1\n  \
+                      consume 1\n\
+                    succeeded, ending at 2\n\
+                    backtrack from 2 to 0\n"
+        );
+        assert!(
+            trace.events()[1]
+                == ParseEvent {
+                    depth: 1,
+                    kind: EventKind::Peek { idx: 0 }
+                }
+        );
+        assert!(tq.take_trace().events().is_empty());
+
+        // nothing is recorded without tracing
+        let mut tq = TokenQueue::from(vec!['(', ')']);
+        tq.parse(parse_nested)?;
+        assert!(tq.take_trace().events().is_empty());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::dynamic::DynamicToken;
+use crate::parse::{ParseError, Result, TokenQueue};
+use crate::span::Spanned;
+
+/// Implemented by token types to expose the values of literal tokens, so
+/// parse functions can consume numbers and strings with
+/// [TokenQueue::consume_int] and friends without matching on the concrete
+/// token type.
+///
+/// Every method returns `None` by default, except [TokenValue::as_float],
+/// which converts the integer value if there is one.
+pub trait TokenValue {
+    /// Borrow the token's string value, if it has one.
+    fn as_str(&self) -> Option<&str> {
+        None
+    }
+
+    /// Get the token's integer value, if it has one.
+    fn as_int(&self) -> Option<i64> {
+        None
+    }
+
+    /// Get the token's floating point value, if it has one.
+    fn as_float(&self) -> Option<f64> {
+        self.as_int().map(|int| int as f64)
+    }
+}
+
+impl<T: TokenValue> TokenValue for Spanned<T> {
+    fn as_str(&self) -> Option<&str> {
+        self.value.as_str()
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        self.value.as_int()
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        self.value.as_float()
+    }
+}
+
+/// A dynamic token's value is its text, parsed as a number where it's
+/// decimal digits, with an optional leading `-`, fraction, and exponent.
+/// Without a type to say which tokens are string literals, every token has
+/// a string value, so [TokenQueue::consume_str] consumes any token.
+impl TokenValue for DynamicToken {
+    fn as_str(&self) -> Option<&str> {
+        Some(&self.text)
+    }
+
+    fn as_int(&self) -> Option<i64> {
+        let digits = self.text.strip_prefix('-').unwrap_or(&self.text);
+        if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        self.text.parse().ok()
+    }
+
+    fn as_float(&self) -> Option<f64> {
+        // Rust also parses `inf`, `NaN`, and a leading `+`
+        let digits = self.text.strip_prefix('-').unwrap_or(&self.text);
+        if !digits.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            || !digits
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b))
+        {
+            return None;
+        }
+        self.text
+            .parse()
+            .ok()
+            .filter(|float: &f64| float.is_finite())
+    }
+}
+
+impl<T: TokenValue> TokenQueue<T> {
+    /// Consume the front token if it has an integer value, returning the
+    /// value, otherwise return an error.
+    pub fn consume_int(&mut self) -> Result<i64> {
+        self.consume_map("an integer", |token| token.as_int())
+    }
+
+    /// Consume the front token if it has a floating point value, returning
+    /// the value, otherwise return an error.
+    pub fn consume_float(&mut self) -> Result<f64> {
+        self.consume_map("a number", |token| token.as_float())
+    }
+
+    /// Consume the front token if it has a string value, returning the
+    /// value, otherwise return an error.
+    pub fn consume_str(&mut self) -> Result<&str> {
+        if self.peek()?.as_str().is_none() {
+            return Err(ParseError::at(self.get_idx(), "Expected a string!"));
+        }
+        self.increment()?;
+        Ok(self.prev()?.as_str().unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic::DynamicToken;
+    use crate::parse::TokenQueue;
+    use crate::span::Span;
+    use crate::value::TokenValue;
+
+    #[derive(Debug, PartialEq)]
+    enum Token {
+        Int(i64),
+        Float(f64),
+        Str(String),
+        Comma,
+    }
+
+    impl TokenValue for Token {
+        fn as_str(&self) -> Option<&str> {
+            match self {
+                Self::Str(s) => Some(s),
+                _ => None,
+            }
+        }
+
+        fn as_int(&self) -> Option<i64> {
+            match self {
+                Self::Int(int) => Some(*int),
+                _ => None,
+            }
+        }
+
+        fn as_float(&self) -> Option<f64> {
+            match self {
+                Self::Float(float) => Some(*float),
+                _ => self.as_int().map(|int| int as f64),
+            }
+        }
+    }
+
+    #[test]
+    fn test_consume_values() -> anyhow::Result<()> {
+        let mut tq = TokenQueue::from(vec![
+            Token::Int(1),
+            Token::Float(2.5),
+            Token::Int(3),
+            Token::Str("a".to_owned()),
+            Token::Comma,
+        ]);
+
+        assert!(tq.consume_int()? == 1);
+        assert!(tq.consume_int().is_err() && tq.get_idx() == 1);
+        assert!(tq.consume_float()? == 2.5);
+        assert!(tq.consume_float()? == 3.0);
+        assert!(tq.consume_str()? == "a");
+        let err = tq.consume_str().unwrap_err();
+        assert!(err.message() == "Expected a string!" && err.idx() == Some(4));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_dynamic_values() {
+        let token = |text: &str| DynamicToken {
+            kind: "t".into(),
+            text: text.to_owned(),
+            span: Span::new(0, text.len()),
+        };
+
+        assert!(token("-12").as_int() == Some(-12));
+        assert!(token("1.5e3").as_float() == Some(1500.0));
+        assert!(token("-.5").as_float() == Some(-0.5));
+        assert!(token("12").as_float() == Some(12.0));
+        for text in ["+1", "-", "", "1.5", "x1"] {
+            assert!(token(text).as_int().is_none(), "{text} is an int");
+        }
+        for text in ["inf", "-infinity", "NaN", "+1", "1e400", "e5", ""] {
+            assert!(token(text).as_float().is_none(), "{text} is a float");
+        }
+
+        // every token is a string
+        assert!(token("if").as_str() == Some("if"));
+        assert!(token("1").as_str() == Some("1"));
+    }
+}
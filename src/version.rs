@@ -0,0 +1,129 @@
+use crate::{Error, Result};
+use std::cmp::Ordering;
+use std::fmt::Display;
+
+/// A language version like `2.0` or `1.x`, for keeping the dialects of an
+/// evolving language in one lexer and grammar. See [VersionRange].
+///
+/// Versions compare component by component, with missing components
+/// counting as 0. An `x` component is a wildcard: as the lower bound of a
+/// [VersionRange] it counts as 0, and as the upper bound it matches any
+/// number, so a range until `1.x` includes `1.9` but not `2.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Version {
+    /// The components, with `None` for `x`.
+    parts: Vec<Option<u32>>,
+}
+
+impl Version {
+    /// Parse a version made of dot-separated numbers or `x`s, returning an
+    /// error if `s` isn't one.
+    pub fn parse(s: &str) -> Result<Self> {
+        let parts = s
+            .split('.')
+            .map(|part| match part {
+                "x" => Ok(None),
+                part => part.parse().map(Some),
+            })
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|_| Error::build(format!("Invalid version `{s}`!")))?;
+        Ok(Self { parts })
+    }
+
+    /// Compare the version to `other`, with `x` components of either counting
+    /// as `wildcard`.
+    fn compare(&self, other: &Version, wildcard: u32) -> Ordering {
+        let len = self.parts.len().max(other.parts.len());
+        let part = |version: &Version, i: usize| match version.parts.get(i) {
+            Some(Some(part)) => *part,
+            Some(None) => wildcard,
+            None => 0,
+        };
+        (0..len)
+            .map(|i| part(self, i).cmp(&part(other, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (i, part) in self.parts.iter().enumerate() {
+            if i > 0 {
+                write!(f, ".")?;
+            }
+            match part {
+                Some(part) => write!(f, "{part}")?,
+                None => write!(f, "x")?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The versions of a language a lexer rule or grammar production is part
+/// of. The default range includes every version.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct VersionRange {
+    since: Option<Version>,
+    until: Option<Version>,
+}
+
+impl VersionRange {
+    /// Include only versions from `since` on.
+    pub fn with_since(mut self, since: Version) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Include only versions up to and including `until`.
+    pub fn with_until(mut self, until: Version) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Return true if `version` is in the range.
+    pub fn contains(&self, version: &Version) -> bool {
+        self.since
+            .as_ref()
+            .is_none_or(|since| version.compare(since, 0).is_ge())
+            && self
+                .until
+                .as_ref()
+                .is_none_or(|until| version.compare(until, u32::MAX).is_le())
+    }
+}
+
+impl Display for VersionRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.since, &self.until) {
+            (None, None) => write!(f, "all versions"),
+            (Some(since), None) => write!(f, "since {since}"),
+            (None, Some(until)) => write!(f, "until {until}"),
+            (Some(since), Some(until)) => write!(f, "{since} to {until}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::version::{Version, VersionRange};
+
+    #[test]
+    fn test_version_range() -> anyhow::Result<()> {
+        let v = |s| Version::parse(s).unwrap();
+
+        let range = VersionRange::default().with_until(v("1.x"));
+        assert!(range.contains(&v("1")) && range.contains(&v("1.9.3")));
+        assert!(!range.contains(&v("2.0")));
+
+        let range = range.with_since(v("1.2"));
+        assert!(range.contains(&v("1.2.0")) && !range.contains(&v("1.1.9")));
+        assert!(range.to_string() == "1.2 to 1.x");
+        assert!(VersionRange::default().contains(&v("0.1")));
+
+        assert!(Version::parse("2.y").is_err());
+
+        Ok(())
+    }
+}